@@ -0,0 +1,400 @@
+use crate::error;
+use crate::importer::{ForeignAccount, HistoryEntry, HistoryImport, Leg, Target};
+use crate::journal;
+use crate::utils;
+use crate::walletdb;
+
+use std::collections::HashMap;
+
+struct Posting {
+    account: String,
+    amount: Option<(i64, String)>
+}
+
+struct Entry {
+    line: usize,
+    date: i64,
+    charged: bool,
+    payee: Option<String>,
+    narration: String,
+    tags: Vec<String>,
+    postings: Vec<Posting>,
+    // Why the entry can't be imported, found while reading it.
+    problem: Option<String>
+}
+
+// Account, cents and currency of a posting once the entry is balanced.
+type BalancedPosting = (String, i64, String);
+
+#[derive(Default)]
+struct Journal {
+    entries: Vec<Entry>,
+    // Currency given in the 'open' directive of each account.
+    currencies: HashMap<String, String>,
+    skipped: Vec<(usize, String)>
+}
+
+// Wallet 'import beancount' subcommand is defined below.
+// Asset and liability accounts become wallet accounts, the other accounts of
+// an entry give the category of the transaction.
+pub fn import_beancount(path: &str, dry_run: bool) -> error::Result<()> {
+    let content = String::from_utf8_lossy(&utils::read_file(path)?).to_string();
+
+    let journal_file = read_journal(&content);
+
+    let mut conn = walletdb::open_database()?;
+
+    let tx = conn.transaction()?;
+    let id_operation = journal::begin(&tx)?;
+
+    let mut import = HistoryImport::new(&tx, id_operation, "beancount");
+
+    for (line, reason) in &journal_file.skipped {
+        import.skip(&format!("line {}", line), reason);
+    }
+
+    for entry in &journal_file.entries {
+        let location = format!("line {}", entry.line);
+
+        match history_entry(entry, &journal_file.currencies) {
+            Ok(history_entry) => import.import_entry(&location, &history_entry)?,
+            Err(reason) => import.skip(&location, &reason)
+        }
+    }
+
+    import.finish(dry_run)?;
+
+    if dry_run {
+        return Ok(());
+    }
+
+    Ok(tx.commit()?)
+}
+
+fn history_entry(entry: &Entry, currencies: &HashMap<String, String>) -> Result<HistoryEntry, String> {
+    if let Some(problem) = &entry.problem {
+        return Err(problem.clone());
+    }
+
+    let mut legs: Vec<Leg> = Vec::new();
+
+    for (account, cents, posting_currency) in balanced_postings(entry)? {
+        let target = if account.starts_with("Assets:") || account.starts_with("Liabilities:") {
+            let account_currency = currencies.get(&account).unwrap_or(&posting_currency);
+
+            if *account_currency != posting_currency {
+                return Err(format!(
+                    "'{}' is in {} but the posting is in {}", account, account_currency, posting_currency
+                ));
+            }
+
+            Target::Account(ForeignAccount {
+                name: wallet_account_name(&account),
+                credit: account.starts_with("Liabilities:"),
+                currency: Some(posting_currency),
+                key: account
+            })
+        } else if account.starts_with("Expenses:") || account.starts_with("Income:") {
+            Target::Category(Some(wallet_account_name(&account).to_lowercase()))
+        } else if account.starts_with("Equity:") && account.to_lowercase().contains("opening") {
+            Target::Opening
+        } else {
+            Target::Equity
+        };
+
+        legs.push(Leg { target, cents });
+    }
+
+    Ok(HistoryEntry {
+        date: entry.date,
+        charged: entry.charged,
+        payee: entry.payee.clone(),
+        message: entry.narration.clone(),
+        tags: if entry.tags.is_empty() { None } else { Some(entry.tags.join(",")) },
+        legs
+    })
+}
+
+// Fills the posting without amount, beancount allows one per entry.
+fn balanced_postings(entry: &Entry) -> Result<Vec<BalancedPosting>, String> {
+    let mut postings: Vec<BalancedPosting> = Vec::new();
+    let mut elided: Vec<&str> = Vec::new();
+
+    for posting in &entry.postings {
+        match &posting.amount {
+            Some((cents, currency)) => postings.push((posting.account.clone(), *cents, currency.clone())),
+            None => elided.push(&posting.account)
+        }
+    }
+
+    let mut currencies: Vec<&String> = postings.iter().map(|(_, _, currency)| currency).collect();
+    currencies.dedup();
+
+    if currencies.len() > 1 {
+        return Err("postings in several currencies".to_string());
+    }
+
+    let total: i64 = postings.iter().map(|(_, cents, _)| cents).sum();
+
+    match (elided.as_slice(), currencies.first()) {
+        ([], _) if total != 0 => Err(format!("postings don't balance by {:.2}", total as f64 / 100.0)),
+        ([], _) => Ok(postings),
+        ([account], Some(currency)) => {
+            let currency = currency.to_string();
+            postings.push((account.to_string(), -total, currency));
+            Ok(postings)
+        }
+        _ => Err("postings without amount can't be balanced".to_string())
+    }
+}
+
+// 'Assets:Bank:Checking' becomes the wallet account 'Bank:Checking'.
+fn wallet_account_name(name: &str) -> String {
+    name.split_once(':').map(|(_, rest)| rest).unwrap_or(name).to_string()
+}
+
+fn read_journal(content: &str) -> Journal {
+    let mut journal = Journal::default();
+    let mut pushed_tags: Vec<String> = Vec::new();
+
+    for (index, line) in content.lines().enumerate() {
+        let number = index + 1;
+        let trimmed = line.trim();
+
+        if trimmed.is_empty() || trimmed.starts_with(';') || line.starts_with('*') || line.starts_with('#') {
+            continue;
+        }
+
+        if line.starts_with(char::is_whitespace) {
+            if let Some(entry) = journal.entries.last_mut() {
+                if entry.line < number && trimmed.starts_with(|c: char| c.is_ascii_uppercase() || c == '!' || c == '*') {
+                    read_posting(entry, trimmed);
+                }
+            }
+            continue;
+        }
+
+        let tokens: Vec<&str> = trimmed.split_whitespace().collect();
+
+        match tokens.as_slice() {
+            ["pushtag", tag, ..] => pushed_tags.push(tag.trim_start_matches('#').to_string()),
+            ["poptag", tag, ..] => pushed_tags.retain(|t| t != tag.trim_start_matches('#')),
+            ["option", ..] | ["plugin", ..] => (),
+            [date, directive, rest @ ..] if utils::parse_date(date).is_some() => {
+                let date = utils::parse_date(date).unwrap();
+
+                match *directive {
+                    "*" | "!" | "txn" => {
+                        let header = trimmed.splitn(3, char::is_whitespace).nth(2).unwrap_or("");
+                        let mut entry = read_header(number, date, *directive != "!", header);
+                        entry.tags.extend(pushed_tags.iter().cloned());
+                        journal.entries.push(entry);
+                    }
+                    "open" => {
+                        if let (Some(account), Some(currency)) = (rest.first(), rest.get(1)) {
+                            let currency = currency.split(',').next().unwrap_or(currency);
+                            journal.currencies.insert(account.to_string(), currency.to_string());
+                        }
+                    }
+                    "close" | "commodity" => (),
+                    other => journal.skipped.push((number, format!("'{}' directives are not imported", other)))
+                }
+            }
+            _ => journal.skipped.push((number, format!("unknown line '{}'", trimmed)))
+        }
+    }
+
+    journal
+}
+
+fn read_header(line: usize, date: i64, charged: bool, header: &str) -> Entry {
+    let mut strings: Vec<String> = Vec::new();
+    let mut tags: Vec<String> = Vec::new();
+    let mut chars = header.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                let mut text = String::new();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => text.extend(chars.next()),
+                        '"' => break,
+                        c => text.push(c)
+                    }
+                }
+                strings.push(text);
+            }
+            '#' => {
+                let mut tag = String::new();
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                    tag.push(c);
+                }
+                tags.push(tag);
+            }
+            ';' => break,
+            _ => ()
+        }
+    }
+
+    let (payee, narration) = match strings.len() {
+        0 => (None, String::new()),
+        1 => (None, strings.remove(0)),
+        _ => {
+            let payee = strings.remove(0);
+            (if payee.is_empty() { None } else { Some(payee) }, strings.remove(0))
+        }
+    };
+
+    Entry {
+        line,
+        date,
+        charged,
+        narration: if narration.is_empty() { payee.clone().unwrap_or_default() } else { narration },
+        payee,
+        tags,
+        postings: Vec::new(),
+        problem: None
+    }
+}
+
+fn read_posting(entry: &mut Entry, text: &str) {
+    let text = text.split(';').next().unwrap_or("");
+    let mut tokens: Vec<&str> = text.split_whitespace().collect();
+
+    if matches!(tokens.first(), Some(&"!") | Some(&"*")) {
+        tokens.remove(0);
+    }
+
+    // Metadata lines look like 'key: value'.
+    let account = match tokens.first() {
+        Some(account) if account.contains(':') && !account.ends_with(':') => account.to_string(),
+        _ => return
+    };
+
+    let amount = match tokens.get(1..) {
+        None | Some([]) => None,
+        Some([number, currency]) => match number.replace(',', "").parse::<f64>() {
+            Ok(value) => Some(((value * 100.0).round() as i64, currency.to_string())),
+            Err(_) => {
+                entry.problem = Some(format!("amount '{}' is not a number", number));
+                None
+            }
+        },
+        Some(_) => {
+            entry.problem = Some(format!("posting of '{}' has a cost, a price or an expression", account));
+            None
+        }
+    };
+
+    entry.postings.push(Posting { account, amount });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{env, fs, process};
+
+    use rusqlite::Connection;
+
+    const JOURNAL: &str = "\
+option \"title\" \"Home\"
+2026-01-01 open Assets:Bank:Checking USD
+2026-01-01 open Liabilities:Visa USD
+2026-01-01 * \"Opening balance\"
+  Assets:Bank:Checking       100.00 USD
+  Equity:Opening-Balances
+pushtag #trip
+2026-01-05 * \"Cafe\" \"Coffee\" #food
+  Assets:Bank:Checking        -3.50 USD
+  Expenses:Food:Coffee
+poptag #trip
+2026-01-06 ! \"Card payment\"
+  Assets:Bank:Checking       -20.00 USD
+  Liabilities:Visa            20.00 USD
+2026-01-07 * \"Fees\"
+  Assets:Bank:Checking        -1.00 EUR
+  Expenses:Fees
+2026-01-08 price USD 1.00 EUR
+";
+
+    fn value(conn: &Connection, sql: &str) -> i64 {
+        conn.query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn journals_are_read() {
+        let journal = read_journal(JOURNAL);
+
+        assert_eq!(journal.entries.len(), 4);
+        assert_eq!(journal.currencies["Liabilities:Visa"], "USD");
+        assert_eq!(journal.skipped, vec![(18, "'price' directives are not imported".to_string())]);
+
+        let coffee = &journal.entries[1];
+        assert_eq!(coffee.payee.as_deref(), Some("Cafe"));
+        assert_eq!(coffee.narration, "Coffee");
+        assert_eq!(coffee.tags, vec!["food".to_string(), "trip".to_string()]);
+        assert!(journal.entries[2].tags.is_empty());
+        assert!(!journal.entries[2].charged);
+    }
+
+    #[test]
+    fn elided_amounts_are_balanced() {
+        let journal = read_journal(JOURNAL);
+
+        let postings = balanced_postings(&journal.entries[1]).unwrap();
+        assert_eq!(postings[1], ("Expenses:Food:Coffee".to_string(), 350, "USD".to_string()));
+
+        let mut entry = read_header(1, 0, true, "\"Unbalanced\"");
+        read_posting(&mut entry, "Assets:Bank  -1.00 USD");
+        read_posting(&mut entry, "Expenses:Fees  0.50 USD");
+        assert_eq!(balanced_postings(&entry).unwrap_err(), "postings don't balance by -0.50");
+
+        let mut entry = read_header(1, 0, true, "\"Priced\"");
+        read_posting(&mut entry, "Assets:Bank  1 AAPL {150.00 USD}");
+        assert!(entry.problem.is_some());
+    }
+
+    #[test]
+    fn journals_are_imported_once() {
+        let conn = walletdb::memory_database();
+        let path = env::temp_dir().join(format!("wallet-import-{}.beancount", process::id()));
+        fs::write(&path, JOURNAL).unwrap();
+
+        import_beancount(path.to_str().unwrap(), false).unwrap();
+        assert_eq!(value(&conn, "SELECT COUNT(*) FROM accounts"), 2);
+        assert_eq!(value(&conn, "SELECT COUNT(*) FROM transactions WHERE t_type = 3"), 2);
+        assert_eq!(value(&conn, "SELECT available FROM accounts WHERE name = 'Bank:Checking'"), 7650);
+        assert_eq!(value(&conn, "SELECT balance FROM accounts WHERE name = 'Bank:Checking'"), 9650);
+        assert_eq!(value(&conn, "SELECT category = 'food:coffee' FROM transactions WHERE id_transaction = 1"), 1);
+
+        // The opening balance and the transactions are not imported again.
+        import_beancount(path.to_str().unwrap(), false).unwrap();
+        assert_eq!(value(&conn, "SELECT COUNT(*) FROM transactions"), 3);
+        assert_eq!(value(&conn, "SELECT opening_balance FROM accounts WHERE name = 'Bank:Checking'"), 10000);
+
+        journal::undo(1).unwrap();
+        journal::undo(1).unwrap();
+        assert_eq!(value(&conn, "SELECT COUNT(*) FROM accounts"), 0);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn transfers_move_both_balances() {
+        let conn = walletdb::memory_database();
+
+        walletdb::new_account("Bank", 100.0, false, None).unwrap();
+        walletdb::new_account("Cash", 0.0, false, None).unwrap();
+        walletdb::account_transfer(30.0, "2", None).unwrap();
+
+        assert_eq!(value(&conn, "SELECT balance FROM accounts WHERE id_account = 1"), 7000);
+        assert_eq!(value(&conn, "SELECT balance FROM accounts WHERE id_account = 2"), 3000);
+        assert_eq!(value(&conn, "SELECT COUNT(*) FROM transactions WHERE id_transfer IS NOT NULL"), 2);
+
+        journal::undo(1).unwrap();
+        assert_eq!(value(&conn, "SELECT balance FROM accounts WHERE id_account = 1"), 10000);
+        assert_eq!(value(&conn, "SELECT COUNT(*) FROM transactions"), 0);
+    }
+}
@@ -0,0 +1,229 @@
+use crate::error;
+use crate::journal;
+use crate::output;
+use crate::reports;
+use crate::structs::Budget;
+use crate::utils;
+use crate::walletdb;
+
+use chrono::Datelike;
+use rusqlite::{params, Connection};
+
+const BAR_WIDTH: i64 = 30;
+
+pub fn select_budgets(conn: &Connection) -> rusqlite::Result<Vec<Budget>> {
+    let mut stmt = conn.prepare("SELECT * FROM budgets ORDER BY category")?;
+    let budgets = stmt.query_map([], Budget::from_row)?;
+
+    budgets.collect()
+}
+
+// Every budget with the expenses of its category and subcategories in the
+// period, pending ones included since the money is already committed.
+pub fn budget_status(conn: &Connection, (from, to): (i64, i64)) -> rusqlite::Result<Vec<(Budget, i64)>> {
+    let spending = reports::category_spending(conn, (from, to), None, true, to)?;
+
+    let status = select_budgets(conn)?.into_iter()
+        .map(|budget| {
+            let spent = spending.iter()
+                .filter(|(category, _)| budget.includes(category))
+                .map(|(_, cents)| cents)
+                .sum();
+
+            (budget, spent)
+        })
+        .collect();
+
+    Ok(status)
+}
+
+// First and last day of the month given, or of the current one.
+fn budget_month(opt_month: Option<&str>) -> error::Result<(i64, i64)> {
+    let first_day = match opt_month {
+        Some(str_month) => utils::parse_month(str_month)?,
+        None => utils::julian_day_to_naive(utils::today_julian_day())?
+    };
+
+    match reports::month_periods(first_day.year(), first_day.month()) {
+        Some((current, _)) => Ok(current),
+        None => error::invalid(format!("Invalid month '{}'! Please use the format YYYY-MM...", first_day.format("%Y-%m")))
+    }
+}
+
+
+// Wallet 'budget' subcommands are defined below.
+// Setting the budget of a category that already has one changes its amount.
+pub fn budget_set(category: &str, amount: f64) -> error::Result<()> {
+    let category = reports::normalize_category(category);
+
+    if category.split(':').any(|part| part.is_empty()) {
+        return error::invalid("The category of the budget or its parts can't be empty!");
+    } else if amount < 0.01 {
+        return error::invalid("The amount of a budget must be at least one cent '0.01'!");
+    }
+
+    let mut conn = walletdb::open_database()?;
+
+    let opt_budget = select_budgets(&conn)?.into_iter().find(|budget| budget.category == category);
+    let int_amount = (amount * 100.0).round() as i64;
+
+    let tx = conn.transaction()?;
+    let id_operation = journal::begin(&tx)?;
+
+    match opt_budget {
+        Some(budget) => {
+            let before = journal::row_image(&tx, "budgets", budget.id)?;
+            tx.execute("UPDATE budgets SET amount = ?1 WHERE id_budget = ?2", params![int_amount, budget.id])?;
+            journal::record(&tx, id_operation, "budgets", budget.id, before)?;
+
+            output::message(format!("Budget of [{}] changed from ${:.2} to ${:.2} a month.", category, budget.amount, amount));
+        }
+        None => {
+            tx.execute("INSERT INTO budgets (category, amount) VALUES (?1, ?2)", params![category, int_amount])?;
+            journal::record(&tx, id_operation, "budgets", tx.last_insert_rowid(), None)?;
+
+            output::message(format!("Successfully created budget of ${:.2} a month for [{}]!", amount, category));
+        }
+    }
+
+    Ok(tx.commit()?)
+}
+
+pub fn budget_delete(category: &str) -> error::Result<()> {
+    let category = reports::normalize_category(category);
+
+    let mut conn = walletdb::open_database()?;
+
+    let budget = match select_budgets(&conn)?.into_iter().find(|budget| budget.category == category) {
+        Some(budget) => budget,
+        None => return error::not_found(format!("There is no budget for [{}]! Try 'wallet budget list'.", category))
+    };
+
+    let tx = conn.transaction()?;
+    let id_operation = journal::begin(&tx)?;
+
+    let before = journal::row_image(&tx, "budgets", budget.id)?;
+    tx.execute("DELETE FROM budgets WHERE id_budget = ?1", params![budget.id])?;
+    journal::record(&tx, id_operation, "budgets", budget.id, before)?;
+
+    tx.commit()?;
+
+    output::message(format!("Budget of [{}] deleted.", category));
+
+    Ok(())
+}
+
+// Spending of the month against every budget, the bar is full at the budget.
+pub fn budget_list(opt_month: Option<&str>) -> error::Result<()> {
+    let (from, to) = budget_month(opt_month)?;
+
+    let conn = walletdb::open_database()?;
+
+    let status = budget_status(&conn, (from, to))?;
+
+    if status.is_empty() {
+        output::message("There are no budgets! Try 'wallet budget set --help'.");
+        return Ok(());
+    }
+
+    output::message(format!("Budgets of {}:", &utils::julian_day_to_iso(from)?[0..7]));
+    output::message(format!("{:<26} {:>12} {:>12} {:>12} {:>7}", "Category", "Spent", "Budget", "Left", "%"));
+
+    for (budget, spent) in &status {
+        let limit = (budget.amount * 100.0).round() as i64;
+        let filled = (spent * BAR_WIDTH / limit).min(BAR_WIDTH);
+        let over = if *spent > limit { " over" } else { "" };
+
+        output::message(format!(
+            "{:<26} {:>12.2} {:>12.2} {:>12.2} {:>6.1}%  {}{}{}",
+            budget.category, *spent as f64 / 100.0, budget.amount, (limit - spent) as f64 / 100.0,
+            *spent as f64 * 100.0 / limit as f64, "█".repeat(filled as usize), "░".repeat((BAR_WIDTH - filled) as usize), over
+        ));
+        output::record(vec![
+            ("category", budget.category.as_str().into()),
+            ("spent", output::money(*spent)),
+            ("budget", budget.amount.into()),
+            ("left", output::money(limit - spent))
+        ]);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::structs::EXPENSE;
+
+    fn value(conn: &Connection, sql: &str) -> i64 {
+        conn.query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn budgets_include_their_subcategories() {
+        let budget = Budget { id: 1, category: "food".to_string(), amount: 40.0 };
+
+        assert!(budget.includes("food"));
+        assert!(budget.includes("Food:Restaurants"));
+        assert!(!budget.includes("foodstuff"));
+        assert!(!budget.includes("home:food"));
+    }
+
+    #[test]
+    fn budgets_are_set_changed_and_deleted() {
+        let conn = walletdb::memory_database();
+
+        budget_set(" Food ", 40.0).unwrap();
+        budget_set("food", 55.5).unwrap();
+        assert_eq!(value(&conn, "SELECT COUNT(*) FROM budgets"), 1);
+        assert_eq!(value(&conn, "SELECT amount FROM budgets WHERE category = 'food'"), 5550);
+
+        // Empty categories and amounts under a cent are refused.
+        assert_eq!(budget_set(" : ", 10.0).unwrap_err().kind(), "invalid-input");
+        assert_eq!(budget_set("home", 0.0).unwrap_err().kind(), "invalid-input");
+        assert_eq!(value(&conn, "SELECT COUNT(*) FROM budgets"), 1);
+
+        budget_delete("FOOD").unwrap();
+        assert_eq!(value(&conn, "SELECT COUNT(*) FROM budgets"), 0);
+        assert_eq!(budget_delete("food").unwrap_err().kind(), "not-found");
+
+        journal::undo(1).unwrap();
+        assert_eq!(value(&conn, "SELECT amount FROM budgets"), 5550);
+
+        journal::undo(1).unwrap();
+        assert_eq!(value(&conn, "SELECT amount FROM budgets"), 4000);
+
+        journal::undo(1).unwrap();
+        assert_eq!(value(&conn, "SELECT COUNT(*) FROM budgets"), 0);
+    }
+
+    #[test]
+    fn budgets_count_the_expenses_of_the_month() {
+        let conn = walletdb::memory_database();
+
+        walletdb::new_account("Bank", 500.0, false, None).unwrap();
+        walletdb::new_transaction("Market", 30.0, EXPENSE, true, false, None, None).unwrap();
+        walletdb::new_transaction("Dinner", 25.0, EXPENSE, false, false, None, None).unwrap();
+        walletdb::new_transaction("Rent", 300.0, EXPENSE, true, false, None, None).unwrap();
+        conn.execute_batch(
+            "UPDATE transactions SET category = 'Food' WHERE message = 'Market';
+             UPDATE transactions SET category = 'food : restaurants' WHERE message = 'Dinner';
+             UPDATE transactions SET category = 'home' WHERE message = 'Rent';"
+        ).unwrap();
+
+        budget_set("food", 50.0).unwrap();
+        budget_set("fun", 20.0).unwrap();
+
+        let (from, to) = budget_month(None).unwrap();
+        let status: Vec<(String, i64)> = budget_status(&conn, (from, to)).unwrap().into_iter()
+            .map(|(budget, spent)| (budget.category, spent))
+            .collect();
+
+        // Pending expenses count, the money is already committed.
+        assert_eq!(status, vec![("food".to_string(), 5500), ("fun".to_string(), 0)]);
+
+        assert_eq!(budget_month(Some("2024-02")).unwrap(), (2460342, 2460370));
+        assert_eq!(budget_month(Some("2024-13")).unwrap_err().kind(), "invalid-input");
+    }
+}
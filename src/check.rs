@@ -0,0 +1,272 @@
+use crate::error;
+use crate::journal;
+use crate::output;
+use crate::structs::{Transaction, ADJUSTMENT};
+use crate::utils;
+use crate::walletdb::{self, SIGNED_VALUE};
+
+use rusqlite::Connection;
+use serde_json::Value;
+
+struct AccountCheck {
+    id: i64,
+    name: String,
+    balance: i64,
+    available: i64,
+    expected_balance: i64,
+    expected_available: i64
+}
+
+impl AccountCheck {
+    fn balance_drift(&self) -> i64 {
+        self.balance - self.expected_balance
+    }
+
+    // Drift left in available once the balance drift is fixed.
+    fn available_drift(&self) -> i64 {
+        self.available - self.expected_available - self.balance_drift()
+    }
+}
+
+// Wallet 'check' subcommand is defined below.
+pub fn check(fix: bool) -> error::Result<()> {
+    let mut conn = walletdb::open_database()?;
+
+    let mut problems = check_integrity(&conn)?;
+    problems += check_references(&conn)?;
+
+    let accounts = select_account_checks(&conn)?;
+    let drifted: Vec<&AccountCheck> = accounts.iter()
+        .filter(|a| a.balance_drift() != 0 || a.available_drift() != 0)
+        .collect();
+
+    for account in &accounts {
+        let status = if account.balance_drift() != 0 || account.available_drift() != 0 {
+            "DRIFT"
+        } else {
+            "ok"
+        };
+
+        output::message(format!("{:<4}.- {:<20} {}", account.id, account.name, status));
+        output::record(vec![
+            ("check", "account".into()),
+            ("id", account.id.into()),
+            ("name", account.name.as_str().into()),
+            ("status", status.to_lowercase().into()),
+            ("balance", output::money(account.balance)),
+            ("expected_balance", output::money(account.expected_balance)),
+            ("available", output::money(account.available)),
+            ("expected_available", output::money(account.expected_available))
+        ]);
+
+        if account.balance_drift() != 0 {
+            output::message(format!(
+                "        balance   ${:>15.2} expected ${:>15.2} ({:+.2})",
+                cents(account.balance), cents(account.expected_balance), cents(account.balance_drift())
+            ));
+        }
+
+        if account.available - account.expected_available != 0 {
+            output::message(format!(
+                "        available ${:>15.2} expected ${:>15.2} ({:+.2})",
+                cents(account.available), cents(account.expected_available),
+                cents(account.available - account.expected_available)
+            ));
+        }
+    }
+
+    problems += drifted.len();
+
+    output::record(vec![
+        ("check", "summary".into()),
+        ("problems", problems.into()),
+        ("drifts", drifted.len().into()),
+        ("fixed", (fix && !drifted.is_empty()).into())
+    ]);
+
+    if problems == 0 {
+        output::message("Everything is consistent!");
+        return Ok(());
+    }
+
+    if drifted.is_empty() {
+        output::message(format!("Found {} problems.", problems));
+        return Ok(());
+    }
+
+    if !fix {
+        output::message(format!("Found {} problems. Run 'wallet check --fix' to write adjustments for the drifts.", problems));
+        return Ok(());
+    }
+
+    let tx = conn.transaction()?;
+    let id_operation = journal::begin(&tx)?;
+
+    for account in drifted {
+        write_adjustments(&tx, id_operation, account)?;
+    }
+
+    tx.commit()?;
+
+    output::message("Adjustment transactions written. The history now explains every balance.");
+
+    Ok(())
+}
+
+fn check_integrity(conn: &Connection) -> rusqlite::Result<usize> {
+    let mut stmt = conn.prepare("PRAGMA integrity_check")?;
+    let results: Vec<String> = stmt.query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+
+    if results.len() == 1 && results[0] == "ok" {
+        output::message("Integrity check: ok");
+        output::record(vec![("check", "integrity".into()), ("status", "ok".into()), ("detail", Value::Null)]);
+        return Ok(0);
+    }
+
+    output::message("Integrity check failed:");
+    for result in &results {
+        output::message(format!("    {}", result));
+        output::record(vec![("check", "integrity".into()), ("status", "failed".into()), ("detail", result.as_str().into())]);
+    }
+
+    Ok(results.len())
+}
+
+fn check_references(conn: &Connection) -> rusqlite::Result<usize> {
+    let mut stmt = conn.prepare("PRAGMA foreign_key_check")?;
+    let orphans: Vec<(String, i64, String)> = stmt.query_map([], |row| {
+        Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+    })?.collect::<rusqlite::Result<_>>()?;
+
+    if orphans.is_empty() {
+        output::message("References check: ok");
+        output::record(vec![("check", "references".into()), ("status", "ok".into()), ("detail", Value::Null)]);
+        return Ok(0);
+    }
+
+    output::message("References check failed:");
+    for (table_name, id, parent) in &orphans {
+        let detail = format!("Row {} of '{}' references a missing row in '{}'.", id, table_name, parent);
+
+        output::message(format!("    {}", detail));
+        output::record(vec![("check", "references".into()), ("status", "failed".into()), ("detail", detail.into())]);
+    }
+
+    Ok(orphans.len())
+}
+
+fn select_account_checks(conn: &Connection) -> rusqlite::Result<Vec<AccountCheck>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT a.id_account, a.name, a.balance, a.available,
+            COALESCE(a.opening_balance, 0) + COALESCE((
+                SELECT SUM({signed}) FROM transactions t
+                WHERE t.id_account = a.id_account AND t.charged = 1
+            ), 0),
+            COALESCE(a.opening_balance, 0) + COALESCE((
+                SELECT SUM({signed}) FROM transactions t
+                WHERE t.id_account = a.id_account
+            ), 0)
+        FROM accounts a ORDER BY a.id_account",
+        signed = SIGNED_VALUE
+    ))?;
+
+    let accounts = stmt.query_map([], |row| {
+        Ok(AccountCheck {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            balance: row.get(2)?,
+            available: row.get(3)?,
+            expected_balance: row.get(4)?,
+            expected_available: row.get(5)?
+        })
+    })?.collect();
+
+    accounts
+}
+
+// The stored balances are kept, the adjustments make the history match them.
+fn write_adjustments(conn: &Connection, id_operation: i64, account: &AccountCheck) -> rusqlite::Result<()> {
+    let adjustments = [
+        (account.balance_drift(), true),
+        (account.available_drift(), false)
+    ];
+
+    for (drift, charged) in adjustments {
+        if drift == 0 {
+            continue;
+        }
+
+        let transaction = Transaction {
+            message: "Adjustment by 'wallet check'".to_string(),
+            value: cents(drift),
+            date: utils::today_julian_day(),
+            charged,
+            t_type: ADJUSTMENT,
+            id_account: account.id,
+            ..Transaction::empty()
+        };
+
+        walletdb::insert_transaction(conn, id_operation, &transaction)?;
+        output::message(format!("Adjusted '{}' by {:+.2}{}", account.name, cents(drift), if charged { "" } else { " (available only)" }));
+    }
+
+    Ok(())
+}
+
+fn cents(value: i64) -> f64 {
+    value as f64 / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drifts(conn: &Connection) -> Vec<(i64, i64)> {
+        select_account_checks(conn).unwrap().iter()
+            .map(|a| (a.balance_drift(), a.available_drift()))
+            .collect()
+    }
+
+    #[test]
+    fn fix_writes_adjustments_for_the_drifts() {
+        let conn = walletdb::memory_database();
+
+        walletdb::new_account("Bank", 100.0, false, None).unwrap();
+        walletdb::new_transaction("Coffee", 30.0, 0, true, false, None, None).unwrap();
+        walletdb::new_transaction("Rent", 20.0, 0, false, false, None, None).unwrap();
+        assert_eq!(drifts(&conn), vec![(0, 0)]);
+
+        conn.execute("UPDATE accounts SET balance = balance + 500, available = available - 1000", []).unwrap();
+        assert_eq!(drifts(&conn), vec![(500, -1500)]);
+
+        check(false).unwrap();
+        assert_eq!(drifts(&conn), vec![(500, -1500)]);
+
+        check(true).unwrap();
+        assert_eq!(drifts(&conn), vec![(0, 0)]);
+
+        let adjustments: Vec<(i64, bool)> = conn
+            .prepare("SELECT value, charged FROM transactions WHERE t_type = ?1 ORDER BY id_transaction").unwrap()
+            .query_map([ADJUSTMENT], |row| Ok((row.get(0)?, row.get(1)?))).unwrap()
+            .collect::<rusqlite::Result<_>>().unwrap();
+        assert_eq!(adjustments, vec![(500, true), (-1500, false)]);
+
+        journal::undo(1).unwrap();
+        assert_eq!(drifts(&conn), vec![(500, -1500)]);
+    }
+
+    #[test]
+    fn references_to_missing_accounts_are_problems() {
+        let conn = walletdb::memory_database();
+
+        assert_eq!(check_references(&conn).unwrap(), 0);
+
+        conn.pragma_update(None, "foreign_keys", "OFF").unwrap();
+        conn.execute(
+            "INSERT INTO transactions (message, value, date, t_type, id_account) VALUES ('Lost', 100, 1, 0, 9)", []
+        ).unwrap();
+        assert_eq!(check_references(&conn).unwrap(), 1);
+        assert_eq!(check_integrity(&conn).unwrap(), 0);
+    }
+}
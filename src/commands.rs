@@ -1,6 +1,7 @@
 use clap::{arg, Command};
 
 const ITEM_TYPES: [&str; 6] = ["account", "transaction", "payment", "saving", "queued", "msi"];
+const HISTORY_FORMATS: [&str; 3] = ["ledger", "beancount", "qif"];
 
 pub fn cli() -> Command<'static> {
     Command::new("wallet")
@@ -8,10 +9,16 @@ pub fn cli() -> Command<'static> {
         .subcommand_required(true)
         .arg_required_else_help(true)
         .allow_external_subcommands(true)
+        .args([
+            arg!(--output <MODE> "Write the results as text for people or as one JSON document.").required(false)
+                .possible_values(["text", "json"]).default_value("text"),
+            arg!(--porcelain "Write the results as stable tab-separated lines for scripts.").conflicts_with("output")
+        ])
         // Account subcommands.
         .subcommand(
             Command::new("account")
                 .about("Account related subcommands.")
+                .subcommand_required(true)
                 .arg_required_else_help(true)
                 .subcommand(
                     Command::new("default")
@@ -35,7 +42,9 @@ pub fn cli() -> Command<'static> {
                         .args([
                             arg!([ID] "ID of the account to edit."),
                             arg!(-n --name <NAME> "New name to the account.").required(false),
-                            arg!(-b --balance <BALANCE> "New balance of the account.").required(false)
+                            arg!(-b --balance <BALANCE> "New balance of the account.").required(false),
+                            arg!(--minimum <MINIMUM> "Lowest available balance the account should keep, forecasts warn below it.")
+                                .required(false).allow_hyphen_values(true)
                         ])
                 )
                 .subcommand(
@@ -49,22 +58,204 @@ pub fn cli() -> Command<'static> {
                         ])
                 )
         )
+        // Afford subcommand.
+        .subcommand(
+            Command::new("afford")
+                .about("Check if a purchase fits in an account with the forecast of the next days, nothing is written.")
+                .arg_required_else_help(true)
+                .args([
+                    arg!([AMOUNT] "Price of the purchase."),
+                    arg!(-a --account <ACCOUNT> "ID of the account of the purchase. *Only if is not the default account.").required(false),
+                    arg!(--msi <INSTALLMENTS> "Also check the purchase in this number of monthly installments.").required(false),
+                    arg!(-d --days <DAYS> "Number of days to simulate.").required(false).default_value("90")
+                ])
+        )
         // Backup subcommand.
         .subcommand(
             Command::new("backup")
                 .about("Creates a copy of the current database.")
                 .arg_required_else_help(true)
-                .arg(arg!([FILENAME] "File name of the backup database."))
+                .args([
+                    arg!([FILENAME] "File name of the backup database."),
+                    arg!(--encrypt "Add this to protect the backup with a passphrase.")
+                ])
+        )
+        // Budget subcommands.
+        .subcommand(
+            Command::new("budget")
+                .about("Monthly limits of the expenses of each category.")
+                .subcommand_required(true)
+                .arg_required_else_help(true)
+                .subcommand(
+                    Command::new("delete")
+                        .about("Delete the budget of a category.")
+                        .arg_required_else_help(true)
+                        .arg(arg!([CATEGORY] "Category of the budget."))
+                )
+                .subcommand(
+                    Command::new("list")
+                        .about("Spending of a month against every budget.")
+                        .arg(arg!(-m --month <MONTH> "Month to check (YYYY-MM). The current month by default.").required(false))
+                )
+                .subcommand(
+                    Command::new("set")
+                        .about("Set the monthly budget of a category, its subcategories are included.")
+                        .arg_required_else_help(true)
+                        .args([
+                            arg!([CATEGORY] "Category of the budget, for example 'food'."),
+                            arg!([AMOUNT] "Limit of the expenses of every month.")
+                        ])
+                )
+        )
+        // Check subcommand.
+        .subcommand(
+            Command::new("check")
+                .about("Check the consistency of the database and the account balances.")
+                .arg(arg!(--fix "Add this to write adjustment transactions for the balance drifts."))
+        )
+        // Database subcommands.
+        .subcommand(
+            Command::new("db")
+                .about("Database related subcommands.")
+                .subcommand_required(true)
+                .arg_required_else_help(true)
+                .subcommand(
+                    Command::new("rekey")
+                        .about("Change the passphrase of an encrypted database.")
+                )
         )
         // Dequeue subcommand.
         .subcommand(
             Command::new("dequeue")
                 .about("Dequeue purchases from queued list.")
         )
+        // Export subcommand.
+        .subcommand(
+            Command::new("export")
+                .about("Export items to CSV or JSON, or the whole history to a ledger or beancount journal or a QIF file.")
+                .arg_required_else_help(true)
+                .args([
+                    arg!([ITEM] "Item type to export, 'ledger' or 'beancount' for a journal or 'qif' for a QIF file.")
+                        .possible_values(ITEM_TYPES.iter().chain(&HISTORY_FORMATS)),
+                    arg!(-f --format <FORMAT> "Format of the export.").required(false)
+                        .possible_values(["csv", "json"]).default_value("csv"),
+                    arg!(--from <DATE> "First date of the items to export (YYYY-MM-DD).").required(false),
+                    arg!(--to <DATE> "Last date of the items to export (YYYY-MM-DD).").required(false),
+                    arg!(-a --account <ACCOUNT> "ID of the only account to export.").required(false),
+                    arg!(-o --file <FILE> "File to write. Standard output by default.").required(false),
+                    arg!(--currency <CURRENCY> "Currency of the accounts without one in a journal.").required(false).default_value("MXN")
+                ])
+        )
+        // Forecast subcommand.
+        .subcommand(
+            Command::new("forecast")
+                .about("Simulate the available balance of the accounts with the scheduled payments, incomings, MSI and queued purchases.")
+                .args([
+                    arg!(-d --days <DAYS> "Number of days to simulate.").required(false).default_value("90"),
+                    arg!(-a --account <ACCOUNT> "ID of the only account to simulate.").required(false)
+                ])
+        )
+        // History subcommand.
+        .subcommand(
+            Command::new("history")
+                .about("List the last operations that changed the database.")
+                .arg(arg!(-c --count [COUNT] "Number of operations to list.").default_value("10"))
+        )
+        // Import subcommands.
+        .subcommand(
+            Command::new("import")
+                .about("Import transactions from bank statements.")
+                .subcommand_required(true)
+                .arg_required_else_help(true)
+                .subcommand(
+                    Command::new("beancount")
+                        .about("Import the accounts and transactions of a beancount journal.")
+                        .arg_required_else_help(true)
+                        .args([
+                            arg!([FILE] "Path of the beancount file."),
+                            arg!(--"dry-run" "Add this to preview the import without writing it.")
+                        ])
+                )
+                .subcommand(
+                    Command::new("csv")
+                        .about("Import a CSV statement using a mapping profile.")
+                        .arg_required_else_help(true)
+                        .args([
+                            arg!([FILE] "Path of the CSV file."),
+                            arg!(-p --profile <PROFILE> "Name of the mapping profile of the file."),
+                            arg!(-a --account <ACCOUNT> "ID of the account of the statement. *Only if is not the default account.").required(false),
+                            arg!(-w --window <DAYS> "Days around the date of a row to look for its pending expense.").required(false).default_value("3"),
+                            arg!(--"dry-run" "Add this to preview the transactions without writing them.")
+                        ])
+                )
+                .subcommand(
+                    Command::new("gnucash")
+                        .about("Import the accounts and transactions of a GnuCash book, XML or SQLite.")
+                        .arg_required_else_help(true)
+                        .args([
+                            arg!([FILE] "Path of the GnuCash file."),
+                            arg!(--"dry-run" "Add this to preview the import without writing it.")
+                        ])
+                )
+                .subcommand(
+                    Command::new("homebank")
+                        .about("Import the accounts and transactions of a HomeBank file (.xhb).")
+                        .arg_required_else_help(true)
+                        .args([
+                            arg!([FILE] "Path of the HomeBank file."),
+                            arg!(--"dry-run" "Add this to preview the import without writing it.")
+                        ])
+                )
+                .subcommand(
+                    Command::new("ofx")
+                        .about("Import an OFX/QFX statement.")
+                        .arg_required_else_help(true)
+                        .args([
+                            arg!([FILE] "Path of the OFX file."),
+                            arg!(-a --account <ACCOUNT> "ID of the account to link with the OFX account. *Only the first time.").required(false),
+                            arg!(-w --window <DAYS> "Days around the date of a row to look for its pending expense.").required(false).default_value("3"),
+                            arg!(--"dry-run" "Add this to preview the transactions without writing them.")
+                        ])
+                )
+                .subcommand(
+                    Command::new("profile")
+                        .about("Manage the mapping profiles of CSV statements.")
+                        .subcommand_required(true)
+                        .arg_required_else_help(true)
+                        .subcommand(
+                            Command::new("add")
+                                .about("Add a new mapping profile. Columns start at 1.")
+                                .arg_required_else_help(true)
+                                .args([
+                                    arg!([NAME] "Name of the profile, for example the bank name."),
+                                    arg!(--"date-column" <COLUMN> "Column of the date."),
+                                    arg!(--"date-format" <FORMAT> "Format of the date, for example '%d/%m/%Y'."),
+                                    arg!(--"description-column" <COLUMN> "Column of the description."),
+                                    arg!(--"amount-column" <COLUMN> "Column of the amount, negative for expenses.").required(false),
+                                    arg!(--"debit-column" <COLUMN> "Column of the debits. *Only if there is no amount column.").required(false),
+                                    arg!(--"credit-column" <COLUMN> "Column of the credits. *Only if there is no amount column.").required(false),
+                                    arg!(--"decimal-separator" <SEPARATOR> "Decimal separator of the amounts.").required(false).default_value("."),
+                                    arg!(--delimiter <DELIMITER> "Field delimiter, use '\\t' for tabs.").required(false).default_value(","),
+                                    arg!(--"skip-rows" <ROWS> "Number of header rows to skip.").required(false).default_value("1")
+                                ])
+                        )
+                        .subcommand(
+                            Command::new("delete")
+                                .about("Delete a mapping profile.")
+                                .arg_required_else_help(true)
+                                .arg(arg!([NAME] "Name of the profile to delete."))
+                        )
+                        .subcommand(
+                            Command::new("list")
+                                .about("List the mapping profiles.")
+                        )
+                )
+        )
         // Init subcommand.
         .subcommand(
             Command::new("init")
                 .about("Creates and initialize the database.")
+                .arg(arg!(--encrypt "Add this to encrypt the database with a passphrase."))
         )
         // List subcommand.
         .subcommand(
@@ -74,7 +265,8 @@ pub fn cli() -> Command<'static> {
                 .args([
                     arg!([ITEM] "Item type to list.").possible_values(ITEM_TYPES),
                     arg!(-c --count [COUNT] "Number of items required to list.").default_value("10"),
-                    arg!(--all "List all items in table")
+                    arg!(--all "List all items in table"),
+                    arg!(--"as-of" <DATE> "Show the balances of the accounts at the end of this day (YYYY-MM-DD).").required(false)
                 ])
         )
         // New subcommands.
@@ -90,7 +282,9 @@ pub fn cli() -> Command<'static> {
                         .arg_required_else_help(true)
                         .args(&[
                             arg!([NAME] "Account name."),
-                            arg!([BALANCE] "Initial balance.").default_value("0")
+                            arg!([BALANCE] "Initial balance.").default_value("0").allow_hyphen_values(true),
+                            arg!(--credit "Add this if the account is a credit card or another liability."),
+                            arg!(--currency <CURRENCY> "Currency of the account, for example 'MXN'.").required(false)
                         ])
                 )
                 .subcommand(
@@ -102,7 +296,8 @@ pub fn cli() -> Command<'static> {
                             arg!([VALUE] "Value of the expense."),
                             arg!(-c --charged "Add this if the expense is already charged in the account."),
                             arg!(--force_price "Add this only if the value can be greater than account balance."),
-                            arg!(-a --account <ACCOUNT> "ID of the account of this expense. *Only if is not the default account.").required(false)
+                            arg!(-a --account <ACCOUNT> "ID of the account of this expense. *Only if is not the default account.").required(false),
+                            arg!(-p --payee <PAYEE> "Payee of the expense. The beginning of the name is enough for known payees.").required(false)
                         ])
                 )
                 .subcommand(
@@ -112,7 +307,34 @@ pub fn cli() -> Command<'static> {
                         .args(&[
                             arg!([MESSAGE] "Message of the incomming."),
                             arg!([VALUE] "Value of the incomming."),
-                            arg!(-a --account <ACCOUNT> "ID of the account of this incomming. *Only if is not the default account.").required(false)
+                            arg!(-a --account <ACCOUNT> "ID of the account of this incomming. *Only if is not the default account.").required(false),
+                            arg!(-p --payee <PAYEE> "Payee of the incomming. The beginning of the name is enough for known payees.").required(false)
+                        ])
+                )
+                .subcommand(
+                    Command::new("msi")
+                        .about("Add a purchase paid in monthly installments without interest.")
+                        .arg_required_else_help(true)
+                        .args(&[
+                            arg!([NAME] "Name of the purchase."),
+                            arg!([PRICE] "Total price of the purchase."),
+                            arg!([INSTALLMENTS] "Number of monthly installments."),
+                            arg!(--date <DATE> "Day the next installment is billed (YYYY-MM-DD)."),
+                            arg!(--paid <N> "Installments already paid.").required(false).default_value("0"),
+                            arg!(-a --account <ACCOUNT> "ID of the account of this purchase. *Only if is not the default account.").required(false)
+                        ])
+                )
+                .subcommand(
+                    Command::new("payment")
+                        .about("Add a scheduled payment, or a recurring incoming with --income.")
+                        .arg_required_else_help(true)
+                        .args(&[
+                            arg!([NAME] "Name of the payment."),
+                            arg!([PRICE] "Value billed each time."),
+                            arg!(--date <DATE> "Day of the next billing (YYYY-MM-DD)."),
+                            arg!(--every <MONTHS> "Months between billings, 0 for a payment billed once.").required(false).default_value("1"),
+                            arg!(--income "Add this if the account receives the money, like a salary."),
+                            arg!(-a --account <ACCOUNT> "ID of the account of this payment. *Only if is not the default account.").required(false)
                         ])
                 )
                 .subcommand(
@@ -121,8 +343,200 @@ pub fn cli() -> Command<'static> {
                         .arg_required_else_help(true)
                         .args(&[
                             arg!([MESSAGE] "Message of the expense to queue."),
-                            arg!([VALUE] "Value of the expense to queue.")
+                            arg!([VALUE] "Value of the expense to queue."),
+                            arg!(-a --account <ACCOUNT> "ID of the account of this expense. *Only if is not the default account.").required(false)
                         ])
                 )
         )
+        // Payee subcommands.
+        .subcommand(
+            Command::new("payee")
+                .about("Payees of the transactions and their default categories.")
+                .subcommand_required(true)
+                .arg_required_else_help(true)
+                .subcommand(
+                    Command::new("add")
+                        .about("Add a new payee.")
+                        .arg_required_else_help(true)
+                        .args([
+                            arg!([NAME] "Name of the payee."),
+                            arg!(--category <CATEGORY> "Default category of the payee, for example 'food:snacks'.").required(false)
+                        ])
+                )
+                .subcommand(
+                    Command::new("category")
+                        .about("Set the category of new transactions of a payee without one.")
+                        .arg_required_else_help(true)
+                        .args([
+                            arg!([PAYEE] "Name of the payee or its beginning."),
+                            arg!([CATEGORY] "Default category. Leave it out to remove the current one.")
+                        ])
+                )
+                .subcommand(
+                    Command::new("list")
+                        .about("List the payees with their number of transactions.")
+                )
+                .subcommand(
+                    Command::new("merge")
+                        .about("Move the transactions and rules of a payee to another one and delete the first.")
+                        .arg_required_else_help(true)
+                        .args([
+                            arg!([PAYEE] "Payee to merge and delete."),
+                            arg!([INTO] "Payee that keeps the transactions.")
+                        ])
+                )
+                .subcommand(
+                    Command::new("rename")
+                        .about("Rename a payee in its transactions and rules.")
+                        .arg_required_else_help(true)
+                        .args([
+                            arg!([PAYEE] "Name of the payee or its beginning."),
+                            arg!([NEW_NAME] "New name of the payee.")
+                        ])
+                )
+        )
+        // Reconcile subcommand.
+        .subcommand(
+            Command::new("reconcile")
+                .about("Reconcile an account against the balance of a bank statement.")
+                .arg_required_else_help(true)
+                .args([
+                    arg!([ACCOUNT] "ID of the account to reconcile."),
+                    arg!([STATEMENT_BALANCE] "Balance shown in the bank statement.").allow_hyphen_values(true),
+                    arg!(-d --date <DATE> "Date of the statement (YYYY-MM-DD). Today by default.").required(false),
+                    arg!(--adjust "Add this to post an adjustment for the remaining difference without asking.")
+                ])
+        )
+        // Redo subcommand.
+        .subcommand(
+            Command::new("redo")
+                .about("Reapply the last undone operations.")
+                .arg(arg!([N] "Number of operations to redo.").default_value("1"))
+        )
+        // Report subcommands.
+        .subcommand(
+            Command::new("report")
+                .about("Summaries of the transactions.")
+                .subcommand_required(true)
+                .arg_required_else_help(true)
+                .subcommand(
+                    Command::new("categories")
+                        .about("Expenses of each category in a period, compared with the period before.")
+                        .args([
+                            arg!(-m --month <MONTH> "Month to report (YYYY-MM). The current month by default.").required(false)
+                                .conflicts_with_all(&["from", "to"]),
+                            arg!(--from <DATE> "First date of the period (YYYY-MM-DD).").required(false),
+                            arg!(--to <DATE> "Last date of the period (YYYY-MM-DD). Today by default.").required(false),
+                            arg!(-a --account <ACCOUNT> "ID of the only account to report.").required(false),
+                            arg!(--pending "Add this to include transactions that are not charged yet."),
+                            arg!(--"as-of" <DATE> "Report the transactions as they were at the end of this day (YYYY-MM-DD).").required(false)
+                        ])
+                )
+                .subcommand(
+                    Command::new("html")
+                        .about("A page with charts of the net worth, income, expenses, categories and budgets of a year.")
+                        .args([
+                            arg!(-o --file <FILE> "File to write. Standard output by default.").required(false),
+                            arg!(-y --year <YEAR> "Year to report. The current year by default.").required(false)
+                        ])
+                )
+                .subcommand(
+                    Command::new("monthly")
+                        .about("Income, expenses and savings rate of each month of a year.")
+                        .args([
+                            arg!(-y --year <YEAR> "Year to report. The current year by default.").required(false),
+                            arg!(-a --account <ACCOUNT> "ID of the only account to report.").required(false),
+                            arg!(--pending "Add this to include transactions that are not charged yet."),
+                            arg!(--"as-of" <DATE> "Report the transactions as they were at the end of this day (YYYY-MM-DD).").required(false)
+                        ])
+                )
+                .subcommand(
+                    Command::new("networth")
+                        .about("Balances of the accounts at the end of each year or month, minus credit and MSI debt.")
+                        .args([
+                            arg!(--monthly "Add this to show each month instead of each year."),
+                            arg!(--sparkline "Add this to draw the trend of each account."),
+                            arg!(--from <DATE> "First date to report (YYYY-MM-DD). The first transaction by default.").required(false),
+                            arg!(--"as-of" <DATE> "Last date to report (YYYY-MM-DD). Today by default.").required(false)
+                        ])
+                )
+                .subcommand(
+                    Command::new("payees")
+                        .about("Money spent with and received from each payee.")
+                        .args([
+                            arg!(--from <DATE> "First date of the transactions (YYYY-MM-DD).").required(false),
+                            arg!(--to <DATE> "Last date of the transactions (YYYY-MM-DD).").required(false),
+                            arg!(-a --account <ACCOUNT> "ID of the only account to report.").required(false)
+                        ])
+                )
+        )
+        // Rule subcommands.
+        .subcommand(
+            Command::new("rule")
+                .about("Rules that set the category, payee and tags of new transactions.")
+                .subcommand_required(true)
+                .arg_required_else_help(true)
+                .subcommand(
+                    Command::new("add")
+                        .about("Add a new rule. The oldest matching rule is the one that fires.")
+                        .arg_required_else_help(true)
+                        .args([
+                            arg!(-m --match <PATTERN> "Text to look for in the message, case is ignored."),
+                            arg!(--regex "Add this if the pattern is a regular expression."),
+                            arg!(--category <CATEGORY> "Category to set, for example 'food:snacks'.").required(false),
+                            arg!(--payee <PAYEE> "Payee to set.").required(false),
+                            arg!(--tags <TAGS> "Comma separated tags to set.").required(false),
+                            arg!(-a --account <ACCOUNT> "ID of the only account where the rule applies.").required(false),
+                            arg!(--"amount-range" <RANGE> "Values where the rule applies, for example '10..500', '..500' or '100..'.").required(false)
+                        ])
+                )
+                .subcommand(
+                    Command::new("apply")
+                        .about("Run the rules again over existing expenses and incomings.")
+                        .arg_required_else_help(true)
+                        .args([
+                            arg!(--since <DATE> "First date of the transactions to update (YYYY-MM-DD)."),
+                            arg!(-a --account <ACCOUNT> "ID of the only account to update.").required(false)
+                        ])
+                )
+                .subcommand(
+                    Command::new("delete")
+                        .about("Delete a rule.")
+                        .arg_required_else_help(true)
+                        .arg(arg!([ID] "ID of the rule to delete."))
+                )
+                .subcommand(
+                    Command::new("list")
+                        .about("List the rules in the order they are tried.")
+                )
+                .subcommand(
+                    Command::new("test")
+                        .about("Show which rule fires for a message.")
+                        .arg_required_else_help(true)
+                        .args([
+                            arg!([TEXT] "Message to test."),
+                            arg!(-v --value <VALUE> "Value of the transaction, needed by rules with an amount range.").required(false),
+                            arg!(-a --account <ACCOUNT> "ID of the account of the transaction.").required(false)
+                        ])
+                )
+        )
+        // Statement subcommand.
+        .subcommand(
+            Command::new("statement")
+                .about("Statement of an account for a month, with its opening and closing balances.")
+                .arg_required_else_help(true)
+                .args([
+                    arg!([ACCOUNT] "ID of the account."),
+                    arg!(-m --month <MONTH> "Month of the statement (YYYY-MM)."),
+                    arg!(-f --format <FORMAT> "Format of the statement.").required(false)
+                        .possible_values(["text", "csv", "html"]).default_value("text"),
+                    arg!(-o --file <FILE> "File to write. Standard output by default.").required(false)
+                ])
+        )
+        // Undo subcommand.
+        .subcommand(
+            Command::new("undo")
+                .about("Revert the last operations that changed the database.")
+                .arg(arg!([N] "Number of operations to undo.").default_value("1"))
+        )
 }
@@ -0,0 +1,364 @@
+use crate::error;
+use crate::importer::{self, StatementRow};
+use crate::journal;
+use crate::output;
+use crate::structs::CsvProfile;
+use crate::utils;
+use crate::walletdb;
+
+
+use chrono::NaiveDate;
+use rusqlite::{params, Connection};
+
+// Wallet 'import csv' subcommand is defined below.
+pub fn import_csv(
+            path: &str, profile_name: &str, opt_id_account: Option<&str>, window: i64, dry_run: bool
+        ) -> error::Result<()> {
+    let mut conn = walletdb::open_database()?;
+
+    let profile = match select_profile(&conn, profile_name)? {
+        Some(profile) => profile,
+        None => return error::not_found(format!("Profile '{}' not found! Try 'wallet import profile list'.", profile_name))
+    };
+
+    let mut account = walletdb::select_account(&conn, opt_id_account)?;
+
+    let content = utils::read_file(path)?;
+
+    let rows = read_statement(&content, &profile);
+
+    if dry_run {
+        return importer::import_rows(&conn, None, &mut account, &rows, window);
+    }
+
+    let tx = conn.transaction()?;
+    let id_operation = journal::begin(&tx)?;
+
+    importer::import_rows(&tx, Some(id_operation), &mut account, &rows, window)?;
+
+    Ok(tx.commit()?)
+}
+
+fn read_statement(content: &[u8], profile: &CsvProfile) -> Vec<StatementRow> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(profile.delimiter as u8)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(content);
+
+    let mut rows = Vec::new();
+
+    for (index, record) in reader.byte_records().enumerate() {
+        let line = index + 1;
+
+        if line <= profile.skip_rows {
+            continue;
+        }
+
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                skip_row(line, &e.to_string());
+                continue;
+            }
+        };
+
+        // Statements are not always UTF-8, so invalid characters are replaced.
+        let field = |column: usize| -> String {
+            record.get(column - 1)
+                .map(|bytes| String::from_utf8_lossy(bytes).trim().to_string())
+                .unwrap_or_default()
+        };
+
+        if record.iter().all(|bytes| bytes.iter().all(|b| b.is_ascii_whitespace())) {
+            continue;
+        }
+
+        match read_row(&field, profile) {
+            Ok(Some(row)) => rows.push(row),
+            Ok(None) => (),
+            Err(reason) => skip_row(line, &reason)
+        }
+    }
+
+    rows
+}
+
+fn skip_row(line: usize, reason: &str) {
+    output::message(format!("Row {} skipped: {}", line, reason));
+    output::record(vec![
+        ("record", "skipped".into()),
+        ("entry", format!("row {}", line).into()),
+        ("reason", reason.into())
+    ]);
+}
+
+fn read_row(field: &dyn Fn(usize) -> String, profile: &CsvProfile) -> Result<Option<StatementRow>, String> {
+    let str_date = field(profile.date_column);
+    let date = match NaiveDate::parse_from_str(&str_date, &profile.date_format) {
+        Ok(date) => utils::naive_to_julian_day(date),
+        Err(_) => return Err(format!("invalid date '{}'", str_date))
+    };
+
+    let amount = |column: Option<usize>| -> Result<f64, String> {
+        match column {
+            Some(column) => {
+                let text = field(column);
+                if text.is_empty() {
+                    return Ok(0.0);
+                }
+
+                importer::parse_amount(&text, profile.decimal_separator)
+                    .ok_or(format!("invalid amount '{}'", text))
+            }
+            None => Ok(0.0)
+        }
+    };
+
+    let value = if profile.amount_column.is_some() {
+        amount(profile.amount_column)?
+    } else {
+        amount(profile.credit_column)?.abs() - amount(profile.debit_column)?.abs()
+    };
+
+    if (value * 100.0).round() == 0.0 {
+        return Ok(None);
+    }
+
+    Ok(Some(StatementRow {
+        date,
+        message: field(profile.description_column),
+        value,
+        fitid: None
+    }))
+}
+
+fn select_profile(conn: &Connection, name: &str) -> rusqlite::Result<Option<CsvProfile>> {
+    let mut stmt = conn.prepare("SELECT * FROM csv_profiles WHERE name = ?1")?;
+    let mut rows = stmt.query(params![name])?;
+
+    match rows.next()? {
+        Some(row) => Ok(Some(CsvProfile::from_row(row)?)),
+        None => Ok(None)
+    }
+}
+
+
+// Wallet 'import profile' subcommands are defined below.
+pub fn profile_add(profile: &CsvProfile) -> error::Result<()> {
+    let columns = [
+        Some(profile.date_column), Some(profile.description_column),
+        profile.amount_column, profile.debit_column, profile.credit_column
+    ];
+
+    if profile.amount_column.is_none() && profile.debit_column.is_none() && profile.credit_column.is_none() {
+        return error::invalid("The profile needs an amount column or debit/credit columns!");
+    } else if columns.iter().flatten().any(|&column| column < 1) {
+        return error::invalid("Columns are numbered from 1! Please check the column numbers of the profile...");
+    } else if !profile.delimiter.is_ascii() {
+        return error::invalid(format!("The delimiter '{}' is not an ASCII character! The CSV reader only supports those.", profile.delimiter));
+    }
+
+    let mut conn = walletdb::open_database()?;
+
+    if select_profile(&conn, &profile.name)?.is_some() {
+        return error::invalid(format!("The profile '{}' already exists!", profile.name));
+    }
+
+    let to_int = |column: Option<usize>| column.map(|c| c as i64);
+
+    let tx = conn.transaction()?;
+    let id_operation = journal::begin(&tx)?;
+
+    tx.execute(
+        "INSERT INTO csv_profiles (
+            name, delimiter, skip_rows, date_column, date_format, description_column,
+            amount_column, debit_column, credit_column, decimal_separator
+        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        params![
+            &profile.name, profile.delimiter.to_string(), profile.skip_rows as i64,
+            profile.date_column as i64, &profile.date_format, profile.description_column as i64,
+            to_int(profile.amount_column), to_int(profile.debit_column), to_int(profile.credit_column),
+            profile.decimal_separator.to_string()
+        ]
+    )?;
+    journal::record(&tx, id_operation, "csv_profiles", tx.last_insert_rowid(), None)?;
+
+    tx.commit()?;
+
+    output::message(format!("Successfully created profile '{}'!", profile.name));
+
+    Ok(())
+}
+
+pub fn profile_list() -> error::Result<()> {
+    let conn = walletdb::open_database()?;
+
+    let mut stmt = conn.prepare("SELECT * FROM csv_profiles ORDER BY name")?;
+    let profiles = stmt.query_map([], CsvProfile::from_row)?;
+
+    let mut items_len = 0;
+
+    for profile in profiles {
+        let profile = profile?;
+
+        items_len += 1;
+        output::message(&profile);
+        output::record(vec![
+            ("name", profile.name.as_str().into()),
+            ("delimiter", profile.delimiter.to_string().into()),
+            ("skip_rows", profile.skip_rows.into()),
+            ("date_column", profile.date_column.into()),
+            ("date_format", profile.date_format.as_str().into()),
+            ("description_column", profile.description_column.into()),
+            ("amount_column", profile.amount_column.into()),
+            ("debit_column", profile.debit_column.into()),
+            ("credit_column", profile.credit_column.into()),
+            ("decimal_separator", profile.decimal_separator.to_string().into())
+        ]);
+    }
+
+    if items_len == 0 {
+        output::message("There are no profiles! Try 'wallet import profile add --help'.");
+    }
+
+    Ok(())
+}
+
+pub fn profile_delete(name: &str) -> error::Result<()> {
+    let mut conn = walletdb::open_database()?;
+
+    let tx = conn.transaction()?;
+    let id_operation = journal::begin(&tx)?;
+
+    let ids = journal::ids_where(&tx, "csv_profiles", &format!("name = '{}'", name.replace('\'', "''")))?;
+
+    if ids.is_empty() {
+        return error::not_found(format!("Profile '{}' not found!", name));
+    }
+
+    for id_profile in ids {
+        let before = journal::row_image(&tx, "csv_profiles", id_profile)?;
+        tx.execute("DELETE FROM csv_profiles WHERE id_profile = ?1", params![id_profile])?;
+        journal::record(&tx, id_operation, "csv_profiles", id_profile, before)?;
+    }
+
+    tx.commit()?;
+
+    output::message(format!("Successfully deleted profile '{}'!", name));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    fn profile(name: &str) -> CsvProfile {
+        CsvProfile {
+            name: name.to_string(),
+            delimiter: ',',
+            skip_rows: 1,
+            date_column: 1,
+            date_format: "%Y-%m-%d".to_string(),
+            description_column: 2,
+            amount_column: Some(3),
+            debit_column: None,
+            credit_column: None,
+            decimal_separator: '.'
+        }
+    }
+
+    fn value(conn: &Connection, sql: &str) -> i64 {
+        conn.query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn amount_column_rows() {
+        let content = b"Date,Description,Amount\n2026-10-01,Coffee,-3.50\n2026-10-02,\"Salary, October\",\"1,200.00\"\n";
+        let rows = read_statement(content, &profile("bank"));
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].date, utils::parse_date("2026-10-01").unwrap());
+        assert_eq!((rows[0].message.as_str(), rows[0].value), ("Coffee", -3.5));
+        assert_eq!((rows[1].message.as_str(), rows[1].value), ("Salary, October", 1200.0));
+    }
+
+    #[test]
+    fn debit_and_credit_columns_with_decimal_comma() {
+        let content = "01/10/2026;Caf\u{e9};3,50;\n02/10/2026;N\u{f3}mina;;1.200,00\n".as_bytes();
+        let profile = CsvProfile {
+            delimiter: ';',
+            skip_rows: 0,
+            date_format: "%d/%m/%Y".to_string(),
+            amount_column: None,
+            debit_column: Some(3),
+            credit_column: Some(4),
+            decimal_separator: ',',
+            ..profile("es")
+        };
+
+        let rows = read_statement(content, &profile);
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!((rows[0].message.as_str(), rows[0].value), ("Caf\u{e9}", -3.5));
+        assert_eq!((rows[1].message.as_str(), rows[1].value), ("N\u{f3}mina", 1200.0));
+    }
+
+    #[test]
+    fn bad_and_empty_rows_are_skipped() {
+        let content = b"Date,Description,Amount\nyesterday,Coffee,-3.50\n2026-10-01,Tea,abc\n2026-10-02,Zero,0.00\n,,\n2026-10-03,Caf\xe9,-2\n";
+        let rows = read_statement(content, &profile("bank"));
+
+        assert_eq!(rows.len(), 1);
+        assert_eq!((rows[0].message.as_str(), rows[0].value), ("Caf\u{fffd}", -2.0));
+    }
+
+    #[test]
+    fn invalid_profiles_are_not_saved() {
+        let conn = walletdb::memory_database();
+
+        for invalid in [
+            CsvProfile { delimiter: '\u{a7}', ..profile("section") },
+            CsvProfile { date_column: 0, ..profile("zero") },
+            CsvProfile { amount_column: None, ..profile("amountless") }
+        ] {
+            assert_eq!(profile_add(&invalid).unwrap_err().kind(), "invalid-input");
+        }
+
+        assert_eq!(value(&conn, "SELECT COUNT(*) FROM csv_profiles"), 0);
+    }
+
+    #[test]
+    fn import_round_trip() {
+        let conn = walletdb::memory_database();
+        let path = env::temp_dir().join(format!("wallet-import-{}.csv", process::id()));
+        fs::write(&path, "Date,Description,Amount\n2026-10-01,Coffee,-3.50\n2026-10-02,Salary,100\n").unwrap();
+
+        walletdb::new_account("Bank", 10.0, false, None).unwrap();
+        profile_add(&profile("bank")).unwrap();
+        import_csv(path.to_str().unwrap(), "bank", None, 3, true).unwrap();
+        assert_eq!(value(&conn, "SELECT COUNT(*) FROM transactions"), 0);
+
+        import_csv(path.to_str().unwrap(), "bank", None, 3, false).unwrap();
+        assert_eq!(value(&conn, "SELECT COUNT(*) FROM transactions"), 2);
+        assert_eq!(value(&conn, "SELECT balance FROM accounts"), 10650);
+
+        // Rows without FITID are recognized when the statement is imported again.
+        import_csv(path.to_str().unwrap(), "bank", None, 3, false).unwrap();
+        assert_eq!(value(&conn, "SELECT COUNT(*) FROM transactions"), 2);
+        journal::undo(1).unwrap();
+
+        journal::undo(1).unwrap();
+        assert_eq!(value(&conn, "SELECT balance FROM accounts"), 1000);
+
+        profile_delete("bank").unwrap();
+        assert_eq!(value(&conn, "SELECT COUNT(*) FROM csv_profiles"), 0);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
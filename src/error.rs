@@ -0,0 +1,126 @@
+use std::fmt::{Display, Formatter};
+use std::io;
+
+// Everything that stops a command. Each kind has its own exit code so scripts
+// can tell them apart without reading the message.
+#[derive(Debug)]
+pub enum WalletError {
+    // There is no database or one of its tables is missing.
+    NotInitialised(String),
+    // An account, transaction or other row asked for doesn't exist.
+    NotFound(String),
+    // The account can't pay for an expense or a transfer.
+    InsufficientFunds(String),
+    // Values given in the command line or read from a file are not valid.
+    InvalidInput(String),
+    // A file could not be read or written.
+    Io(String, io::Error),
+    Database(rusqlite::Error)
+}
+
+pub type Result<T> = std::result::Result<T, WalletError>;
+
+impl WalletError {
+    // Short stable name written in the machine-readable output.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            WalletError::NotInitialised(_) => "not-initialised",
+            WalletError::NotFound(_) => "not-found",
+            WalletError::InsufficientFunds(_) => "insufficient-funds",
+            WalletError::InvalidInput(_) => "invalid-input",
+            WalletError::Io(_, _) => "io",
+            WalletError::Database(_) => "database"
+        }
+    }
+
+    // Invalid input shares its code with the command line errors of clap.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            WalletError::Database(_) => 1,
+            WalletError::InvalidInput(_) => 2,
+            WalletError::NotInitialised(_) => 3,
+            WalletError::NotFound(_) => 4,
+            WalletError::InsufficientFunds(_) => 5,
+            WalletError::Io(_, _) => 6
+        }
+    }
+}
+
+impl Display for WalletError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            WalletError::NotInitialised(message)
+            | WalletError::NotFound(message)
+            | WalletError::InsufficientFunds(message)
+            | WalletError::InvalidInput(message) => write!(f, "{}", message),
+            WalletError::Io(message, e) => write!(f, "{} Error: {}", message, e),
+            WalletError::Database(e) => write!(f, "Something went wrong with the database! Error: {}", e)
+        }
+    }
+}
+
+impl From<rusqlite::Error> for WalletError {
+    fn from(e: rusqlite::Error) -> Self {
+        WalletError::Database(e)
+    }
+}
+
+// Values of the command line that can't be parsed, like a price that is not a
+// number. Only the first line of the message of clap is kept.
+impl From<clap::Error> for WalletError {
+    fn from(e: clap::Error) -> Self {
+        let message = e.to_string();
+        let first_line = message.lines().next().unwrap_or_default();
+
+        WalletError::InvalidInput(first_line.trim_start_matches("error: ").to_string())
+    }
+}
+
+// Shorthands for the errors built from a message.
+pub fn invalid<T, S: Into<String>>(message: S) -> Result<T> {
+    Err(WalletError::InvalidInput(message.into()))
+}
+
+pub fn not_found<T, S: Into<String>>(message: S) -> Result<T> {
+    Err(WalletError::NotFound(message.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_kind_has_its_own_exit_code() {
+        let errors = [
+            WalletError::Database(rusqlite::Error::QueryReturnedNoRows),
+            WalletError::InvalidInput(String::new()),
+            WalletError::NotInitialised(String::new()),
+            WalletError::NotFound(String::new()),
+            WalletError::InsufficientFunds(String::new()),
+            WalletError::Io(String::new(), io::Error::from(io::ErrorKind::NotFound))
+        ];
+
+        let codes: Vec<i32> = errors.iter().map(WalletError::exit_code).collect();
+        assert_eq!(codes, vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(errors[4].kind(), "insufficient-funds");
+    }
+
+    #[test]
+    fn messages_are_written_for_people() {
+        assert_eq!(not_found::<(), _>("Account not found!").unwrap_err().to_string(), "Account not found!");
+
+        let io_error = WalletError::Io("Unable to read the file 'a.csv'!".to_string(), io::Error::other("denied"));
+        assert_eq!(io_error.to_string(), "Unable to read the file 'a.csv'! Error: denied");
+    }
+
+    #[test]
+    fn values_clap_can_not_parse_are_invalid_input() {
+        let matches = clap::Command::new("wallet")
+            .arg(clap::arg!(--days <DAYS> "Days."))
+            .get_matches_from(["wallet", "--days", "ten"]);
+
+        let e = WalletError::from(matches.value_of_t::<i64>("days").unwrap_err());
+        assert_eq!(e.kind(), "invalid-input");
+        assert!(!e.to_string().starts_with("error:") && !e.to_string().contains('\n'));
+    }
+}
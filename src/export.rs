@@ -0,0 +1,323 @@
+use crate::error::{self, WalletError};
+use crate::output;
+use crate::structs::{ADJUSTMENT, EXPENSE, INCOMING, TRANSFER};
+use crate::utils;
+use crate::walletdb;
+
+use std::fs::File;
+use std::io::{self, Write};
+
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{Connection, Row};
+use serde_json::{Map, Value};
+
+// How a stored column is written. Money is stored in cents and dates as Julian
+// days, both are converted to what a spreadsheet expects.
+#[derive(Clone, Copy)]
+enum Kind {
+    Integer,
+    Text,
+    Money,
+    Date,
+    Flag,
+    TransactionType
+}
+
+// Column of the table, name in the export and kind of every exported column.
+type Column = (&'static str, &'static str, Kind);
+
+fn table_columns(table_name: &str) -> &'static [Column] {
+    match table_name {
+        "accounts" => &[
+            ("id_account", "id_account", Kind::Integer),
+            ("name", "name", Kind::Text),
+            ("balance", "balance", Kind::Money),
+            ("available", "available", Kind::Money),
+            ("is_default", "is_default", Kind::Flag),
+            ("opening_balance", "opening_balance", Kind::Money),
+            ("ofx_acctid", "ofx_acctid", Kind::Text),
+            ("is_credit", "is_credit", Kind::Flag),
+            ("currency", "currency", Kind::Text),
+            ("created", "created", Kind::Date),
+            ("minimum", "minimum", Kind::Money)
+        ],
+        "transactions" => &[
+            ("id_transaction", "id_transaction", Kind::Integer),
+            ("date", "date", Kind::Date),
+            ("message", "message", Kind::Text),
+            ("value", "value", Kind::Money),
+            ("charged", "charged", Kind::Flag),
+            ("charge_date", "charge_date", Kind::Date),
+            ("t_type", "type", Kind::TransactionType),
+            ("category", "category", Kind::Text),
+            ("payee", "payee", Kind::Text),
+            ("tags", "tags", Kind::Text),
+            ("fitid", "fitid", Kind::Text),
+            ("id_transfer", "id_transfer", Kind::Integer),
+            ("id_account", "id_account", Kind::Integer)
+        ],
+        "payments" => &[
+            ("id_payment", "id_payment", Kind::Integer),
+            ("name", "name", Kind::Text),
+            ("price", "price", Kind::Money),
+            ("billing_date", "billing_date", Kind::Date),
+            ("priodicity", "periodicity", Kind::Integer),
+            ("is_income", "is_income", Kind::Flag),
+            ("id_account", "id_account", Kind::Integer)
+        ],
+        "savings" => &[
+            ("id_saving", "id_saving", Kind::Integer),
+            ("name", "name", Kind::Text),
+            ("goal", "goal", Kind::Money),
+            ("balance", "balance", Kind::Money),
+            ("id_account", "id_account", Kind::Integer)
+        ],
+        "msi_purchases" => &[
+            ("id_msi", "id_msi", Kind::Integer),
+            ("name", "name", Kind::Text),
+            ("price", "price", Kind::Money),
+            ("installments", "installments", Kind::Integer),
+            ("months_paid", "months_paid", Kind::Integer),
+            ("billing_date", "billing_date", Kind::Date),
+            ("id_account", "id_account", Kind::Integer)
+        ],
+        "queued_purchases" => &[
+            ("id_queued", "id_queued", Kind::Integer),
+            ("message", "message", Kind::Text),
+            ("price", "price", Kind::Money),
+            ("id_account", "id_account", Kind::Integer)
+        ],
+        _ => unreachable!()
+    }
+}
+
+// Column used by --from and --to, only some tables have dates.
+fn date_column(table_name: &str) -> Option<&'static str> {
+    match table_name {
+        "transactions" => Some("date"),
+        "payments" => Some("billing_date"),
+        _ => None
+    }
+}
+
+fn transaction_type_name(t_type: i64) -> &'static str {
+    match t_type {
+        EXPENSE => "expense",
+        INCOMING => "incoming",
+        ADJUSTMENT => "adjustment",
+        TRANSFER => "transfer",
+        _ => "unknown"
+    }
+}
+
+fn to_json(kind: Kind, value: SqlValue) -> error::Result<Value> {
+    let json = match (kind, value) {
+        (_, SqlValue::Null) => Value::Null,
+        (Kind::Money, SqlValue::Integer(cents)) => Value::from(cents as f64 / 100.0),
+        (Kind::Date, SqlValue::Integer(julian_date)) => Value::from(utils::julian_day_to_iso(julian_date)?),
+        (Kind::Flag, SqlValue::Integer(flag)) => Value::from(flag != 0),
+        (Kind::TransactionType, SqlValue::Integer(t_type)) => Value::from(transaction_type_name(t_type)),
+        (_, SqlValue::Integer(integer)) => Value::from(integer),
+        (_, SqlValue::Real(real)) => Value::from(real),
+        (_, SqlValue::Text(text)) => Value::from(text),
+        (_, SqlValue::Blob(_)) => Value::Null
+    };
+
+    Ok(json)
+}
+
+fn to_text(kind: Kind, value: SqlValue) -> error::Result<String> {
+    let text = match (kind, value) {
+        (Kind::Money, SqlValue::Integer(cents)) => format!("{:.2}", cents as f64 / 100.0),
+        (_, value) => match to_json(kind, value)? {
+            Value::Null => String::new(),
+            Value::String(text) => text,
+            other => other.to_string()
+        }
+    };
+
+    Ok(text)
+}
+
+// Fields of a row read with 'SELECT *', named and converted like in a JSON
+// export, for the machine-readable output of 'wallet list'.
+pub fn row_fields(table_name: &str, row: &Row) -> error::Result<Vec<(&'static str, Value)>> {
+    table_columns(table_name).iter()
+        .map(|(column, header, kind)| Ok((*header, to_json(*kind, row.get(*column)?)?)))
+        .collect()
+}
+
+fn select_rows(
+            conn: &Connection, table_name: &str, condition: &str, params: &[&dyn rusqlite::ToSql]
+        ) -> rusqlite::Result<Vec<Vec<SqlValue>>> {
+    let columns = table_columns(table_name);
+    let names: Vec<&str> = columns.iter().map(|(column, _, _)| *column).collect();
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM {} WHERE {} ORDER BY {}",
+        names.join(", "), table_name, condition, names[0]
+    ))?;
+
+    let rows = stmt.query_map(params, |row| {
+        (0..columns.len()).map(|index| row.get::<_, SqlValue>(index)).collect()
+    })?;
+
+    rows.collect()
+}
+
+fn write_csv(out: Box<dyn Write>, columns: &[Column], rows: Vec<Vec<SqlValue>>) -> error::Result<()> {
+    let mut writer = csv::Writer::from_writer(out);
+
+    writer.write_record(columns.iter().map(|(_, header, _)| *header)).map_err(csv_error)?;
+
+    for row in rows {
+        let record: Vec<String> = columns.iter().zip(row)
+            .map(|((_, _, kind), value)| to_text(*kind, value))
+            .collect::<error::Result<_>>()?;
+
+        writer.write_record(&record).map_err(csv_error)?;
+    }
+
+    writer.flush().map_err(write_error)
+}
+
+fn write_json(mut out: Box<dyn Write>, columns: &[Column], rows: Vec<Vec<SqlValue>>) -> error::Result<()> {
+    let mut items: Vec<Value> = Vec::new();
+
+    for row in rows {
+        let mut item = Map::new();
+
+        for ((_, header, kind), value) in columns.iter().zip(row) {
+            item.insert(header.to_string(), to_json(*kind, value)?);
+        }
+
+        items.push(Value::Object(item));
+    }
+
+    serde_json::to_writer_pretty(&mut out, &items).map_err(io::Error::from).map_err(write_error)?;
+    writeln!(out).map_err(write_error)
+}
+
+fn csv_error(e: csv::Error) -> WalletError {
+    write_error(io::Error::from(e))
+}
+
+fn write_error(e: io::Error) -> WalletError {
+    WalletError::Io("Unable to write the export!".to_string(), e)
+}
+
+// Standard output is used when there is no file.
+pub fn open_output(opt_path: Option<&str>) -> error::Result<Box<dyn Write>> {
+    match opt_path {
+        Some(path) => match File::create(path) {
+            Ok(file) => Ok(Box::new(file)),
+            Err(e) => Err(WalletError::Io(format!("Unable to create the file '{}'!", path), e))
+        },
+        None => {
+            output::raw_output();
+            Ok(Box::new(io::stdout()))
+        }
+    }
+}
+
+// Wallet 'export' subcommand is defined below.
+pub fn export(
+            table_name: &str, format: &str, opt_from: Option<&str>, opt_to: Option<&str>,
+            opt_id_account: Option<&str>, opt_path: Option<&str>
+        ) -> error::Result<()> {
+    let conn = walletdb::open_database()?;
+
+    let mut conditions: Vec<String> = vec!["1 = 1".to_string()];
+    let mut params: Vec<i64> = Vec::new();
+
+    if opt_from.is_some() || opt_to.is_some() {
+        let column = match date_column(table_name) {
+            Some(column) => column,
+            None => return error::invalid(format!("The items of '{}' have no dates, --from and --to can't be used!", table_name))
+        };
+
+        for (opt_date, operator) in [(opt_from, ">="), (opt_to, "<=")] {
+            if let Some(str_date) = opt_date {
+                params.push(utils::parse_date_arg(str_date)?);
+                conditions.push(format!("{} {} ?{}", column, operator, params.len()));
+            }
+        }
+    }
+
+    if opt_id_account.is_some() {
+        let account = walletdb::select_account(&conn, opt_id_account)?;
+
+        params.push(account.id);
+        conditions.push(format!("id_account = ?{}", params.len()));
+    }
+
+    let sql_params: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p as &dyn rusqlite::ToSql).collect();
+    let rows = select_rows(&conn, table_name, &conditions.join(" AND "), &sql_params)?;
+    let n_rows = rows.len();
+
+    let out = open_output(opt_path)?;
+
+    let columns = table_columns(table_name);
+    match format {
+        "json" => write_json(out, columns, rows)?,
+        _ => write_csv(out, columns, rows)?
+    }
+
+    if let Some(path) = opt_path {
+        output::message(format!("Exported {} items of '{}' to '{}'.", n_rows, table_name, path));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    #[test]
+    fn values_are_converted_by_kind() {
+        assert_eq!(to_text(Kind::Money, SqlValue::Integer(-1050)).unwrap(), "-10.50");
+        assert_eq!(to_json(Kind::Money, SqlValue::Integer(-1050)).unwrap(), Value::from(-10.5));
+        assert_eq!(to_text(Kind::Date, SqlValue::Integer(2_461_333)).unwrap(), "2026-10-19");
+        assert_eq!(to_json(Kind::Flag, SqlValue::Integer(1)).unwrap(), Value::from(true));
+        assert_eq!(to_text(Kind::TransactionType, SqlValue::Integer(EXPENSE)).unwrap(), "expense");
+        assert_eq!(to_text(Kind::Text, SqlValue::Null).unwrap(), "");
+        assert_eq!(to_json(Kind::Integer, SqlValue::Null).unwrap(), Value::Null);
+    }
+
+    #[test]
+    fn transactions_to_csv_and_json() {
+        let conn = walletdb::memory_database();
+        let path = env::temp_dir().join(format!("wallet-export-{}", process::id()));
+        let str_path = path.to_str().unwrap();
+
+        walletdb::new_account("Bank", 100.0, false, None).unwrap();
+        walletdb::new_account("Cash", 10.0, false, None).unwrap();
+        walletdb::new_transaction("Coffee, large", 3.5, EXPENSE, true, false, None, None).unwrap();
+        walletdb::new_transaction("Gift", 5.0, INCOMING, true, false, Some("2"), None).unwrap();
+        conn.execute("UPDATE transactions SET date = 2461333, charge_date = 2461333", []).unwrap();
+
+        export("transactions", "csv", Some("2026-10-19"), None, Some("1"), Some(str_path)).unwrap();
+        let csv = fs::read_to_string(&path).unwrap();
+        assert_eq!(csv.lines().collect::<Vec<_>>(), vec![
+            "id_transaction,date,message,value,charged,charge_date,type,category,payee,tags,fitid,id_transfer,id_account",
+            "1,2026-10-19,\"Coffee, large\",3.50,true,2026-10-19,expense,,,,,,1"
+        ]);
+
+        export("transactions", "json", None, Some("2026-10-18"), None, Some(str_path)).unwrap();
+        let json: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(json, Value::Array(Vec::new()));
+
+        export("accounts", "json", None, None, None, Some(str_path)).unwrap();
+        let json: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(json[1]["name"], "Cash");
+        assert_eq!(json[1]["balance"], 15.0);
+        assert_eq!(json[0]["is_default"], true);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,549 @@
+use crate::error;
+use crate::output;
+use crate::structs::{Account, MsiPurchase, Payment, QueuedPurchase};
+use crate::utils;
+use crate::walletdb;
+
+use chrono::Months;
+use rusqlite::{params, Connection};
+
+// Money expected to come in or out of an account on a day, in cents.
+#[derive(Clone)]
+pub struct Movement {
+    pub date: i64,
+    pub id_account: i64,
+    pub label: String,
+    pub cents: i64
+}
+
+// How the available balance of an account goes through the forecast.
+pub struct AccountForecast {
+    pub account: Account,
+    pub start: i64,
+    pub end: i64,
+    pub minimum: i64,
+    pub lowest: (i64, i64),
+    // First day that ends below the minimum and its available balance.
+    pub first_below: Option<(i64, i64)>
+}
+
+// Movements of the payments, recurring incomings, MSI installments and queued
+// purchases from the first day to the last one, in date order. Queued
+// purchases are bought on the first day. MSI purchases without billing date
+// are counted in the second value, they can't be placed in the calendar.
+pub fn scheduled_movements(
+            conn: &Connection, first_day: i64, last_day: i64, opt_account_id: Option<i64>
+        ) -> error::Result<(Vec<Movement>, usize)> {
+    let mut movements = Vec::new();
+    let in_account = |id_account: i64| opt_account_id.is_none_or(|id| id == id_account);
+
+    let payments: Vec<Payment> = {
+        let mut stmt = conn.prepare("SELECT * FROM payments ORDER BY billing_date, id_payment")?;
+        let rows = stmt.query_map([], Payment::from_row)?;
+
+        rows.collect::<rusqlite::Result<_>>()?
+    };
+
+    for payment in payments.iter().filter(|payment| in_account(payment.id_account)) {
+        let cents = (payment.price * 100.0).round() as i64;
+
+        for date in billing_dates(payment.billing_date, payment.periodicity, first_day, last_day)? {
+            movements.push(Movement {
+                date,
+                id_account: payment.id_account,
+                label: format!("{} '{}'", if payment.income { "Incoming" } else { "Payment" }, payment.name),
+                cents: if payment.income { cents } else { -cents }
+            });
+        }
+    }
+
+    let msi_purchases: Vec<MsiPurchase> = {
+        let mut stmt = conn.prepare("SELECT * FROM msi_purchases WHERE months_paid < installments ORDER BY id_msi")?;
+        let rows = stmt.query_map([], MsiPurchase::from_row)?;
+
+        rows.collect::<rusqlite::Result<_>>()?
+    };
+
+    let mut n_undated = 0;
+
+    for msi in msi_purchases.iter().filter(|msi| in_account(msi.id_account)) {
+        let billing_date = match msi.billing_date {
+            Some(billing_date) => billing_date,
+            None => {
+                n_undated += 1;
+                continue;
+            }
+        };
+
+        // Installments of past months are taken as paid.
+        let mut month = 0;
+        while add_months(billing_date, month)? < first_day {
+            month += 1;
+        }
+
+        for (n, cents) in msi.remaining_installments().into_iter().enumerate().skip(month as usize) {
+            let date = add_months(billing_date, n as u32)?;
+            if date > last_day {
+                break;
+            }
+
+            movements.push(Movement {
+                date,
+                id_account: msi.id_account,
+                label: format!("MSI '{}' {}/{}", msi.name, msi.months_paid + n as i64 + 1, msi.installments),
+                cents: -cents
+            });
+        }
+    }
+
+    let mut stmt = conn.prepare("SELECT * FROM queued_purchases ORDER BY id_queued")?;
+    let queued = stmt.query_map([], QueuedPurchase::from_row)?;
+
+    for purchase in queued {
+        let purchase = purchase?;
+
+        if in_account(purchase.id_account) {
+            movements.push(Movement {
+                date: first_day,
+                id_account: purchase.id_account,
+                label: format!("Queued '{}'", purchase.message),
+                cents: -(purchase.price * 100.0).round() as i64
+            });
+        }
+    }
+
+    movements.sort_by_key(|movement| (movement.date, movement.id_account));
+
+    Ok((movements, n_undated))
+}
+
+// Days a payment is billed between both days, every 'periodicity' months from
+// its billing date.
+fn billing_dates(billing_date: i64, periodicity: i64, first_day: i64, last_day: i64) -> error::Result<Vec<i64>> {
+    if periodicity <= 0 {
+        return Ok(if billing_date >= first_day && billing_date <= last_day { vec![billing_date] } else { vec![] });
+    }
+
+    let mut dates = Vec::new();
+
+    for n in 0.. {
+        let date = add_months(billing_date, n * periodicity as u32)?;
+        if date > last_day {
+            break;
+        }
+        if date >= first_day {
+            dates.push(date);
+        }
+    }
+
+    Ok(dates)
+}
+
+// Days past the end of the month are moved to its last day, like 01-31 plus
+// one month is 02-28.
+pub fn add_months(julian_date: i64, months: u32) -> error::Result<i64> {
+    let date = utils::julian_day_to_naive(julian_date)?;
+
+    Ok(date.checked_add_months(Months::new(months))
+        .map(utils::naive_to_julian_day)
+        .unwrap_or(i64::MAX))
+}
+
+// Available balance of the accounts at the end of every day with movements.
+// Movements of the same day are added together before the minimum is checked.
+pub fn simulate(accounts: Vec<Account>, movements: &[Movement]) -> Vec<AccountForecast> {
+    accounts.into_iter()
+        .map(|account| {
+            let start = (account.available * 100.0).round() as i64;
+            let minimum = (account.minimum * 100.0).round() as i64;
+
+            let mut forecast = AccountForecast {
+                start,
+                end: start,
+                minimum,
+                lowest: (utils::today_julian_day(), start),
+                first_below: None,
+                account
+            };
+
+            let account_movements: Vec<&Movement> = movements.iter()
+                .filter(|movement| movement.id_account == forecast.account.id)
+                .collect();
+
+            for (index, movement) in account_movements.iter().enumerate() {
+                forecast.end += movement.cents;
+
+                let end_of_day = account_movements.get(index + 1).is_none_or(|next| next.date != movement.date);
+                if !end_of_day {
+                    continue;
+                }
+
+                if forecast.end < forecast.lowest.1 {
+                    forecast.lowest = (movement.date, forecast.end);
+                }
+                if forecast.end < minimum && forecast.first_below.is_none() {
+                    forecast.first_below = Some((movement.date, forecast.end));
+                }
+            }
+
+            if start < minimum {
+                forecast.first_below = Some((utils::today_julian_day(), start));
+            }
+
+            forecast
+        })
+        .collect()
+}
+
+// Accounts to forecast, all of them or only the given one.
+pub fn forecast_accounts(conn: &Connection, opt_id_account: Option<&str>) -> error::Result<Vec<Account>> {
+    let opt_account_id = walletdb::account_filter(conn, opt_id_account)?;
+
+    let mut stmt = conn.prepare("SELECT * FROM accounts WHERE ?1 IS NULL OR id_account = ?1 ORDER BY id_account")?;
+    let accounts = stmt.query_map(params![opt_account_id], Account::from_row)?;
+
+    Ok(accounts.collect::<rusqlite::Result<_>>()?)
+}
+
+fn money(cents: i64) -> String {
+    format!("{:.2}", cents as f64 / 100.0)
+}
+
+// Wallet 'forecast' subcommand is defined below.
+// Each account starts with its available balance today and goes through the
+// scheduled movements day by day, the first day it ends below zero or below
+// its minimum is highlighted.
+pub fn forecast(days: i64, opt_id_account: Option<&str>) -> error::Result<()> {
+    if days < 1 {
+        return error::invalid("The forecast needs at least one day!");
+    }
+
+    let conn = walletdb::open_database()?;
+
+    let accounts = forecast_accounts(&conn, opt_id_account)?;
+
+    if accounts.is_empty() {
+        output::message("Table 'accounts' is empty! Try 'wallet new --help'.");
+        return Ok(());
+    }
+
+    let today = utils::today_julian_day();
+    let last_day = today + days - 1;
+    let opt_account_id = if opt_id_account.is_some() { Some(accounts[0].id) } else { None };
+
+    let (movements, n_undated) = scheduled_movements(&conn, today, last_day, opt_account_id)?;
+    let forecasts = simulate(accounts, &movements);
+
+    output::message(format!(
+        "Forecast of the next {} days, from {} to {}:\n",
+        days, utils::julian_day_to_iso(today)?, utils::julian_day_to_iso(last_day)?
+    ));
+
+    if movements.is_empty() {
+        output::message("Nothing scheduled! Try 'wallet new payment --help'.\n");
+    } else {
+        output::message(format!("{:<10}  {:<20} {:<30} {:>12} {:>14}", "Date", "Account", "Movement", "Value", "Available"));
+
+        let mut available: Vec<i64> = forecasts.iter().map(|forecast| forecast.start).collect();
+
+        for (n, movement) in movements.iter().enumerate() {
+            let index = forecasts.iter().position(|forecast| forecast.account.id == movement.id_account);
+            let index = match index {
+                Some(index) => index,
+                None => continue
+            };
+
+            available[index] += movement.cents;
+
+            // Movements are sorted by day and account, the day ends with the
+            // last movement of the account.
+            let end_of_day = movements.get(n + 1)
+                .is_none_or(|next| next.date != movement.date || next.id_account != movement.id_account);
+
+            let forecast = &forecasts[index];
+            let marker = match forecast.first_below {
+                Some((date, _)) if end_of_day && date == movement.date => {
+                    format!("  << first day below {}", money(forecast.minimum))
+                }
+                _ => String::new()
+            };
+
+            output::message(format!(
+                "{:<10}  {:<20} {:<30} {:>12} {:>14}{}",
+                utils::julian_day_to_iso(movement.date)?, forecast.account.name, movement.label,
+                format!("{:+.2}", movement.cents as f64 / 100.0), money(available[index]), marker
+            ));
+        }
+
+        output::message("");
+    }
+
+    output::message(format!("{:<20} {:>14} {:>14} {:>10}  {:>12}  Status", "Account", "Today", "Lowest", "On", "Minimum"));
+
+    for forecast in &forecasts {
+        let lowest_date = utils::julian_day_to_iso(forecast.lowest.0)?;
+        let first_below = forecast.first_below.map(|(date, _)| utils::julian_day_to_iso(date)).transpose()?;
+
+        let status = match (forecast.first_below, &first_below) {
+            (Some((_, balance)), Some(date)) => format!(
+                "Below {} on {} ({})", money(forecast.minimum), date, money(balance)
+            ),
+            _ => "OK".to_string()
+        };
+
+        output::message(format!(
+            "{:<20} {:>14} {:>14} {:>10}  {:>12}  {}",
+            forecast.account.name, money(forecast.start), money(forecast.lowest.1),
+            lowest_date, money(forecast.minimum), status
+        ));
+        output::record(vec![
+            ("id_account", forecast.account.id.into()),
+            ("name", forecast.account.name.as_str().into()),
+            ("today", output::money(forecast.start)),
+            ("end", output::money(forecast.end)),
+            ("lowest", output::money(forecast.lowest.1)),
+            ("lowest_date", lowest_date.into()),
+            ("minimum", output::money(forecast.minimum)),
+            ("first_below", first_below.into())
+        ]);
+    }
+
+    if n_undated > 0 {
+        output::message(format!("\n{} MSI purchases without billing date are left out.", n_undated));
+    }
+
+    Ok(())
+}
+
+// Wallet 'afford' subcommand is defined below.
+// The purchase is added to the forecast of the account, paid today and, with
+// 'msi', in monthly installments from next month. Nothing is written.
+pub fn afford(amount: f64, opt_id_account: Option<&str>, opt_msi: Option<i64>, days: i64) -> error::Result<()> {
+    if amount < 0.01 {
+        return error::invalid("The value of a purchase must be at least one cent '0.01'!");
+    } else if opt_msi.is_some_and(|installments| installments < 1) {
+        return error::invalid("A MSI plan needs at least one installment!");
+    } else if days < 1 {
+        return error::invalid("The forecast needs at least one day!");
+    }
+
+    let conn = walletdb::open_database()?;
+
+    let account = walletdb::select_account(&conn, opt_id_account)?;
+
+    let today = utils::today_julian_day();
+    let last_day = today + days - 1;
+    let cents = (amount * 100.0).round() as i64;
+
+    let (movements, n_undated) = scheduled_movements(&conn, today, last_day, Some(account.id))?;
+
+    output::message(format!(
+        "Purchase of ${:.2} in account '{}', available ${:.2}, minimum ${:.2}.",
+        amount, account.name, account.available, account.minimum
+    ));
+    output::message(format!(
+        "Scheduled movements of the next {} days, to {}, are counted.\n",
+        days, utils::julian_day_to_iso(last_day)?
+    ));
+
+    let id_account = account.id;
+    let with_movements = |extra: Vec<Movement>| -> AccountForecast {
+        let mut all: Vec<Movement> = movements.iter().cloned().chain(extra).collect();
+        all.sort_by_key(|movement| movement.date);
+
+        simulate(vec![account.clone()], &all).remove(0)
+    };
+
+    let without = with_movements(vec![]);
+    print_option("without", "Without the purchase", &without, false)?;
+
+    let paid_now = with_movements(vec![Movement { date: today, id_account, label: "Purchase".to_string(), cents: -cents }]);
+    print_option("today", "Paid today", &paid_now, true)?;
+
+    if let Some(installments) = opt_msi {
+        let with_msi = with_movements(installment_movements(amount, installments, id_account, today, last_day)?);
+        print_option("msi", &format!("In {} MSI of ${:.2}", installments, amount / installments as f64), &with_msi, true)?;
+    }
+
+    if n_undated > 0 {
+        output::message(format!("\n{} MSI purchases without billing date are left out.", n_undated));
+    }
+
+    Ok(())
+}
+
+// Lowest balance of the account with one of the options and whether it keeps
+// the account above its minimum. The option is the short name in the records.
+fn print_option(option: &str, title: &str, forecast: &AccountForecast, purchase: bool) -> error::Result<()> {
+    let lowest_date = utils::julian_day_to_iso(forecast.lowest.0)?;
+    let first_below = forecast.first_below.map(|(date, _)| utils::julian_day_to_iso(date)).transpose()?;
+
+    let verdict = match (forecast.first_below, &first_below, purchase) {
+        (Some((date, balance)), Some(str_date), _) => format!(
+            "{}below {} {} ({})",
+            if purchase { "doesn't fit, " } else { "" },
+            money(forecast.minimum),
+            if date == utils::today_julian_day() { "today".to_string() } else { format!("on {}", str_date) },
+            money(balance)
+        ),
+        (_, _, true) => "fits".to_string(),
+        (_, _, false) => format!("never below {}", money(forecast.minimum))
+    };
+
+    output::message(format!(
+        "{:<26} lowest {:>12} on {}, {}",
+        format!("{}:", title), money(forecast.lowest.1), lowest_date, verdict
+    ));
+    output::record(vec![
+        ("option", option.into()),
+        ("lowest", output::money(forecast.lowest.1)),
+        ("lowest_date", lowest_date.into()),
+        ("minimum", output::money(forecast.minimum)),
+        ("first_below", first_below.into()),
+        ("fits", forecast.first_below.is_none().into())
+    ]);
+
+    Ok(())
+}
+
+// Installments of a purchase made today in MSI, billed every month from the
+// next one, up to the last day.
+fn installment_movements(
+            price: f64, installments: i64, id_account: i64, today: i64, last_day: i64
+        ) -> error::Result<Vec<Movement>> {
+    let plan = MsiPurchase {
+        id: -1,
+        name: "Purchase".to_string(),
+        price,
+        installments,
+        months_paid: 0,
+        id_account,
+        billing_date: Some(add_months(today, 1)?)
+    };
+
+    let mut movements = Vec::new();
+
+    for (n, installment) in plan.remaining_installments().into_iter().enumerate() {
+        let date = add_months(today, n as u32 + 1)?;
+
+        if date <= last_day {
+            movements.push(Movement {
+                date,
+                id_account,
+                label: "Purchase".to_string(),
+                cents: -installment
+            });
+        }
+    }
+
+    Ok(movements)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(str_date: &str) -> i64 {
+        utils::parse_date(str_date).unwrap()
+    }
+
+    fn movement(date: i64, cents: i64) -> Movement {
+        Movement { date, id_account: 1, label: String::new(), cents }
+    }
+
+    #[test]
+    fn months_end_on_their_last_day() {
+        assert_eq!(add_months(date("2026-01-31"), 1).unwrap(), date("2026-02-28"));
+        assert_eq!(add_months(date("2026-01-31"), 3).unwrap(), date("2026-04-30"));
+        assert_eq!(add_months(date("2026-10-19"), 0).unwrap(), date("2026-10-19"));
+    }
+
+    #[test]
+    fn payments_are_billed_every_period() {
+        assert_eq!(
+            billing_dates(date("2026-01-31"), 1, date("2026-02-01"), date("2026-04-30")).unwrap(),
+            vec![date("2026-02-28"), date("2026-03-31"), date("2026-04-30")]
+        );
+        assert_eq!(billing_dates(date("2026-01-15"), 6, date("2026-01-01"), date("2026-12-31")).unwrap(), vec![date("2026-01-15"), date("2026-07-15")]);
+        assert_eq!(billing_dates(date("2026-01-15"), 0, date("2026-02-01"), date("2026-12-31")).unwrap(), Vec::<i64>::new());
+    }
+
+    #[test]
+    fn the_last_installment_takes_the_rounding() {
+        let msi = MsiPurchase {
+            id: 1,
+            name: "Phone".to_string(),
+            price: 100.0,
+            installments: 3,
+            months_paid: 1,
+            id_account: 1,
+            billing_date: None
+        };
+
+        assert_eq!(msi.remaining_installments(), vec![3333, 3334]);
+        assert_eq!(MsiPurchase { months_paid: 3, ..msi }.remaining_installments(), Vec::<i64>::new());
+    }
+
+    #[test]
+    fn the_minimum_is_checked_at_the_end_of_each_day() {
+        let account = Account { id: 1, available: 100.0, minimum: 50.0, ..Account::empty() };
+        let movements = [movement(10, -6000), movement(10, 2000), movement(12, -2000), movement(13, 5000)];
+
+        let forecast = &simulate(vec![account], &movements)[0];
+
+        assert_eq!((forecast.start, forecast.end), (10000, 9000));
+        assert_eq!(forecast.lowest, (12, 4000));
+        assert_eq!(forecast.first_below, Some((12, 4000)));
+    }
+
+    #[test]
+    fn purchases_in_msi_start_next_month() {
+        let movements = installment_movements(100.0, 3, 1, date("2026-10-31"), date("2026-12-31")).unwrap();
+        let summary: Vec<(i64, i64)> = movements.iter().map(|m| (m.date, m.cents)).collect();
+
+        assert_eq!(summary, vec![(date("2026-11-30"), -3333), (date("2026-12-31"), -3333)]);
+    }
+
+    #[test]
+    fn a_purchase_fits_when_the_minimum_is_kept() {
+        let account = Account { id: 1, available: 100.0, minimum: 20.0, ..Account::empty() };
+        let today = date("2026-10-19");
+
+        let paid_now = simulate(vec![account.clone()], &[movement(today, -9000)]).remove(0);
+        assert_eq!(paid_now.first_below, Some((today, 1000)));
+
+        let in_msi = simulate(vec![account], &installment_movements(60.0, 3, 1, today, today + 100).unwrap()).remove(0);
+        assert_eq!(in_msi.first_below, None);
+        assert_eq!(in_msi.lowest, (date("2027-01-19"), 4000));
+    }
+
+    #[test]
+    fn movements_of_payments_installments_and_queued_purchases() {
+        let conn = walletdb::memory_database();
+
+        walletdb::new_account("Bank", 100.0, false, None).unwrap();
+        walletdb::new_account("Card", 0.0, true, None).unwrap();
+        walletdb::new_payment("Rent", 40.0, "2026-11-01", 1, false, None).unwrap();
+        walletdb::new_payment("Salary", 90.0, "2026-11-15", 1, true, None).unwrap();
+        walletdb::new_msi("Phone", 30.0, 3, "2026-11-20", 1, Some("2")).unwrap();
+        walletdb::new_queued("Chair", 15.0, None).unwrap();
+
+        let (movements, n_undated) = scheduled_movements(&conn, date("2026-10-19"), date("2026-12-31"), None).unwrap();
+        let summary: Vec<(i64, i64, i64)> = movements.iter().map(|m| (m.date, m.id_account, m.cents)).collect();
+
+        assert_eq!(n_undated, 0);
+        assert_eq!(summary, vec![
+            (date("2026-10-19"), 1, -1500),
+            (date("2026-11-01"), 1, -4000),
+            (date("2026-11-15"), 1, 9000),
+            (date("2026-11-20"), 2, -1000),
+            (date("2026-12-01"), 1, -4000),
+            (date("2026-12-15"), 1, 9000),
+            (date("2026-12-20"), 2, -1000)
+        ]);
+        assert_eq!(movements[3].label, "MSI 'Phone' 2/3");
+
+        let (movements, _) = scheduled_movements(&conn, date("2026-10-19"), date("2026-12-31"), Some(2)).unwrap();
+        assert_eq!(movements.len(), 2);
+    }
+}
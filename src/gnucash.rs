@@ -0,0 +1,472 @@
+use crate::error;
+use crate::importer::{ForeignAccount, HistoryEntry, HistoryImport, Leg, Target};
+use crate::journal;
+use crate::utils;
+use crate::walletdb;
+
+use std::collections::HashMap;
+use std::io::Read;
+
+use chrono::NaiveDate;
+use flate2::read::GzDecoder;
+use rusqlite::{Connection, OpenFlags};
+
+const GNC_NAMESPACE: &str = "http://www.gnucash.org/XML/gnc";
+
+struct GncAccount {
+    name: String,
+    account_type: String,
+    parent: Option<String>,
+    currency: Option<String>
+}
+
+struct GncSplit {
+    account: String,
+    cents: i64,
+    memo: String
+}
+
+struct GncTransaction {
+    date: i64,
+    description: String,
+    currency: Option<String>,
+    splits: Vec<GncSplit>
+}
+
+#[derive(Default)]
+struct Book {
+    accounts: HashMap<String, GncAccount>,
+    transactions: Vec<GncTransaction>
+}
+
+// Wallet 'import gnucash' subcommand is defined below.
+// Bank, cash, asset, credit and liability accounts become wallet accounts,
+// income and expense accounts become categories named after their path.
+pub fn import_gnucash(path: &str, dry_run: bool) -> error::Result<()> {
+    let content = utils::read_file(path)?;
+
+    let result = if content.starts_with(b"SQLite format 3\0") {
+        read_sqlite_book(path).map_err(|e| e.to_string())
+    } else {
+        read_xml_book(&content)
+    };
+
+    let book = match result {
+        Ok(book) => book,
+        Err(reason) => return error::invalid(format!("Unable to read the GnuCash file '{}'! Error: {}", path, reason))
+    };
+
+    let mut conn = walletdb::open_database()?;
+
+    let tx = conn.transaction()?;
+    let id_operation = journal::begin(&tx)?;
+
+    let mut import = HistoryImport::new(&tx, id_operation, "gnucash");
+
+    for transaction in &book.transactions {
+        // Scheduled transactions live under a template tree, they are not history.
+        if transaction.splits.iter().any(|split| book.is_template(&split.account)) {
+            continue;
+        }
+
+        let location = format!("{} '{}'", utils::julian_day_to_iso(transaction.date)?, transaction.description);
+
+        match book.history_entry(transaction) {
+            Ok(entry) => import.import_entry(&location, &entry)?,
+            Err(reason) => import.skip(&location, &reason)
+        }
+    }
+
+    import.finish(dry_run)?;
+
+    if dry_run {
+        return Ok(());
+    }
+
+    Ok(tx.commit()?)
+}
+
+impl Book {
+    // Names of the account and its parents, without the root account.
+    fn path(&self, guid: &str) -> Vec<&str> {
+        let mut names = Vec::new();
+        let mut current = self.accounts.get(guid);
+
+        while let Some(account) = current {
+            if account.account_type == "ROOT" {
+                break;
+            }
+
+            names.insert(0, account.name.as_str());
+            current = account.parent.as_deref().and_then(|parent| self.accounts.get(parent));
+        }
+
+        names
+    }
+
+    fn is_template(&self, guid: &str) -> bool {
+        let mut current = self.accounts.get(guid);
+
+        while let Some(account) = current {
+            if account.account_type == "ROOT" {
+                return account.name == "Template Root";
+            }
+
+            current = account.parent.as_deref().and_then(|parent| self.accounts.get(parent));
+        }
+
+        false
+    }
+
+    // The top account, like 'Assets' or 'Expenses', is left out of the name.
+    fn short_name(&self, guid: &str) -> String {
+        let path = self.path(guid);
+
+        if path.len() > 1 { path[1..].join(":") } else { path.join(":") }
+    }
+
+    fn history_entry(&self, transaction: &GncTransaction) -> Result<HistoryEntry, String> {
+        let mut legs: Vec<Leg> = Vec::new();
+
+        for split in &transaction.splits {
+            let account = self.accounts.get(&split.account)
+                .ok_or(format!("split of an unknown account '{}'", split.account))?;
+
+            let target = match account.account_type.as_str() {
+                "BANK" | "CASH" | "ASSET" | "RECEIVABLE" | "CREDIT" | "LIABILITY" | "PAYABLE" => {
+                    if account.currency != transaction.currency {
+                        return Err(format!("'{}' is in another currency", account.name));
+                    }
+
+                    Target::Account(ForeignAccount {
+                        key: split.account.clone(),
+                        name: self.short_name(&split.account),
+                        credit: matches!(account.account_type.as_str(), "CREDIT" | "LIABILITY" | "PAYABLE"),
+                        currency: account.currency.clone()
+                    })
+                }
+                "INCOME" | "EXPENSE" => Target::Category(Some(self.short_name(&split.account).to_lowercase())),
+                "EQUITY" if account.name.to_lowercase().contains("opening") => Target::Opening,
+                "EQUITY" => Target::Equity,
+                other => return Err(format!("'{}' is a {} account", account.name, other.to_lowercase()))
+            };
+
+            legs.push(Leg { target, cents: split.cents });
+        }
+
+        let message = if transaction.description.is_empty() {
+            transaction.splits.iter().map(|split| split.memo.as_str()).find(|memo| !memo.is_empty())
+                .unwrap_or("GnuCash transaction").to_string()
+        } else {
+            transaction.description.clone()
+        };
+
+        Ok(HistoryEntry {
+            date: transaction.date,
+            charged: transaction.date <= utils::today_julian_day(),
+            payee: None,
+            message,
+            tags: None,
+            legs
+        })
+    }
+}
+
+// Amounts are fractions like '-85050/100', rounded to cents.
+fn fraction_to_cents(numerator: i64, denominator: i64) -> i64 {
+    if denominator == 0 {
+        return 0;
+    }
+
+    let scaled = numerator as i128 * 100;
+    let denominator = denominator as i128;
+    let rounded = (scaled.abs() * 2 + denominator.abs()) / (denominator.abs() * 2);
+
+    if (scaled < 0) != (denominator < 0) { -rounded as i64 } else { rounded as i64 }
+}
+
+// Dates look like '2023-01-05 10:59:00 +0000' or '20230105105900'.
+fn parse_gnc_date(text: &str) -> Option<i64> {
+    let text = text.trim();
+
+    let date = text.get(0..10).and_then(|day| NaiveDate::parse_from_str(day, "%Y-%m-%d").ok())
+        .or_else(|| text.get(0..8).and_then(|day| NaiveDate::parse_from_str(day, "%Y%m%d").ok()))?;
+
+    Some(utils::naive_to_julian_day(date))
+}
+
+fn read_sqlite_book(path: &str) -> rusqlite::Result<Book> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let mut book = Book::default();
+
+    let currencies: HashMap<String, String> = {
+        let mut stmt = conn.prepare("SELECT guid, mnemonic FROM commodities")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+        rows.collect::<rusqlite::Result<_>>()?
+    };
+
+    {
+        let mut stmt = conn.prepare("SELECT guid, name, account_type, parent_guid, commodity_guid FROM accounts")?;
+        let mut rows = stmt.query([])?;
+
+        while let Some(row) = rows.next()? {
+            let commodity: Option<String> = row.get(4)?;
+
+            book.accounts.insert(row.get(0)?, GncAccount {
+                name: row.get(1)?,
+                account_type: row.get(2)?,
+                parent: row.get(3)?,
+                currency: commodity.and_then(|guid| currencies.get(&guid).cloned())
+            });
+        }
+    }
+
+    let mut splits: HashMap<String, Vec<GncSplit>> = HashMap::new();
+    {
+        let mut stmt = conn.prepare("SELECT tx_guid, account_guid, value_num, value_denom, memo FROM splits")?;
+        let mut rows = stmt.query([])?;
+
+        while let Some(row) = rows.next()? {
+            splits.entry(row.get(0)?).or_default().push(GncSplit {
+                account: row.get(1)?,
+                cents: fraction_to_cents(row.get(2)?, row.get(3)?),
+                memo: row.get::<_, Option<String>>(4)?.unwrap_or_default()
+            });
+        }
+    }
+
+    let mut stmt = conn.prepare("SELECT guid, post_date, description, currency_guid FROM transactions ORDER BY post_date")?;
+    let mut rows = stmt.query([])?;
+
+    while let Some(row) = rows.next()? {
+        let guid: String = row.get(0)?;
+        let post_date: Option<String> = row.get(1)?;
+        let currency: String = row.get(3)?;
+
+        book.transactions.push(GncTransaction {
+            date: post_date.as_deref().and_then(parse_gnc_date).unwrap_or_else(utils::today_julian_day),
+            description: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+            currency: currencies.get(&currency).cloned(),
+            splits: splits.remove(&guid).unwrap_or_default()
+        });
+    }
+
+    Ok(book)
+}
+
+// XML books are usually compressed with gzip.
+fn read_xml_book(content: &[u8]) -> Result<Book, String> {
+    let text = if content.starts_with(&[0x1f, 0x8b]) {
+        let mut text = String::new();
+        GzDecoder::new(content).read_to_string(&mut text).map_err(|e| e.to_string())?;
+        text
+    } else {
+        String::from_utf8_lossy(content).to_string()
+    };
+
+    let document = roxmltree::Document::parse(&text).map_err(|e| e.to_string())?;
+    let mut book = Book::default();
+
+    let child = |node: roxmltree::Node<'_, '_>, name: &str| -> Option<String> {
+        node.children().find(|c| c.tag_name().name() == name).and_then(|c| c.text()).map(|t| t.trim().to_string())
+    };
+    let nested = |node: roxmltree::Node<'_, '_>, name: &str, inner: &str| -> Option<String> {
+        node.children().find(|c| c.tag_name().name() == name).and_then(|c| child(c, inner))
+    };
+
+    let is_gnc = |node: &roxmltree::Node<'_, '_>, name: &str| {
+        node.tag_name().name() == name && node.tag_name().namespace() == Some(GNC_NAMESPACE)
+    };
+
+    let gnc_book = document.descendants()
+        .find(|node| is_gnc(node, "book"))
+        .ok_or("no book found")?;
+
+    // Accounts of the template tree are inside 'template-transactions'.
+    for node in document.descendants().filter(|node| is_gnc(node, "account")) {
+        let guid = match child(node, "id") {
+            Some(guid) => guid,
+            None => continue
+        };
+
+        book.accounts.insert(guid, GncAccount {
+            name: child(node, "name").unwrap_or_default(),
+            account_type: child(node, "type").unwrap_or_default(),
+            parent: child(node, "parent"),
+            currency: nested(node, "commodity", "id")
+        });
+    }
+
+    for node in gnc_book.children().filter(|node| is_gnc(node, "transaction")) {
+        let splits = node.children()
+            .find(|c| c.tag_name().name() == "splits")
+            .map(|splits| splits.children().filter(|c| c.tag_name().name() == "split").collect::<Vec<_>>())
+            .unwrap_or_default();
+
+        let mut gnc_splits = Vec::new();
+        for split in splits {
+            let value = child(split, "value").unwrap_or_default();
+            let (numerator, denominator) = value.split_once('/').unwrap_or((&value, "1"));
+
+            gnc_splits.push(GncSplit {
+                account: child(split, "account").unwrap_or_default(),
+                cents: fraction_to_cents(
+                    numerator.parse().map_err(|_| format!("invalid value '{}'", value))?,
+                    denominator.parse().map_err(|_| format!("invalid value '{}'", value))?
+                ),
+                memo: child(split, "memo").unwrap_or_default()
+            });
+        }
+
+        book.transactions.push(GncTransaction {
+            date: nested(node, "date-posted", "date").as_deref().and_then(parse_gnc_date)
+                .unwrap_or_else(utils::today_julian_day),
+            description: child(node, "description").unwrap_or_default(),
+            currency: nested(node, "currency", "id"),
+            splits: gnc_splits
+        });
+    }
+
+    book.transactions.sort_by_key(|transaction| transaction.date);
+
+    Ok(book)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{env, fs, process};
+
+    const XML: &str = r#"<?xml version="1.0" encoding="utf-8" ?>
+<gnc-v2 xmlns:gnc="http://www.gnucash.org/XML/gnc" xmlns:act="http://www.gnucash.org/XML/act"
+        xmlns:trn="http://www.gnucash.org/XML/trn" xmlns:cmdty="http://www.gnucash.org/XML/cmdty"
+        xmlns:ts="http://www.gnucash.org/XML/ts" xmlns:split="http://www.gnucash.org/XML/split">
+<gnc:book version="2.0.0">
+<gnc:account version="2.0.0">
+  <act:name>Root Account</act:name><act:id type="guid">root</act:id><act:type>ROOT</act:type>
+</gnc:account>
+<gnc:account version="2.0.0">
+  <act:name>Assets</act:name><act:id type="guid">assets</act:id><act:type>ASSET</act:type>
+  <act:parent type="guid">root</act:parent>
+</gnc:account>
+<gnc:account version="2.0.0">
+  <act:name>Checking</act:name><act:id type="guid">checking</act:id><act:type>BANK</act:type>
+  <act:commodity><cmdty:space>CURRENCY</cmdty:space><cmdty:id>USD</cmdty:id></act:commodity>
+  <act:parent type="guid">assets</act:parent>
+</gnc:account>
+<gnc:account version="2.0.0">
+  <act:name>Groceries</act:name><act:id type="guid">groceries</act:id><act:type>EXPENSE</act:type>
+  <act:commodity><cmdty:space>CURRENCY</cmdty:space><cmdty:id>USD</cmdty:id></act:commodity>
+  <act:parent type="guid">root</act:parent>
+</gnc:account>
+<gnc:account version="2.0.0">
+  <act:name>Opening Balances</act:name><act:id type="guid">opening</act:id><act:type>EQUITY</act:type>
+  <act:parent type="guid">root</act:parent>
+</gnc:account>
+<gnc:transaction version="2.0.0">
+  <trn:id type="guid">t2</trn:id>
+  <trn:currency><cmdty:space>CURRENCY</cmdty:space><cmdty:id>USD</cmdty:id></trn:currency>
+  <trn:date-posted><ts:date>2026-01-05 10:59:00 +0000</ts:date></trn:date-posted>
+  <trn:description></trn:description>
+  <trn:splits>
+    <trn:split><split:memo>Market</split:memo><split:value>-1250/100</split:value><split:account type="guid">checking</split:account></trn:split>
+    <trn:split><split:value>1250/100</split:value><split:account type="guid">groceries</split:account></trn:split>
+  </trn:splits>
+</gnc:transaction>
+<gnc:transaction version="2.0.0">
+  <trn:id type="guid">t1</trn:id>
+  <trn:currency><cmdty:space>CURRENCY</cmdty:space><cmdty:id>USD</cmdty:id></trn:currency>
+  <trn:date-posted><ts:date>2026-01-01 10:59:00 +0000</ts:date></trn:date-posted>
+  <trn:description>Opening</trn:description>
+  <trn:splits>
+    <trn:split><split:value>10000/100</split:value><split:account type="guid">checking</split:account></trn:split>
+    <trn:split><split:value>-10000/100</split:value><split:account type="guid">opening</split:account></trn:split>
+  </trn:splits>
+</gnc:transaction>
+</gnc:book>
+</gnc-v2>
+"#;
+
+    fn value(conn: &Connection, sql: &str) -> i64 {
+        conn.query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn fractions_are_rounded_to_cents() {
+        assert_eq!(fraction_to_cents(-85050, 100), -85050);
+        assert_eq!(fraction_to_cents(12345, 1000), 1235);
+        assert_eq!(fraction_to_cents(-12345, 1000), -1235);
+        assert_eq!(fraction_to_cents(1, 3), 33);
+        assert_eq!(fraction_to_cents(7, 0), 0);
+    }
+
+    #[test]
+    fn dates_of_both_formats() {
+        assert_eq!(parse_gnc_date("2026-10-19 10:59:00 +0000"), Some(2461333));
+        assert_eq!(parse_gnc_date("20261019105900"), Some(2461333));
+        assert_eq!(parse_gnc_date("19/10/2026"), None);
+    }
+
+    #[test]
+    fn xml_books_are_read() {
+        let book = read_xml_book(XML.as_bytes()).unwrap();
+
+        assert_eq!(book.accounts.len(), 5);
+        assert_eq!(book.short_name("checking"), "Checking");
+        assert_eq!(book.path("checking"), vec!["Assets", "Checking"]);
+        assert_eq!(book.transactions[0].description, "Opening");
+
+        let entry = book.history_entry(&book.transactions[1]).unwrap();
+        assert_eq!(entry.message, "Market");
+        assert!(matches!(&entry.legs[1].target, Target::Category(Some(category)) if category == "groceries"));
+    }
+
+    #[test]
+    fn sqlite_books_are_read() {
+        let path = env::temp_dir().join(format!("wallet-book-{}.gnucash", process::id()));
+        let book = Connection::open(&path).unwrap();
+        book.execute_batch("
+            CREATE TABLE commodities (guid TEXT, mnemonic TEXT);
+            CREATE TABLE accounts (guid TEXT, name TEXT, account_type TEXT, parent_guid TEXT, commodity_guid TEXT);
+            CREATE TABLE splits (tx_guid TEXT, account_guid TEXT, value_num INTEGER, value_denom INTEGER, memo TEXT);
+            CREATE TABLE transactions (guid TEXT, post_date TEXT, description TEXT, currency_guid TEXT);
+            INSERT INTO commodities VALUES ('usd', 'USD');
+            INSERT INTO accounts VALUES ('root', 'Root Account', 'ROOT', NULL, NULL);
+            INSERT INTO accounts VALUES ('cash', 'Cash', 'CASH', 'root', 'usd');
+            INSERT INTO accounts VALUES ('food', 'Food', 'EXPENSE', 'root', 'usd');
+            INSERT INTO splits VALUES ('t1', 'cash', -350, 100, NULL);
+            INSERT INTO splits VALUES ('t1', 'food', 350, 100, 'Coffee');
+            INSERT INTO transactions VALUES ('t1', '2026-10-19 10:59:00', 'Cafe', 'usd');
+        ").unwrap();
+        drop(book);
+
+        let book = read_sqlite_book(path.to_str().unwrap()).unwrap();
+        assert_eq!(book.accounts["cash"].currency.as_deref(), Some("USD"));
+        assert_eq!(book.transactions[0].date, 2461333);
+        assert_eq!(book.transactions[0].splits[0].cents, -350);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn books_are_imported_once() {
+        let conn = walletdb::memory_database();
+        let path = env::temp_dir().join(format!("wallet-import-{}.gnucash", process::id()));
+        fs::write(&path, XML).unwrap();
+
+        import_gnucash(path.to_str().unwrap(), false).unwrap();
+        import_gnucash(path.to_str().unwrap(), false).unwrap();
+
+        assert_eq!(value(&conn, "SELECT COUNT(*) FROM accounts"), 1);
+        assert_eq!(value(&conn, "SELECT COUNT(*) FROM transactions"), 1);
+        assert_eq!(value(&conn, "SELECT balance FROM accounts"), 8750);
+        assert_eq!(value(&conn, "SELECT opening_balance FROM accounts"), 10000);
+
+        journal::undo(2).unwrap();
+        assert_eq!(value(&conn, "SELECT COUNT(*) FROM accounts"), 0);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
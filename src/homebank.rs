@@ -0,0 +1,258 @@
+use crate::error;
+use crate::importer::{ForeignAccount, HistoryEntry, HistoryImport, Leg, Target};
+use crate::journal;
+use crate::utils;
+use crate::walletdb;
+
+use std::collections::HashMap;
+
+use roxmltree::Node;
+
+// HomeBank dates are GLib Julian days, day 1 is 0001-01-01.
+const GDATE_OFFSET: i64 = 1_721_425;
+
+// Values of the 'st' attribute of operations.
+const STATUS_REMIND: i64 = 3;
+const STATUS_VOID: i64 = 4;
+
+// Wallet 'import homebank' subcommand is defined below.
+// Accounts keep their initial balance, operations become transactions with the
+// category path and payee they have in HomeBank, internal transfers become
+// transfers.
+pub fn import_homebank(path: &str, dry_run: bool) -> error::Result<()> {
+    let content = String::from_utf8_lossy(&utils::read_file(path)?).to_string();
+
+    let document = match roxmltree::Document::parse(&content) {
+        Ok(document) => document,
+        Err(e) => return error::invalid(format!("Unable to read the HomeBank file '{}'! Error: {}", path, e))
+    };
+
+    let elements = |name: &'static str| document.root_element().children().filter(move |node| node.has_tag_name(name));
+
+    let currencies: HashMap<i64, String> = elements("cur")
+        .filter_map(|node| Some((attribute(node, "key")?, node.attribute("iso")?.to_string())))
+        .collect();
+
+    let payees: HashMap<i64, String> = elements("pay")
+        .filter_map(|node| Some((attribute(node, "key")?, node.attribute("name")?.to_string())))
+        .collect();
+
+    let category_nodes: HashMap<i64, (Option<i64>, String)> = elements("cat")
+        .filter_map(|node| Some((
+            attribute(node, "key")?,
+            (attribute(node, "parent"), node.attribute("name")?.to_string())
+        )))
+        .collect();
+
+    // Subcategories are written as 'parent:child'.
+    let categories: HashMap<i64, String> = category_nodes.iter()
+        .map(|(key, (opt_parent, name))| {
+            let path = match opt_parent.and_then(|parent| category_nodes.get(&parent)) {
+                Some((_, parent_name)) => format!("{}:{}", parent_name, name),
+                None => name.clone()
+            };
+
+            (*key, path.to_lowercase())
+        })
+        .collect();
+
+    let accounts: HashMap<i64, (ForeignAccount, i64)> = elements("account")
+        .filter_map(|node| {
+            let key: i64 = attribute(node, "key")?;
+            let account_type: i64 = attribute(node, "type").unwrap_or(1);
+
+            let account = ForeignAccount {
+                key: key.to_string(),
+                name: node.attribute("name")?.to_string(),
+                credit: account_type == 4 || account_type == 5,
+                currency: attribute(node, "curr").and_then(|curr| currencies.get(&curr).cloned())
+            };
+
+            Some((key, (account, amount(node, "initial").unwrap_or(0))))
+        })
+        .collect();
+
+    let mut conn = walletdb::open_database()?;
+
+    let tx = conn.transaction()?;
+    let id_operation = journal::begin(&tx)?;
+
+    let mut import = HistoryImport::new(&tx, id_operation, "homebank");
+
+    let mut keys: Vec<&i64> = accounts.keys().collect();
+    keys.sort();
+
+    for key in keys {
+        let (account, initial) = &accounts[key];
+
+        if *initial != 0 {
+            import.add_opening(account, *initial)?;
+        }
+    }
+
+    // Both operations of a transfer share 'kxfer', the first one is imported.
+    let mut transfers: Vec<i64> = Vec::new();
+
+    for node in elements("ope") {
+        let date = attribute(node, "date").map(|date: i64| date + GDATE_OFFSET).unwrap_or_else(utils::today_julian_day);
+        let payee = attribute(node, "payee").and_then(|key| payees.get(&key).cloned());
+        let memo = node.attribute("wording").filter(|text| !text.is_empty())
+            .or_else(|| node.attribute("info").filter(|text| !text.is_empty()));
+
+        let message = memo.map(|text| text.to_string())
+            .or_else(|| payee.clone())
+            .unwrap_or_else(|| "HomeBank operation".to_string());
+
+        let location = format!("{} '{}'", utils::julian_day_to_iso(date)?, message);
+
+        let status: i64 = attribute(node, "st").unwrap_or(0);
+        if status == STATUS_VOID {
+            import.skip(&location, "the operation is void");
+            continue;
+        }
+
+        let cents = match amount(node, "amount") {
+            Some(cents) => cents,
+            None => {
+                import.skip(&location, "the amount is not a number");
+                continue;
+            }
+        };
+
+        let account = match attribute(node, "account").and_then(|key| accounts.get(&key)) {
+            Some((account, _)) => account,
+            None => {
+                import.skip(&location, "the account doesn't exist");
+                continue;
+            }
+        };
+
+        let mut legs = vec![Leg { target: Target::Account(account.clone()), cents }];
+
+        match (attribute(node, "kxfer").filter(|kxfer: &i64| *kxfer != 0), attribute(node, "dst_account")) {
+            (Some(kxfer), Some(destination)) => {
+                if transfers.contains(&kxfer) {
+                    continue;
+                }
+                transfers.push(kxfer);
+
+                match accounts.get(&destination) {
+                    Some((destination, _)) => legs.push(Leg { target: Target::Account(destination.clone()), cents: -cents }),
+                    None => {
+                        import.skip(&location, "the destination account doesn't exist");
+                        continue;
+                    }
+                }
+            }
+            _ => legs.push(Leg { target: Target::Category(operation_category(node, &categories)), cents: -cents })
+        }
+
+        let entry = HistoryEntry {
+            date,
+            charged: status != STATUS_REMIND && date <= utils::today_julian_day(),
+            payee,
+            message,
+            tags: node.attribute("tags")
+                .map(|tags| tags.split_whitespace().collect::<Vec<&str>>().join(","))
+                .filter(|tags| !tags.is_empty()),
+            legs
+        };
+
+        import.import_entry(&location, &entry)?;
+    }
+
+    import.finish(dry_run)?;
+
+    if dry_run {
+        return Ok(());
+    }
+
+    Ok(tx.commit()?)
+}
+
+fn attribute<T: std::str::FromStr>(node: Node<'_, '_>, name: &str) -> Option<T> {
+    node.attribute(name).and_then(|value| value.parse().ok())
+}
+
+fn amount(node: Node<'_, '_>, name: &str) -> Option<i64> {
+    attribute(node, name).map(|value: f64| (value * 100.0).round() as i64)
+}
+
+// Split operations keep the category of their largest part.
+fn operation_category(node: Node<'_, '_>, categories: &HashMap<i64, String>) -> Option<String> {
+    if let Some(category) = attribute(node, "category").and_then(|key: i64| categories.get(&key)) {
+        return Some(category.clone());
+    }
+
+    let split_categories = node.attribute("scat")?.split("||");
+    let split_amounts = node.attribute("samt")?.split("||");
+
+    split_categories.zip(split_amounts)
+        .filter_map(|(key, value)| Some((key.parse::<i64>().ok()?, value.parse::<f64>().ok()?)))
+        .max_by(|a, b| a.1.abs().total_cmp(&b.1.abs()))
+        .and_then(|(key, _)| categories.get(&key).cloned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{env, fs, process};
+
+    use rusqlite::Connection;
+
+    const XHB: &str = r#"<?xml version="1.0"?>
+<homebank v="1.3999999999999999" d="050504">
+<cur key="1" flags="0" iso="EUR" name="Euro" symb="€" syprf="0" dchar="," gchar="." frac="2"/>
+<account key="1" pos="1" type="1" curr="1" name="Checking" initial="100"/>
+<account key="2" pos="2" type="4" curr="1" name="Visa" initial="0"/>
+<pay key="1" name="Market"/>
+<cat key="1" name="Food"/>
+<cat key="2" parent="1" name="Groceries"/>
+<cat key="3" name="Home"/>
+<ope date="739908" amount="-12.5" account="1" paymode="6" payee="1" category="2" tags="weekly food"/>
+<ope date="739908" amount="-30" account="1" flags="2" scat="1||3" samt="-10||-20" wording="Split"/>
+<ope date="739908" amount="-20" account="1" dst_account="2" kxfer="1" wording="Card"/>
+<ope date="739908" amount="20" account="2" dst_account="1" kxfer="1" wording="Card"/>
+<ope date="739908" amount="-5" account="1" st="4" wording="Void"/>
+</homebank>
+"#;
+
+    fn value(conn: &Connection, sql: &str) -> i64 {
+        conn.query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn split_operations_keep_the_largest_category() {
+        let document = roxmltree::Document::parse(r#"<ope scat="1||3" samt="-10||-20"/>"#).unwrap();
+        let categories = HashMap::from([(1, "food".to_string()), (3, "home".to_string())]);
+
+        assert_eq!(operation_category(document.root_element(), &categories), Some("home".to_string()));
+        assert_eq!(amount(document.root_element(), "samt"), None);
+    }
+
+    #[test]
+    fn files_are_imported_once() {
+        let conn = walletdb::memory_database();
+        let path = env::temp_dir().join(format!("wallet-import-{}.xhb", process::id()));
+        fs::write(&path, XHB).unwrap();
+
+        import_homebank(path.to_str().unwrap(), false).unwrap();
+        import_homebank(path.to_str().unwrap(), false).unwrap();
+
+        assert_eq!(value(&conn, "SELECT COUNT(*) FROM accounts WHERE currency = 'EUR'"), 2);
+        assert_eq!(value(&conn, "SELECT COUNT(*) FROM transactions"), 4);
+        assert_eq!(value(&conn, "SELECT balance FROM accounts WHERE name = 'Checking'"), 3750);
+        assert_eq!(value(&conn, "SELECT is_credit FROM accounts WHERE name = 'Visa'"), 1);
+        assert_eq!(value(&conn, "
+            SELECT COUNT(*) FROM transactions
+            WHERE category = 'food:groceries' AND payee = 'Market' AND tags = 'weekly,food'
+        "), 1);
+        assert_eq!(value(&conn, "SELECT COUNT(*) FROM transactions WHERE category = 'home'"), 1);
+
+        journal::undo(2).unwrap();
+        assert_eq!(value(&conn, "SELECT COUNT(*) FROM accounts"), 0);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
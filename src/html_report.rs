@@ -0,0 +1,387 @@
+use crate::budgets;
+use crate::error::{self, WalletError};
+use crate::export;
+use crate::output;
+use crate::reports;
+use crate::utils;
+use crate::walletdb;
+
+use std::io::Write;
+
+use chrono::{Datelike, NaiveDate};
+
+const CHART_WIDTH: i64 = 640;
+const CHART_HEIGHT: i64 = 220;
+const MARGIN: i64 = 40;
+const INCOME_COLOR: &str = "#2e7d32";
+const EXPENSE_COLOR: &str = "#c62828";
+const LINE_COLOR: &str = "#1565c0";
+
+const STYLE: &str = "body { font-family: sans-serif; max-width: 760px; margin: 20px auto; color: #222; }
+h2 { margin-top: 36px; border-bottom: 1px solid #ddd; }
+.cards { display: flex; gap: 12px; }
+.card { flex: 1; padding: 10px; background: #f5f5f5; border-radius: 6px; }
+.card b { display: block; font-size: 1.3em; }
+svg text { font-size: 11px; fill: #555; }
+.empty { color: #888; }";
+
+fn money(cents: i64) -> String {
+    format!("{:.2}", cents as f64 / 100.0)
+}
+
+// Wallet 'report html' subcommand is defined below.
+// A single page without external files, so it can be sent by mail or opened
+// offline. The current year is reported up to today.
+pub fn report_html(opt_path: Option<&str>, opt_year: Option<i32>) -> error::Result<()> {
+    let today = utils::today_julian_day();
+    let year = match opt_year {
+        Some(year) => year,
+        None => utils::julian_day_to_naive(today)?.year()
+    };
+
+    let (first_day, last_day) = match (NaiveDate::from_ymd_opt(year, 1, 1), NaiveDate::from_ymd_opt(year, 12, 31)) {
+        (Some(first_day), Some(last_day)) => (utils::naive_to_julian_day(first_day), utils::naive_to_julian_day(last_day)),
+        _ => return error::invalid(format!("Invalid year '{}'!", year))
+    };
+
+    if first_day > today {
+        return error::invalid(format!("The year {} has not started yet!", year));
+    }
+
+    let last_day = last_day.min(today);
+
+    let conn = walletdb::open_database()?;
+
+    let periods = reports::period_ends(first_day, last_day, true)?;
+    let history = reports::balance_history(&conn, &periods)?;
+
+    if history.names.is_empty() {
+        output::message("There are no accounts! Try 'wallet new account --help'.");
+        return Ok(());
+    }
+
+    let msi_debt = reports::msi_debt(&conn, &periods)?;
+
+    let networth: Vec<(String, i64)> = periods.iter().enumerate()
+        .map(|(index, (label, _))| {
+            let total: i64 = history.balances.iter().map(|balances| balances[index]).sum();
+
+            (label[5..].to_string(), total - msi_debt[index])
+        })
+        .collect();
+
+    let totals = reports::monthly_totals(&conn, year, None, false, last_day)?;
+    let monthly: Vec<(String, i64, i64)> = periods.iter()
+        .map(|(label, _)| {
+            let (income, expenses) = totals.iter()
+                .find(|(month, _, _)| month == label)
+                .map(|(_, income, expenses)| (*income, *expenses))
+                .unwrap_or((0, 0));
+
+            (label[5..].to_string(), income, expenses)
+        })
+        .collect();
+
+    let spending = reports::category_spending(&conn, (first_day, last_day), None, false, last_day)?;
+    let categories: Vec<(String, i64)> = reports::roll_up(&spending).into_iter()
+        .map(|(top, cents, _)| (top, cents))
+        .collect();
+
+    let (budget_month, _) = periods.last().cloned().unwrap_or_default();
+    let budget_period = match reports::month_periods(year, utils::julian_day_to_naive(last_day)?.month()) {
+        Some((current, _)) => current,
+        None => (first_day, last_day)
+    };
+    let budget_status = budgets::budget_status(&conn, budget_period)?;
+
+    let income: i64 = monthly.iter().map(|(_, income, _)| income).sum();
+    let expenses: i64 = monthly.iter().map(|(_, _, expenses)| expenses).sum();
+    let rate = if income > 0 { format!("{:.1}%", (income - expenses) as f64 * 100.0 / income as f64) } else { "-".to_string() };
+
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Wallet report {0}</title>\n<style>\n{1}\n</style>\n</head>\n<body>\n\
+         <h1>Wallet report {0}</h1>\n<p>From {2} to {3}.</p>\n",
+        year, STYLE, utils::julian_day_to_iso(first_day)?, utils::julian_day_to_iso(last_day)?
+    );
+
+    html.push_str("<div class=\"cards\">\n");
+    for (title, value) in [
+        ("Net worth", money(networth.last().map(|(_, total)| *total).unwrap_or(0))),
+        ("Income", money(income)),
+        ("Expenses", money(expenses)),
+        ("Savings rate", rate)
+    ] {
+        html.push_str(&format!("<div class=\"card\">{}<b>{}</b></div>\n", title, value));
+    }
+    html.push_str("</div>\n");
+
+    html.push_str("<h2>Net worth</h2>\n");
+    html.push_str(&line_chart(&networth));
+    if let Some(debt) = msi_debt.last().filter(|debt| **debt > 0) {
+        html.push_str(&format!("<p class=\"empty\">Net worth is net of the MSI installments not billed yet, {} in the last month.</p>\n", money(*debt)));
+    }
+
+    html.push_str("<h2>Income and expenses</h2>\n");
+    html.push_str(&column_chart(&monthly));
+
+    html.push_str("<h2>Expenses by category</h2>\n");
+    let total_spent: i64 = categories.iter().map(|(_, cents)| cents).sum();
+    let category_bars: Vec<Bar> = categories.iter()
+        .map(|(name, cents)| (
+            name.clone(), *cents, None,
+            format!("{} ({:.1}%)", money(*cents), *cents as f64 * 100.0 / total_spent.max(1) as f64)
+        ))
+        .collect();
+    html.push_str(&bar_list(&category_bars, "No expenses this year."));
+
+    html.push_str(&format!("<h2>Budgets of {}</h2>\n", budget_month));
+    let budget_bars: Vec<Bar> = budget_status.iter()
+        .map(|(budget, spent)| {
+            let limit = (budget.amount * 100.0).round() as i64;
+            (budget.category.clone(), *spent, Some(limit), format!("{} of {}", money(*spent), money(limit)))
+        })
+        .collect();
+    html.push_str(&bar_list(&budget_bars, "There are no budgets."));
+
+    html.push_str(&format!("<p class=\"empty\">Generated on {}.</p>\n</body>\n</html>\n", utils::julian_day_to_iso(today)?));
+
+    let mut out = export::open_output(opt_path)?;
+
+    if let Err(e) = out.write_all(html.as_bytes()) {
+        return Err(WalletError::Io("Unable to write the report!".to_string(), e));
+    }
+
+    if let Some(path) = opt_path {
+        output::message(format!("Report of {} written to '{}'.", year, path));
+    }
+
+    Ok(())
+}
+
+// Vertical position of a value in a chart going from min at the bottom to max
+// at the top.
+fn y_position(value: i64, min: i64, max: i64) -> i64 {
+    let height = CHART_HEIGHT - 2 * MARGIN;
+
+    if max == min {
+        return CHART_HEIGHT - MARGIN;
+    }
+
+    CHART_HEIGHT - MARGIN - (value - min) * height / (max - min)
+}
+
+fn svg_start() -> String {
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">\n",
+        CHART_WIDTH, CHART_HEIGHT
+    )
+}
+
+// Lines of the lowest and highest value, and of zero when it's between them.
+fn y_axis(min: i64, max: i64) -> String {
+    let mut svg = String::new();
+    let mut values = vec![min, max];
+
+    if min < 0 && max > 0 {
+        values.push(0);
+    }
+
+    for value in values {
+        let y = y_position(value, min, max);
+
+        svg.push_str(&format!(
+            "<line x1=\"{0}\" y1=\"{1}\" x2=\"{2}\" y2=\"{1}\" stroke=\"#ddd\"/>\
+             <text x=\"{0}\" y=\"{3}\">{4}</text>\n",
+            MARGIN, y, CHART_WIDTH - 10, y - 3, money(value)
+        ));
+    }
+
+    svg
+}
+
+fn line_chart(points: &[(String, i64)]) -> String {
+    let min = points.iter().map(|(_, value)| *value).min().unwrap_or(0).min(0);
+    let max = points.iter().map(|(_, value)| *value).max().unwrap_or(0).max(0);
+    let step = (CHART_WIDTH - 2 * MARGIN) / points.len().max(1) as i64;
+
+    let mut svg = svg_start();
+    svg.push_str(&y_axis(min, max));
+
+    let coordinates: Vec<(i64, i64)> = points.iter().enumerate()
+        .map(|(index, (_, value))| (MARGIN + step / 2 + index as i64 * step, y_position(*value, min, max)))
+        .collect();
+
+    svg.push_str(&format!(
+        "<polyline fill=\"none\" stroke=\"{}\" stroke-width=\"2\" points=\"{}\"/>\n",
+        LINE_COLOR,
+        coordinates.iter().map(|(x, y)| format!("{},{}", x, y)).collect::<Vec<_>>().join(" ")
+    ));
+
+    for ((label, value), (x, y)) in points.iter().zip(&coordinates) {
+        svg.push_str(&format!(
+            "<circle cx=\"{0}\" cy=\"{1}\" r=\"3\" fill=\"{2}\"><title>{3}</title></circle>\
+             <text x=\"{0}\" y=\"{4}\" text-anchor=\"middle\">{5}</text>\n",
+            x, y, LINE_COLOR, money(*value), CHART_HEIGHT - MARGIN / 2, utils::html_escape(label)
+        ));
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+// Income and expenses of every month side by side.
+fn column_chart(months: &[(String, i64, i64)]) -> String {
+    let max = months.iter().map(|(_, income, expenses)| *income.max(expenses)).max().unwrap_or(0);
+    let step = (CHART_WIDTH - 2 * MARGIN) / months.len().max(1) as i64;
+    let width = (step / 3).max(1);
+
+    let mut svg = svg_start();
+    svg.push_str(&y_axis(0, max));
+
+    for (index, (label, income, expenses)) in months.iter().enumerate() {
+        let x = MARGIN + index as i64 * step + step / 6;
+
+        for (offset, value, color, name) in [(0, income, INCOME_COLOR, "Income"), (width, expenses, EXPENSE_COLOR, "Expenses")] {
+            let y = y_position(*value, 0, max);
+
+            svg.push_str(&format!(
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"><title>{} {}</title></rect>\n",
+                x + offset, y, width, CHART_HEIGHT - MARGIN - y, color, name, money(*value)
+            ));
+        }
+
+        svg.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>\n",
+            x + width, CHART_HEIGHT - MARGIN / 2, utils::html_escape(label)
+        ));
+    }
+
+    svg.push_str(&format!(
+        "<rect x=\"{0}\" y=\"6\" width=\"10\" height=\"10\" fill=\"{1}\"/><text x=\"{2}\" y=\"15\">Income</text>\
+         <rect x=\"{3}\" y=\"6\" width=\"10\" height=\"10\" fill=\"{4}\"/><text x=\"{5}\" y=\"15\">Expenses</text>\n",
+        CHART_WIDTH - 170, INCOME_COLOR, CHART_WIDTH - 155, CHART_WIDTH - 90, EXPENSE_COLOR, CHART_WIDTH - 75
+    ));
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+// Label, value, limit and text of a bar. Bars without limit, like the ones of
+// the categories, are only compared with each other.
+type Bar = (String, i64, Option<i64>, String);
+
+// One horizontal bar for each item, relative to the biggest value or limit.
+// The limit is drawn behind the value, which turns red when it goes over it.
+fn bar_list(bars: &[Bar], empty: &str) -> String {
+    if bars.is_empty() {
+        return format!("<p class=\"empty\">{}</p>\n", empty);
+    }
+
+    let row_height = 26;
+    let label_width = 150;
+    let bar_width = CHART_WIDTH - label_width - 170;
+    let max = bars.iter().map(|(_, value, limit, _)| limit.unwrap_or(0).max(*value)).max().unwrap_or(0).max(1);
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">\n",
+        CHART_WIDTH, bars.len() as i64 * row_height + 4
+    );
+
+    for (index, (label, value, limit, text)) in bars.iter().enumerate() {
+        let y = index as i64 * row_height + 4;
+        let color = if limit.is_some_and(|limit| *value > limit) { EXPENSE_COLOR } else { LINE_COLOR };
+
+        svg.push_str(&format!(
+            "<text x=\"0\" y=\"{}\">{}</text>\
+             <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"16\" fill=\"#eee\"/>\
+             <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"16\" fill=\"{}\"/>\
+             <text x=\"{}\" y=\"{}\">{}</text>\n",
+            y + 12, utils::html_escape(label),
+            label_width, y, limit.unwrap_or(*value) * bar_width / max,
+            label_width, y, value * bar_width / max, color,
+            label_width + bar_width + 8, y + 12, text
+        ));
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::budgets;
+    use crate::structs::EXPENSE;
+
+    use std::{env, fs, process};
+
+    fn bar(label: &str, value: i64, limit: Option<i64>) -> Bar {
+        (label.to_string(), value, limit, String::new())
+    }
+
+    #[test]
+    fn values_are_placed_between_the_margins() {
+        assert_eq!(y_position(0, 0, 100), CHART_HEIGHT - MARGIN);
+        assert_eq!(y_position(100, 0, 100), MARGIN);
+        assert_eq!(y_position(5, 5, 5), CHART_HEIGHT - MARGIN);
+
+        // Zero gets its own line when the values go below it.
+        assert_eq!(y_axis(-100, 100).matches("<line").count(), 3);
+        assert_eq!(y_axis(0, 100).matches("<line").count(), 2);
+    }
+
+    #[test]
+    fn charts_have_a_mark_for_every_value() {
+        let points = vec![("01".to_string(), 1000), ("02".to_string(), -500), ("<3>".to_string(), 2000)];
+        let svg = line_chart(&points);
+
+        assert_eq!(svg.matches("<circle").count(), 3);
+        assert!(svg.contains("&lt;3&gt;"));
+
+        let months = vec![("01".to_string(), 3000, 1000), ("02".to_string(), 2000, 2500)];
+        assert_eq!(column_chart(&months).matches("<title>").count(), 4);
+
+        assert_eq!(bar_list(&[], "Nothing."), "<p class=\"empty\">Nothing.</p>\n");
+    }
+
+    #[test]
+    fn only_bars_over_their_limit_are_red() {
+        let red = format!("fill=\"{}\"", EXPENSE_COLOR);
+
+        // Categories have no limit, the biggest one is never flagged.
+        assert!(!bar_list(&[bar("food", 5000, None), bar("home", 100, None)], "").contains(&red));
+
+        assert!(!bar_list(&[bar("food", 3000, Some(4000))], "").contains(&red));
+        assert_eq!(bar_list(&[bar("food", 5000, Some(4000)), bar("fun", 100, Some(200))], "").matches(&red).count(), 1);
+    }
+
+    #[test]
+    fn the_report_is_a_whole_page() {
+        let conn = walletdb::memory_database();
+        let path = env::temp_dir().join(format!("wallet-report-{}.html", process::id()));
+        let str_path = path.to_str().unwrap();
+
+        // Nothing is written without accounts.
+        report_html(Some(str_path), None).unwrap();
+        assert!(!path.exists());
+
+        walletdb::new_account("Bank", 500.0, false, None).unwrap();
+        walletdb::new_transaction("Market", 45.0, EXPENSE, true, false, None, None).unwrap();
+        conn.execute("UPDATE transactions SET category = 'food'", []).unwrap();
+        budgets::budget_set("food", 40.0).unwrap();
+
+        report_html(Some(str_path), None).unwrap();
+        let html = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(html.starts_with("<!DOCTYPE html>") && html.ends_with("</html>\n"));
+        assert!(html.contains("<b>455.00</b>"));
+        assert!(html.contains("45.00 of 40.00"));
+        // The legend of the expenses and the budget, not the category.
+        assert_eq!(html.matches(&format!("fill=\"{}\"/>", EXPENSE_COLOR)).count(), 2);
+
+        assert_eq!(report_html(Some(str_path), Some(9999)).unwrap_err().kind(), "invalid-input");
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,778 @@
+use crate::error;
+use crate::journal;
+use crate::output;
+use crate::rules;
+use crate::structs::{Account, Transaction, ADJUSTMENT, EXPENSE, INCOMING, TRANSFER};
+use crate::utils;
+use crate::walletdb;
+
+use std::collections::HashMap;
+
+use rusqlite::{params, Connection};
+
+// A movement read from a bank statement. Negative values are money that left
+// the account.
+pub struct StatementRow {
+    pub date: i64,
+    pub message: String,
+    pub value: f64,
+    pub fitid: Option<String>
+}
+
+impl StatementRow {
+    pub fn to_transaction(&self, id_account: i64) -> Transaction {
+        Transaction {
+            message: self.message.clone(),
+            value: self.value.abs(),
+            date: self.date,
+            charged: true,
+            t_type: if self.value < 0.0 { EXPENSE } else { INCOMING },
+            id_account,
+            fitid: self.fitid.clone(),
+            ..Transaction::empty()
+        }
+    }
+}
+
+// Writes the rows of a statement as charged transactions of the account.
+// Before inserting a row it looks for:
+//  - a transaction with the same FITID, already imported by a previous run.
+//  - a charged transaction with the same date, value and message, a duplicate
+//    from an overlapping statement.
+//  - pending expenses of the same value within the date window. A single
+//    candidate gets charged instead of inserting the row, several candidates
+//    are left for review.
+// Without an operation nothing is written and the rows are only previewed.
+pub fn import_rows(
+            conn: &Connection, opt_id_operation: Option<i64>, account: &mut Account,
+            rows: &[StatementRow], window: i64
+        ) -> error::Result<()> {
+    let mut n_imported = 0;
+    let mut n_matched = 0;
+    let mut n_duplicates = 0;
+    let mut n_skipped = 0;
+
+    // Transactions matched or inserted by this import can't match again.
+    let mut used_ids: Vec<i64> = Vec::new();
+    let mut ambiguous: Vec<(&StatementRow, Vec<i64>)> = Vec::new();
+
+    if opt_id_operation.is_none() {
+        output::message(format!("Dry run, nothing will be written. Rows to import into '{}':", account.name));
+    }
+
+    let fitids = statement_fitids(rows);
+    let rules = rules::select_rules(conn)?;
+
+    for (row, fitid) in rows.iter().zip(&fitids) {
+        if let Some(id) = select_by_fitid(conn, account.id, fitid)? {
+            row_record(row, "already-imported", vec![id])?;
+            used_ids.push(id);
+            n_skipped += 1;
+            continue;
+        }
+
+        let mut transaction = row.to_transaction(account.id);
+        transaction.fitid = Some(fitid.clone());
+
+        if let Some(id) = find_duplicate(conn, &transaction, &used_ids)? {
+            if opt_id_operation.is_none() {
+                output::message(format!("{} duplicate of #{}", preview(row)?, id));
+            }
+            row_record(row, "duplicate", vec![id])?;
+
+            used_ids.push(id);
+            n_duplicates += 1;
+            continue;
+        }
+
+        if transaction.t_type == EXPENSE {
+            let candidates = find_pending(conn, &transaction, window, &used_ids)?;
+
+            if candidates.len() > 1 {
+                row_record(row, "review", candidates.iter().map(|t| t.id).collect())?;
+                ambiguous.push((row, candidates.iter().map(|t| t.id).collect()));
+                continue;
+            }
+
+            if let Some(pending) = candidates.first() {
+                if let Some(id_operation) = opt_id_operation {
+                    walletdb::charge_transaction(conn, id_operation, pending.id, Some(fitid), transaction.date)?;
+                }
+
+                output::message(format!("{} charges pending #{} '{}'", preview(row)?, pending.id, pending.message));
+                row_record(row, "charged", vec![pending.id])?;
+
+                account.charge(pending);
+                used_ids.push(pending.id);
+                n_matched += 1;
+                continue;
+            }
+        }
+
+        match opt_id_operation {
+            Some(id_operation) => {
+                let id = walletdb::insert_transaction_with_rules(conn, id_operation, &transaction, &rules)?;
+
+                row_record(row, "new", vec![id])?;
+                used_ids.push(id);
+            }
+            None => {
+                output::message(format!("{} new", preview(row)?));
+                row_record(row, "new", vec![])?;
+            }
+        }
+
+        account.apply(&transaction);
+        n_imported += 1;
+    }
+
+    if !ambiguous.is_empty() {
+        output::message("These rows match several pending expenses and were not imported, review them with 'wallet reconcile':");
+
+        for (row, ids) in &ambiguous {
+            let ids: Vec<String> = ids.iter().map(|id| format!("#{}", id)).collect();
+            output::message(format!("{} could be {}", preview(row)?, ids.join(", ")));
+        }
+    }
+
+    let summary = format!(
+        "{} new, {} pending charged, {} duplicates, {} already imported, {} to review.",
+        n_imported, n_matched, n_duplicates, n_skipped, ambiguous.len()
+    );
+
+    match opt_id_operation {
+        Some(id_operation) => {
+            walletdb::update_account(conn, id_operation, account)?;
+            output::message(format!("Import finished: {}", summary));
+        }
+        None => output::message(format!("Import preview: {}", summary))
+    }
+
+    output::message(format!("Resulting account:\n{}", account));
+    output::record(vec![
+        ("record", "summary".into()),
+        ("new", n_imported.into()),
+        ("charged", n_matched.into()),
+        ("duplicates", n_duplicates.into()),
+        ("already_imported", n_skipped.into()),
+        ("review", ambiguous.len().into()),
+        ("dry_run", opt_id_operation.is_none().into()),
+        ("balance", account.balance.into()),
+        ("available", account.available.into())
+    ]);
+
+    Ok(())
+}
+
+// One row of the statement, what the import did with it and the transactions
+// it was written as or matched.
+fn row_record(row: &StatementRow, status: &str, ids: Vec<i64>) -> error::Result<()> {
+    output::record(vec![
+        ("record", "row".into()),
+        ("date", utils::julian_day_to_iso(row.date)?.into()),
+        ("value", row.value.into()),
+        ("message", row.message.as_str().into()),
+        ("status", status.into()),
+        ("transactions", ids.into())
+    ]);
+
+    Ok(())
+}
+
+// Rows without a FITID get one made of their date, value, message and how many
+// identical rows came before in the statement, so imports of the same rows are
+// recognized even when they charged a pending expense with another message.
+fn statement_fitids(rows: &[StatementRow]) -> Vec<String> {
+    let mut fitids: Vec<String> = Vec::new();
+
+    for row in rows {
+        let fitid = match &row.fitid {
+            Some(fitid) => fitid.clone(),
+            None => {
+                let key = format!(
+                    "{}:{}:{}", row.date, (row.value * 100.0).round() as i64, row.message
+                );
+                let occurrence = fitids.iter().filter(|f| f.starts_with(&format!("{}#", key))).count();
+
+                format!("{}#{}", key, occurrence + 1)
+            }
+        };
+
+        fitids.push(fitid);
+    }
+
+    fitids
+}
+
+fn select_by_fitid(conn: &Connection, id_account: i64, fitid: &str) -> rusqlite::Result<Option<i64>> {
+    let mut stmt = conn.prepare(
+        "SELECT id_transaction FROM transactions WHERE id_account = ?1 AND fitid = ?2"
+    )?;
+    let mut rows = stmt.query(params![id_account, fitid])?;
+
+    match rows.next()? {
+        Some(row) => Ok(Some(row.get(0)?)),
+        None => Ok(None)
+    }
+}
+
+fn find_duplicate(
+            conn: &Connection, transaction: &Transaction, used_ids: &[i64]
+        ) -> rusqlite::Result<Option<i64>> {
+    let mut stmt = conn.prepare(
+        "SELECT id_transaction FROM transactions
+         WHERE id_account = ?1 AND charged = 1 AND t_type = ?2 AND value = ?3 AND date = ?4 AND message = ?5
+         ORDER BY id_transaction"
+    )?;
+
+    let ids: Vec<i64> = stmt.query_map(
+        params![
+            transaction.id_account, transaction.t_type, (transaction.value * 100.0).round() as i64,
+            transaction.date, &transaction.message
+        ],
+        |row| row.get(0)
+    )?.collect::<rusqlite::Result<_>>()?;
+
+    Ok(ids.into_iter().find(|id| !used_ids.contains(id)))
+}
+
+fn find_pending(
+            conn: &Connection, transaction: &Transaction, window: i64, used_ids: &[i64]
+        ) -> rusqlite::Result<Vec<Transaction>> {
+    let mut stmt = conn.prepare(
+        "SELECT * FROM transactions
+         WHERE id_account = ?1 AND charged = 0 AND t_type = ?2 AND value = ?3
+            AND date BETWEEN ?4 AND ?5
+         ORDER BY ABS(date - ?6), id_transaction"
+    )?;
+
+    let candidates: Vec<Transaction> = stmt.query_map(
+        params![
+            transaction.id_account, EXPENSE, (transaction.value * 100.0).round() as i64,
+            transaction.date - window, transaction.date + window, transaction.date
+        ],
+        Transaction::from_row
+    )?.collect::<rusqlite::Result<_>>()?;
+
+    Ok(candidates.into_iter().filter(|t| !used_ids.contains(&t.id)).collect())
+}
+
+pub fn preview(row: &StatementRow) -> error::Result<String> {
+    Ok(format!("    {} ${:>12.2} '{}'", utils::julian_day_to_iso(row.date)?, row.value, row.message))
+}
+
+// Parses amounts as written by banks: currency symbols, thousands separators,
+// parentheses or a trailing minus for negative values.
+pub fn parse_amount(text: &str, decimal_separator: char) -> Option<f64> {
+    let mut text = text.trim().to_string();
+    let mut negative = false;
+
+    if text.starts_with('(') && text.ends_with(')') {
+        negative = true;
+        text = text[1..text.len() - 1].to_string();
+    }
+
+    if let Some(stripped) = text.strip_suffix('-') {
+        negative = true;
+        text = stripped.to_string();
+    }
+
+    let mut number = String::new();
+    for c in text.chars() {
+        if c.is_ascii_digit() || c == '-' || c == '+' {
+            number.push(c);
+        } else if c == decimal_separator {
+            number.push('.');
+        }
+    }
+
+    if number.is_empty() {
+        return None;
+    }
+
+    let value: f64 = number.parse().ok()?;
+
+    Some(if negative { -value } else { value })
+}
+
+
+// Histories kept in other finance apps are imported with the code below. Each
+// importer turns the entries of its file into legs and the entries become
+// wallet transactions, transfers or opening balances.
+#[derive(Clone)]
+pub struct ForeignAccount {
+    // Identifies the account in the imported file.
+    pub key: String,
+    pub name: String,
+    pub credit: bool,
+    pub currency: Option<String>
+}
+
+pub enum Target {
+    Account(ForeignAccount),
+    // Expenses and incomings, with their category if any.
+    Category(Option<String>),
+    Opening,
+    Equity
+}
+
+pub struct Leg {
+    pub target: Target,
+    pub cents: i64
+}
+
+pub struct HistoryEntry {
+    pub date: i64,
+    pub charged: bool,
+    pub payee: Option<String>,
+    pub message: String,
+    pub tags: Option<String>,
+    pub legs: Vec<Leg>
+}
+
+pub struct HistoryImport<'a> {
+    conn: &'a Connection,
+    id_operation: i64,
+    // Prefix of the FITIDs, so each app recognizes its own imports.
+    source: &'static str,
+    accounts: HashMap<String, Account>,
+    created: Vec<i64>,
+    openings: HashMap<i64, i64>,
+    fitids: Vec<String>,
+    n_transactions: usize,
+    n_transfers: usize,
+    n_openings: usize,
+    n_before: usize,
+    skipped: Vec<(String, String)>
+}
+
+impl<'a> HistoryImport<'a> {
+    pub fn new(conn: &'a Connection, id_operation: i64, source: &'static str) -> HistoryImport<'a> {
+        HistoryImport {
+            conn,
+            id_operation,
+            source,
+            accounts: HashMap::new(),
+            created: Vec::new(),
+            openings: HashMap::new(),
+            fitids: Vec::new(),
+            n_transactions: 0,
+            n_transfers: 0,
+            n_openings: 0,
+            n_before: 0,
+            skipped: Vec::new()
+        }
+    }
+
+    pub fn skip(&mut self, location: &str, reason: &str) {
+        self.skipped.push((location.to_string(), reason.to_string()));
+    }
+
+    // An entry with one account becomes an expense or incoming when the other
+    // legs have categories, an opening balance when they are openings and an
+    // adjustment otherwise. An entry between two accounts becomes a transfer.
+    pub fn import_entry(&mut self, location: &str, entry: &HistoryEntry) -> rusqlite::Result<()> {
+        let (own, other): (Vec<&Leg>, Vec<&Leg>) = entry.legs.iter()
+            .partition(|leg| matches!(leg.target, Target::Account(_)));
+
+        if entry.legs.iter().map(|leg| leg.cents).sum::<i64>() != 0 {
+            self.skip(location, "the entry is not balanced");
+            return Ok(());
+        }
+
+        match (own.as_slice(), other.as_slice()) {
+            ([Leg { target: Target::Account(account), cents }], others) if !others.is_empty() => {
+                if others.iter().all(|leg| matches!(leg.target, Target::Opening)) {
+                    return self.add_opening(account, *cents);
+                }
+
+                let category = others.iter()
+                    .filter_map(|leg| match &leg.target {
+                        Target::Category(category) => Some((category, leg.cents.abs())),
+                        _ => None
+                    })
+                    .max_by_key(|(_, cents)| *cents)
+                    .map(|(category, _)| category.clone());
+
+                let (t_type, value) = match category {
+                    None => (ADJUSTMENT, *cents),
+                    Some(_) if *cents < 0 => (EXPENSE, -cents),
+                    Some(_) => (INCOMING, *cents)
+                };
+
+                let id_account = self.account_id(account)?;
+                let mut transaction = entry_transaction(entry, id_account, t_type, value);
+                transaction.category = category.flatten();
+
+                if self.imported_before(&mut transaction)?.is_some() {
+                    self.n_before += 1;
+                    return Ok(());
+                }
+
+                walletdb::insert_transaction(self.conn, self.id_operation, &transaction)?;
+                self.apply(&account.key, &transaction);
+                self.n_transactions += 1;
+            }
+            ([source, destination], []) => {
+                let (source, destination) = if source.cents < 0 { (source, destination) } else { (destination, source) };
+
+                let (source_account, destination_account) = match (&source.target, &destination.target) {
+                    (Target::Account(source), Target::Account(destination)) => (source, destination),
+                    _ => unreachable!()
+                };
+
+                if source_account.currency != destination_account.currency {
+                    self.skip(location, "transfers between currencies are not supported");
+                    return Ok(());
+                }
+
+                let id_source = self.account_id(source_account)?;
+                let id_destination = self.account_id(destination_account)?;
+
+                let mut outgoing = entry_transaction(entry, id_source, TRANSFER, source.cents);
+                let mut incoming = entry_transaction(entry, id_destination, TRANSFER, destination.cents);
+
+                let outgoing_before = self.imported_before(&mut outgoing)?;
+                let incoming_before = self.imported_before(&mut incoming)?;
+
+                match (outgoing_before, incoming_before) {
+                    (Some(_), Some(_)) => {
+                        self.n_before += 1;
+                        return Ok(());
+                    }
+                    (None, None) => {
+                        walletdb::insert_transfer(self.conn, self.id_operation, &outgoing, &incoming)?;
+                        self.apply(&source_account.key, &outgoing);
+                        self.apply(&destination_account.key, &incoming);
+                    }
+                    // An earlier import wrote one side only, the missing one is
+                    // added and linked to it.
+                    (Some(id_outgoing), None) => {
+                        walletdb::complete_transfer(self.conn, self.id_operation, id_outgoing, &incoming)?;
+                        self.apply(&destination_account.key, &incoming);
+                    }
+                    (None, Some(id_incoming)) => {
+                        walletdb::complete_transfer(self.conn, self.id_operation, id_incoming, &outgoing)?;
+                        self.apply(&source_account.key, &outgoing);
+                    }
+                }
+
+                self.n_transfers += 1;
+            }
+            ([], _) => self.skip(location, "no leg in an asset or liability account"),
+            _ => self.skip(location, &format!("{} asset or liability legs can't be mapped to a transaction", own.len()))
+        }
+
+        Ok(())
+    }
+
+    // Opening balances are only added to the accounts created by the import,
+    // so importing the same file again doesn't add them twice.
+    pub fn add_opening(&mut self, account: &ForeignAccount, cents: i64) -> rusqlite::Result<()> {
+        let id_account = self.account_id(account)?;
+
+        if !self.created.contains(&id_account) {
+            self.n_before += 1;
+            return Ok(());
+        }
+
+        *self.openings.entry(id_account).or_insert(0) += cents;
+
+        let wallet_account = self.accounts.get_mut(&account.key).unwrap();
+        wallet_account.balance += cents as f64 / 100.0;
+        wallet_account.available += cents as f64 / 100.0;
+
+        self.n_openings += 1;
+
+        Ok(())
+    }
+
+    fn apply(&mut self, key: &str, transaction: &Transaction) {
+        let wallet_account = self.accounts.get_mut(key).unwrap();
+        wallet_account.apply(transaction);
+    }
+
+    // Transactions get a FITID made of their account, date, value and message,
+    // the same one a later import of the file would make. Returns the ID of the
+    // transaction written by an earlier import, if any.
+    fn imported_before(&mut self, transaction: &mut Transaction) -> rusqlite::Result<Option<i64>> {
+        let key = format!(
+            "{}:{}:{}:{}:{}",
+            self.source, transaction.id_account, transaction.date,
+            (transaction.value * 100.0).round() as i64, transaction.message
+        );
+        let occurrence = self.fitids.iter().filter(|f| f.starts_with(&format!("{}#", key))).count();
+        let fitid = format!("{}#{}", key, occurrence + 1);
+
+        self.fitids.push(fitid.clone());
+
+        let opt_id = select_by_fitid(self.conn, transaction.id_account, &fitid)?;
+
+        transaction.fitid = Some(fitid);
+
+        Ok(opt_id)
+    }
+
+    // Wallet accounts are found by name, the missing ones are created.
+    fn account_id(&mut self, foreign: &ForeignAccount) -> rusqlite::Result<i64> {
+        if let Some(account) = self.accounts.get(&foreign.key) {
+            return Ok(account.id);
+        }
+
+        let mut stmt = self.conn.prepare("SELECT * FROM accounts WHERE name = ?1 AND is_credit = ?2")?;
+        let mut rows = stmt.query(params![&foreign.name, foreign.credit])?;
+
+        let account = match rows.next()? {
+            Some(row) => Account::from_row(row)?,
+            None => {
+                self.conn.execute(
+                    "INSERT INTO accounts (name, balance, available, is_default, opening_balance, is_credit, currency, created)
+                     VALUES (?1, 0, 0, 0, 0, ?2, ?3, ?4)",
+                    params![&foreign.name, foreign.credit, &foreign.currency, utils::today_julian_day()]
+                )?;
+
+                let id_account = self.conn.last_insert_rowid();
+                journal::record(self.conn, self.id_operation, "accounts", id_account, None)?;
+                self.created.push(id_account);
+
+                Account {
+                    id: id_account,
+                    name: foreign.name.clone(),
+                    credit: foreign.credit,
+                    currency: foreign.currency.clone(),
+                    ..Account::empty()
+                }
+            }
+        };
+
+        let id_account = account.id;
+        self.accounts.insert(foreign.key.clone(), account);
+
+        Ok(id_account)
+    }
+
+    // Saves the balances of the accounts and prints what was imported.
+    pub fn finish(self, dry_run: bool) -> rusqlite::Result<()> {
+        for account in self.accounts.values() {
+            walletdb::update_account(self.conn, self.id_operation, account)?;
+
+            if let Some(opening) = self.openings.get(&account.id) {
+                let before = journal::row_image(self.conn, "accounts", account.id)?;
+
+                self.conn.execute(
+                    "UPDATE accounts SET opening_balance = opening_balance + ?1 WHERE id_account = ?2",
+                    params![opening, account.id]
+                )?;
+
+                journal::record(self.conn, self.id_operation, "accounts", account.id, before)?;
+            }
+        }
+
+        // The first account is the default one, as with 'wallet new account'.
+        let n_default: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM accounts WHERE is_default = 1", [], |row| row.get(0)
+        )?;
+
+        if let (0, Some(id_account)) = (n_default, self.created.first()) {
+            let before = journal::row_image(self.conn, "accounts", *id_account)?;
+            self.conn.execute("UPDATE accounts SET is_default = 1 WHERE id_account = ?1", params![id_account])?;
+            journal::record(self.conn, self.id_operation, "accounts", *id_account, before)?;
+        }
+
+        let mut accounts: Vec<&Account> = self.accounts.values().collect();
+        accounts.sort_by_key(|account| account.id);
+
+        for account in accounts {
+            let status = if self.created.contains(&account.id) { "new" } else { "existing" };
+            output::message(format!("{} ({})", account, status));
+            output::record(vec![
+                ("record", "account".into()),
+                ("id", account.id.into()),
+                ("name", account.name.as_str().into()),
+                ("status", status.into()),
+                ("balance", account.balance.into()),
+                ("available", account.available.into())
+            ]);
+        }
+
+        if !self.skipped.is_empty() {
+            output::message("These entries could not be mapped and were skipped:");
+
+            for (location, reason) in &self.skipped {
+                output::message(format!("    {:<20} {}", location, reason));
+                output::record(vec![
+                    ("record", "skipped".into()),
+                    ("entry", location.as_str().into()),
+                    ("reason", reason.as_str().into())
+                ]);
+            }
+        }
+
+        let summary = format!(
+            "{} accounts created, {} transactions, {} transfers, {} opening balances, {} already imported, {} skipped.",
+            self.created.len(), self.n_transactions, self.n_transfers, self.n_openings, self.n_before,
+            self.skipped.len()
+        );
+
+        output::record(vec![
+            ("record", "summary".into()),
+            ("accounts_created", self.created.len().into()),
+            ("transactions", self.n_transactions.into()),
+            ("transfers", self.n_transfers.into()),
+            ("openings", self.n_openings.into()),
+            ("already_imported", self.n_before.into()),
+            ("skipped", self.skipped.len().into()),
+            ("dry_run", dry_run.into())
+        ]);
+
+        if dry_run {
+            output::discard_changes();
+            output::message(format!("Import preview, nothing was written: {}", summary));
+        } else {
+            output::message(format!("Import finished: {}", summary));
+        }
+
+        Ok(())
+    }
+}
+
+fn entry_transaction(entry: &HistoryEntry, id_account: i64, t_type: i64, cents: i64) -> Transaction {
+    Transaction {
+        message: entry.message.clone(),
+        value: cents as f64 / 100.0,
+        date: entry.date,
+        charged: entry.charged,
+        t_type,
+        id_account,
+        payee: entry.payee.clone(),
+        tags: entry.tags.clone(),
+        ..Transaction::empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::journal;
+
+    #[test]
+    fn amounts_as_written_by_banks() {
+        assert_eq!(parse_amount("1,234.56", '.'), Some(1234.56));
+        assert_eq!(parse_amount("1.234,56", ','), Some(1234.56));
+        assert_eq!(parse_amount("$ -12.50", '.'), Some(-12.5));
+        assert_eq!(parse_amount("(12.50)", '.'), Some(-12.5));
+        assert_eq!(parse_amount("12.50-", '.'), Some(-12.5));
+        assert_eq!(parse_amount("+7", '.'), Some(7.0));
+        assert_eq!(parse_amount("EUR", '.'), None);
+        assert_eq!(parse_amount("1-2", '.'), None);
+    }
+
+    fn row(date: i64, message: &str, value: f64) -> StatementRow {
+        StatementRow { date, message: message.to_string(), value, fitid: None }
+    }
+
+    fn import(conn: &Connection, rows: &[StatementRow]) -> Account {
+        let mut account = walletdb::select_account(conn, Some("1")).unwrap();
+        let id_operation = journal::begin(conn).unwrap();
+
+        import_rows(conn, Some(id_operation), &mut account, rows, 3).unwrap();
+
+        account
+    }
+
+    fn transactions(conn: &Connection) -> Vec<(String, i64, bool, Option<String>)> {
+        conn.prepare("SELECT message, value, charged, fitid FROM transactions ORDER BY id_transaction").unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))).unwrap()
+            .collect::<rusqlite::Result<_>>().unwrap()
+    }
+
+    #[test]
+    fn rows_without_fitid_get_one_per_occurrence() {
+        let rows = [row(10, "Coffee", -3.5), row(10, "Coffee", -3.5), row(11, "Coffee", -3.5)];
+
+        assert_eq!(statement_fitids(&rows), vec!["10:-350:Coffee#1", "10:-350:Coffee#2", "11:-350:Coffee#1"]);
+    }
+
+    #[test]
+    fn fitids_and_duplicates_are_skipped() {
+        let conn = walletdb::memory_database();
+        walletdb::new_account("Bank", 100.0, false, None).unwrap();
+
+        let rows = [
+            StatementRow { fitid: Some("A1".to_string()), ..row(10, "Coffee", -3.5) },
+            row(11, "Salary", 50.0)
+        ];
+        import(&conn, &rows);
+        let account = import(&conn, &rows);
+
+        assert_eq!(transactions(&conn).len(), 2);
+        assert_eq!(account.balance, 146.5);
+
+        // The same movement in an overlapping statement with another FITID.
+        import(&conn, &[StatementRow { fitid: Some("B7".to_string()), ..row(10, "Coffee", -3.5) }]);
+        assert_eq!(transactions(&conn).len(), 2);
+    }
+
+    #[test]
+    fn single_pending_expenses_are_charged() {
+        let conn = walletdb::memory_database();
+        let today = utils::today_julian_day();
+
+        walletdb::new_account("Bank", 100.0, false, None).unwrap();
+        walletdb::new_transaction("Groceries", 20.0, EXPENSE, false, false, None, None).unwrap();
+
+        let account = import(&conn, &[row(today + 2, "SUPERMARKET 123", -20.0)]);
+
+        assert_eq!(transactions(&conn), vec![
+            ("Groceries".to_string(), 2000, true, Some(format!("{}:-2000:SUPERMARKET 123#1", today + 2)))
+        ]);
+        assert_eq!((account.balance, account.available), (80.0, 80.0));
+
+        // Outside of the window the row is a new transaction.
+        walletdb::new_transaction("Books", 10.0, EXPENSE, false, false, None, None).unwrap();
+        import(&conn, &[row(today + 4, "BOOKSHOP", -10.0)]);
+        assert_eq!(transactions(&conn).len(), 3);
+    }
+
+    #[test]
+    fn several_pending_expenses_are_left_for_review() {
+        let conn = walletdb::memory_database();
+
+        walletdb::new_account("Bank", 100.0, false, None).unwrap();
+        walletdb::new_transaction("Lunch", 8.0, EXPENSE, false, false, None, None).unwrap();
+        walletdb::new_transaction("Taxi", 8.0, EXPENSE, false, false, None, None).unwrap();
+
+        let account = import(&conn, &[row(utils::today_julian_day(), "CARD PAYMENT", -8.0)]);
+
+        assert!(transactions(&conn).iter().all(|t| !t.2));
+        assert_eq!(account.balance, 100.0);
+    }
+
+    #[test]
+    fn imported_rows_are_categorised() {
+        let conn = walletdb::memory_database();
+
+        walletdb::new_account("Bank", 100.0, false, None).unwrap();
+        rules::rule_add("coffee", false, Some("Food"), None, None, None, None).unwrap();
+        import(&conn, &[row(10, "COFFEE SHOP", -3.5), row(10, "Salary", 50.0)]);
+
+        let categories: Vec<Option<String>> = conn.prepare("SELECT category FROM transactions ORDER BY id_transaction").unwrap()
+            .query_map([], |row| row.get(0)).unwrap()
+            .collect::<rusqlite::Result<_>>().unwrap();
+        assert_eq!(categories, vec![Some("Food".to_string()), None]);
+    }
+
+    #[test]
+    fn negative_rows_are_expenses() {
+        let row = StatementRow { date: 10, message: "Coffee".to_string(), value: -3.5, fitid: Some("1".to_string()) };
+        let transaction = row.to_transaction(7);
+
+        assert_eq!((transaction.t_type, transaction.value, transaction.id_account), (EXPENSE, 3.5, 7));
+        assert_eq!(transaction.fitid.as_deref(), Some("1"));
+        assert!(transaction.charged);
+
+        let row = StatementRow { value: 3.5, ..row };
+        assert_eq!(row.to_transaction(7).t_type, INCOMING);
+    }
+}
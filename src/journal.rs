@@ -0,0 +1,322 @@
+use crate::error;
+use crate::output;
+use crate::utils;
+use crate::walletdb;
+
+use std::env;
+
+use rusqlite::{params, Connection};
+
+// Journal helpers used by every mutating command. Each command opens one
+// operation and records the before/after image of every row it touches.
+pub fn begin(conn: &Connection) -> rusqlite::Result<i64> {
+    // A new operation discards the operations that could be redone.
+    conn.execute(
+        "DELETE FROM operation_rows WHERE id_operation IN
+            (SELECT id_operation FROM operations WHERE undone = 1)", []
+    )?;
+    conn.execute("DELETE FROM operations WHERE undone = 1", [])?;
+
+    let command: Vec<String> = env::args()
+        .skip(1)
+        .map(|arg| if arg.contains(' ') { format!("\"{}\"", arg) } else { arg })
+        .collect();
+
+    conn.execute(
+        "INSERT INTO operations (command, date) VALUES (?1, ?2)",
+        params![command.join(" "), utils::today_julian_day()]
+    )?;
+
+    Ok(conn.last_insert_rowid())
+}
+
+pub fn row_image(conn: &Connection, table_name: &str, id: i64) -> rusqlite::Result<Option<String>> {
+    let columns = table_columns(conn, table_name)?;
+
+    let pairs: Vec<String> = columns.iter().map(|c| format!("'{}', {}", c, c)).collect();
+    let query = format!(
+        "SELECT json_object({}) FROM {} WHERE {} = ?1",
+        pairs.join(", "), table_name, columns[0]
+    );
+
+    let mut stmt = conn.prepare(&query)?;
+    let mut rows = stmt.query(params![id])?;
+
+    match rows.next()? {
+        Some(row) => Ok(Some(row.get(0)?)),
+        None => Ok(None)
+    }
+}
+
+pub fn record(
+            conn: &Connection, id_operation: i64, table_name: &str, id: i64,
+            before: Option<String>
+        ) -> rusqlite::Result<()> {
+    let after = row_image(conn, table_name, id)?;
+
+    conn.execute(
+        "INSERT INTO operation_rows (id_operation, table_name, row_id, before, after)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![id_operation, table_name, id, before, after]
+    )?;
+
+    output::change(table_name, id, before.as_deref(), after.as_deref());
+
+    Ok(())
+}
+
+pub fn ids_where(conn: &Connection, table_name: &str, condition: &str) -> rusqlite::Result<Vec<i64>> {
+    let columns = table_columns(conn, table_name)?;
+    let query = format!("SELECT {} FROM {} WHERE {}", columns[0], table_name, condition);
+
+    let mut stmt = conn.prepare(&query)?;
+    let ids = stmt.query_map([], |row| row.get(0))?.collect();
+
+    ids
+}
+
+fn table_columns(conn: &Connection, table_name: &str) -> rusqlite::Result<Vec<String>> {
+    let stmt = conn.prepare(&format!("SELECT * FROM {} LIMIT 0", table_name))?;
+    let columns = stmt.column_names().iter().map(|c| c.to_string()).collect();
+
+    Ok(columns)
+}
+
+fn restore_row(
+            conn: &Connection, table_name: &str, id: i64, image: Option<&str>
+        ) -> rusqlite::Result<()> {
+    let columns = table_columns(conn, table_name)?;
+
+    match image {
+        Some(json) => {
+            let values: Vec<String> = columns.iter()
+                .map(|c| format!("json_extract(?1, '$.{}')", c))
+                .collect();
+
+            conn.execute(
+                &format!(
+                    "INSERT OR REPLACE INTO {} ({}) SELECT {}",
+                    table_name, columns.join(", "), values.join(", ")
+                ),
+                params![json]
+            )?;
+        }
+        None => {
+            conn.execute(
+                &format!("DELETE FROM {} WHERE {} = ?1", table_name, columns[0]),
+                params![id]
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+fn apply_operation(conn: &Connection, id_operation: i64, undo: bool) -> rusqlite::Result<()> {
+    let order = if undo { "DESC" } else { "ASC" };
+    let mut stmt = conn.prepare(&format!(
+        "SELECT table_name, row_id, before, after FROM operation_rows
+         WHERE id_operation = ?1 ORDER BY id_operation_row {}", order
+    ))?;
+
+    let changes = stmt.query_map(params![id_operation], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, i64>(1)?,
+            row.get::<_, Option<String>>(2)?,
+            row.get::<_, Option<String>>(3)?
+        ))
+    })?;
+
+    for change in changes {
+        let (table_name, id, before, after) = change?;
+        let image = if undo { before } else { after };
+
+        let current = row_image(conn, &table_name, id)?;
+        restore_row(conn, &table_name, id, image.as_deref())?;
+        output::change(&table_name, id, current.as_deref(), image.as_deref());
+    }
+
+    conn.execute(
+        "UPDATE operations SET undone = ?1 WHERE id_operation = ?2",
+        params![if undo { 1 } else { 0 }, id_operation]
+    )?;
+
+    Ok(())
+}
+
+
+// Wallet 'undo', 'redo' and 'history' subcommands are defined below.
+pub fn undo(count: i64) -> error::Result<()> {
+    let mut conn = walletdb::open_database()?;
+    let tx = conn.transaction()?;
+
+    let operations = select_operations(
+        &tx, "WHERE undone = 0 ORDER BY id_operation DESC", count
+    )?;
+
+    if operations.is_empty() {
+        output::message("There is nothing to undo!");
+        return Ok(());
+    }
+
+    for (id_operation, command) in &operations {
+        apply_operation(&tx, *id_operation, true)?;
+        output::message(format!("Undone: {:<4} wallet {}", id_operation, command));
+        output::record(vec![
+            ("operation", (*id_operation).into()),
+            ("action", "undone".into()),
+            ("command", command.as_str().into())
+        ]);
+    }
+
+    Ok(tx.commit()?)
+}
+
+pub fn redo(count: i64) -> error::Result<()> {
+    let mut conn = walletdb::open_database()?;
+    let tx = conn.transaction()?;
+
+    let operations = select_operations(
+        &tx, "WHERE undone = 1 ORDER BY id_operation ASC", count
+    )?;
+
+    if operations.is_empty() {
+        output::message("There is nothing to redo!");
+        return Ok(());
+    }
+
+    for (id_operation, command) in &operations {
+        apply_operation(&tx, *id_operation, false)?;
+        output::message(format!("Redone: {:<4} wallet {}", id_operation, command));
+        output::record(vec![
+            ("operation", (*id_operation).into()),
+            ("action", "redone".into()),
+            ("command", command.as_str().into())
+        ]);
+    }
+
+    Ok(tx.commit()?)
+}
+
+pub fn history(count: i64) -> error::Result<()> {
+    let conn = walletdb::open_database()?;
+
+    let mut stmt = conn.prepare(
+        "SELECT o.id_operation, o.command, o.date, o.undone, COUNT(r.id_operation_row)
+         FROM operations o LEFT JOIN operation_rows r ON r.id_operation = o.id_operation
+         GROUP BY o.id_operation ORDER BY o.id_operation DESC LIMIT ?1"
+    )?;
+
+    let operations = stmt.query_map(params![count], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, i64>(2)?,
+            row.get::<_, i64>(3)?,
+            row.get::<_, i64>(4)?
+        ))
+    })?;
+
+    let mut items_len = 0;
+
+    for operation in operations {
+        let (id_operation, command, julian_date, undone, n_rows) = operation?;
+        let date = utils::julian_day_to_iso(julian_date)?;
+
+        items_len += 1;
+        output::message(format!(
+            "{:<4}.- {} {} {:>3} rows  wallet {}",
+            id_operation, date, if undone == 1 { "(undone)" } else { "        " }, n_rows, command
+        ));
+        output::record(vec![
+            ("operation", id_operation.into()),
+            ("date", date.as_str().into()),
+            ("undone", (undone == 1).into()),
+            ("rows", n_rows.into()),
+            ("command", command.into())
+        ]);
+    }
+
+    if items_len == 0 {
+        output::message("The history is empty!");
+    }
+
+    Ok(())
+}
+
+fn select_operations(
+            conn: &Connection, clause: &str, count: i64
+        ) -> rusqlite::Result<Vec<(i64, String)>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id_operation, command FROM operations {} LIMIT ?1", clause
+    ))?;
+
+    let operations = stmt.query_map(params![count], |row| Ok((row.get(0)?, row.get(1)?)))?.collect();
+
+    operations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(conn: &Connection, sql: &str) -> i64 {
+        conn.query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn undo_and_redo_round_trip() {
+        let conn = walletdb::memory_database();
+
+        walletdb::new_account("Bank", 100.0, false, None).unwrap();
+        walletdb::new_transaction("Coffee", 30.0, 0, true, false, None, None).unwrap();
+        assert_eq!(count(&conn, "SELECT balance FROM accounts"), 7000);
+
+        undo(1).unwrap();
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM transactions"), 0);
+        assert_eq!(count(&conn, "SELECT balance FROM accounts"), 10000);
+
+        redo(1).unwrap();
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM transactions"), 1);
+        assert_eq!(count(&conn, "SELECT balance FROM accounts"), 7000);
+
+        undo(2).unwrap();
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM accounts"), 0);
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM operations WHERE undone = 1"), 2);
+    }
+
+    #[test]
+    fn account_commands_are_undone() {
+        let conn = walletdb::memory_database();
+
+        walletdb::new_account("Bank", 100.0, false, None).unwrap();
+        walletdb::new_account("Cash", 20.0, false, None).unwrap();
+
+        walletdb::account_edit("1", Some("Savings"), Some("150"), None).unwrap();
+        undo(1).unwrap();
+        assert_eq!(count(&conn, "SELECT balance FROM accounts WHERE name = 'Bank'"), 10000);
+
+        walletdb::account_default("2").unwrap();
+        undo(1).unwrap();
+        assert_eq!(count(&conn, "SELECT id_account FROM accounts WHERE is_default = 1"), 1);
+
+        walletdb::account_delete(None, true).unwrap();
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM accounts"), 0);
+        undo(1).unwrap();
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM accounts"), 2);
+    }
+
+    #[test]
+    fn new_operations_discard_the_undone_ones() {
+        let conn = walletdb::memory_database();
+
+        walletdb::new_account("Bank", 100.0, false, None).unwrap();
+        undo(1).unwrap();
+        walletdb::new_account("Cash", 20.0, false, None).unwrap();
+
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM operations"), 1);
+        redo(1).unwrap();
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM accounts"), 1);
+    }
+}
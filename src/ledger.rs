@@ -0,0 +1,475 @@
+use crate::error::{self, WalletError};
+use crate::export;
+use crate::output;
+use crate::structs::{Account, Transaction, ADJUSTMENT, EXPENSE, TRANSFER};
+use crate::utils;
+use crate::walletdb;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::Write;
+
+use rusqlite::{params, Connection};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Flavor {
+    Ledger,
+    Beancount
+}
+
+// A movement of 'cents' into 'account', balanced by 'counter'.
+struct Entry<'a> {
+    date: i64,
+    charged: bool,
+    payee: Option<&'a str>,
+    message: &'a str,
+    tags: Option<&'a str>,
+    account: &'a str,
+    counter: &'a str,
+    cents: i64
+}
+
+const OPENING_ACCOUNT: &str = "Equity:Opening-Balances";
+const ADJUSTMENT_ACCOUNT: &str = "Equity:Adjustments";
+
+// Wallet 'export ledger' and 'export beancount' subcommands are defined below.
+// Every transaction is written as a balanced entry between the account and an
+// income, expense or equity account. Pending transactions are flagged with '!'.
+pub fn export_journal(
+            flavor: Flavor, currency: &str, opt_from: Option<&str>, opt_to: Option<&str>,
+            opt_id_account: Option<&str>, opt_path: Option<&str>
+        ) -> error::Result<()> {
+    let conn = walletdb::open_database()?;
+
+    let (opt_from_date, opt_to_date) = utils::parse_date_range(opt_from, opt_to)?;
+
+    let opt_account_id = walletdb::account_filter(&conn, opt_id_account)?;
+
+    // All the accounts are read because transfers can point to any of them.
+    let accounts = accounts_before(&conn, opt_from_date)?;
+    let transactions = select_history(&conn, opt_account_id, opt_from_date, opt_to_date)?;
+
+    let transfer_accounts = transfer_accounts(&conn)?;
+
+    let names = account_names(&accounts);
+    let currencies: HashMap<i64, &str> = accounts.iter()
+        .map(|(account, _)| (account.id, account.currency.as_deref().unwrap_or(currency)))
+        .collect();
+
+    let exported = |id_account: i64| opt_account_id.is_none_or(|id| id == id_account);
+
+    if flavor == Flavor::Beancount {
+        let invalid = accounts.iter()
+            .map(|(account, _)| account.id)
+            .filter(|id| exported(*id))
+            .map(|id| currencies[&id])
+            .find(|account_currency| !is_commodity(account_currency));
+
+        if let Some(invalid) = invalid {
+            output::message(format!("Invalid currency '{}'! Beancount commodities are capital letters, like 'USD'.", invalid));
+            return Ok(());
+        }
+    }
+
+    // Wallet accounts with their currency, other accounts take any currency.
+    let mut used_accounts: BTreeMap<String, Option<&str>> = accounts.iter()
+        .filter(|(account, _)| exported(account.id))
+        .map(|(account, _)| (names[&account.id].clone(), Some(currencies[&account.id])))
+        .collect();
+    let mut entries: Vec<String> = Vec::new();
+
+    for (account, opening) in accounts.iter().filter(|(account, _)| exported(account.id)) {
+        if *opening == 0 {
+            continue;
+        }
+
+        let date = opt_from_date.unwrap_or_else(|| {
+            transactions.iter()
+                .filter(|t| t.id_account == account.id)
+                .map(|t| t.date)
+                .min()
+                .unwrap_or_else(utils::today_julian_day)
+        });
+
+        used_accounts.insert(OPENING_ACCOUNT.to_string(), None);
+        entries.push(format_entry(flavor, &Entry {
+            date,
+            charged: true,
+            payee: None,
+            message: "Opening balance",
+            tags: None,
+            account: &names[&account.id],
+            counter: OPENING_ACCOUNT,
+            cents: *opening
+        }, currencies[&account.id])?);
+    }
+
+    // Both rows of a transfer are written as a single entry.
+    let mut written_transfers: Vec<i64> = Vec::new();
+
+    for transaction in &transactions {
+        if written_transfers.contains(&transaction.id) {
+            continue;
+        }
+
+        let cents = (transaction.value * 100.0).round() as i64;
+        let signed = if transaction.t_type == EXPENSE { -cents } else { cents };
+
+        let counter = match transaction.id_transfer.and_then(|id| transfer_accounts.get(&id).map(|a| (id, a))) {
+            Some((id_transfer, id_account)) if transaction.t_type == TRANSFER => {
+                written_transfers.push(id_transfer);
+                names[id_account].clone()
+            }
+            _ => counter_account(transaction)
+        };
+        used_accounts.entry(counter.clone()).or_insert(None);
+
+        entries.push(format_entry(flavor, &Entry {
+            date: transaction.date,
+            charged: transaction.charged,
+            payee: transaction.payee.as_deref(),
+            message: &transaction.message,
+            tags: transaction.tags.as_deref(),
+            account: &names[&transaction.id_account],
+            counter: &counter,
+            cents: signed
+        }, currencies[&transaction.id_account])?);
+    }
+
+    let first_date = transactions.iter().map(|t| t.date)
+        .chain(opt_from_date)
+        .min()
+        .unwrap_or_else(utils::today_julian_day);
+
+    let mut journal = format!("; Exported by wallet on {}\n\n", utils::julian_day_to_iso(utils::today_julian_day())?);
+
+    let used_currencies: BTreeSet<&str> = used_accounts.values().flatten().copied().collect();
+
+    match flavor {
+        Flavor::Ledger => {
+            for used_currency in &used_currencies {
+                journal.push_str(&format!("commodity {}\n", used_currency));
+            }
+            journal.push('\n');
+
+            for name in used_accounts.keys() {
+                journal.push_str(&format!("account {}\n", name));
+            }
+        }
+        Flavor::Beancount => {
+            if let Some(first_currency) = used_currencies.iter().next() {
+                journal.push_str(&format!("option \"operating_currency\" \"{}\"\n\n", first_currency));
+            }
+
+            for (name, opt_currency) in &used_accounts {
+                journal.push_str(&format!("{} open {}", utils::julian_day_to_iso(first_date)?, name));
+                if let Some(account_currency) = opt_currency {
+                    journal.push_str(&format!(" {}", account_currency));
+                }
+                journal.push('\n');
+            }
+        }
+    }
+
+    for entry in &entries {
+        journal.push('\n');
+        journal.push_str(entry);
+    }
+
+    let mut out = export::open_output(opt_path)?;
+
+    if let Err(e) = out.write_all(journal.as_bytes()) {
+        return Err(WalletError::Io("Unable to write the export!".to_string(), e));
+    }
+
+    if let Some(path) = opt_path {
+        output::message(format!("Exported {} entries to '{}'.", entries.len(), path));
+    }
+
+    Ok(())
+}
+
+// Each account with its balance before the first exported day.
+// Each account with its cleared balance before the first exported day.
+pub fn accounts_before(conn: &Connection, opt_from_date: Option<i64>) -> rusqlite::Result<Vec<(Account, i64)>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT a.*, COALESCE(a.opening_balance, 0) + COALESCE((
+            SELECT SUM({}) FROM transactions t
+            WHERE t.id_account = a.id_account AND t.charged = 1 AND t.date < ?1
+        ), 0)
+        FROM accounts a ORDER BY a.id_account",
+        walletdb::SIGNED_VALUE
+    ))?;
+
+    let rows = stmt.query_map(
+        params![opt_from_date.unwrap_or(i64::MIN)],
+        |row| Ok((Account::from_row(row)?, row.get(row.as_ref().column_count() - 1)?))
+    )?;
+
+    rows.collect()
+}
+
+// Expenses still pending from before the first day are returned too, they are
+// not part of the opening balance.
+pub fn select_history(
+            conn: &Connection, opt_account_id: Option<i64>, opt_from_date: Option<i64>, opt_to_date: Option<i64>
+        ) -> rusqlite::Result<Vec<Transaction>> {
+    let mut stmt = conn.prepare(
+        "SELECT * FROM transactions
+         WHERE (?1 IS NULL OR id_account = ?1) AND date <= ?3 AND (date >= ?2 OR charged = 0)
+         ORDER BY date, id_transaction"
+    )?;
+
+    let rows = stmt.query_map(
+        params![opt_account_id, opt_from_date.unwrap_or(i64::MIN), opt_to_date.unwrap_or(i64::MAX)],
+        Transaction::from_row
+    )?;
+
+    rows.collect()
+}
+
+// Account of the other row of every transfer, by the ID of that row.
+pub fn transfer_accounts(conn: &Connection) -> rusqlite::Result<HashMap<i64, i64>> {
+    let mut stmt = conn.prepare("SELECT id_transaction, id_account FROM transactions WHERE t_type = ?1")?;
+    let rows = stmt.query_map(params![TRANSFER], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+    rows.collect()
+}
+
+// Accounts are assets, or liabilities when they are credit accounts. Names
+// that end up equal get the ID of the account appended.
+fn account_names(accounts: &[(Account, i64)]) -> HashMap<i64, String> {
+    let mut names: HashMap<i64, String> = HashMap::new();
+
+    for (account, _) in accounts {
+        let root = if account.credit { "Liabilities" } else { "Assets" };
+        let mut name = format!("{}:{}", root, account_path(&account.name));
+
+        if names.values().any(|other| *other == name) {
+            name = format!("{}-{}", name, account.id);
+        }
+
+        names.insert(account.id, name);
+    }
+
+    names
+}
+
+// Expenses and incomings go to an account derived from their category, for
+// example 'food:snacks' becomes 'Expenses:Food:Snacks'.
+fn counter_account(transaction: &Transaction) -> String {
+    let root = match transaction.t_type {
+        EXPENSE => "Expenses",
+        ADJUSTMENT => return ADJUSTMENT_ACCOUNT.to_string(),
+        _ => "Income"
+    };
+
+    match transaction.category.as_deref() {
+        Some(category) if !category.trim().is_empty() => format!("{}:{}", root, account_path(category)),
+        _ => format!("{}:Uncategorized", root)
+    }
+}
+
+// Each part separated by ':' becomes a component of the account name.
+fn account_path(text: &str) -> String {
+    let components: Vec<String> = text.split(':')
+        .filter(|component| !component.trim().is_empty())
+        .map(account_component)
+        .collect();
+
+    if components.is_empty() { account_component(text) } else { components.join(":") }
+}
+
+// Account components must start with a capital letter or a digit and only
+// have letters, digits and dashes.
+fn account_component(text: &str) -> String {
+    let words: Vec<String> = text.chars()
+        .map(|c| match c {
+            'á' | 'à' | 'ä' | 'â' => 'a',
+            'é' | 'è' | 'ë' | 'ê' => 'e',
+            'í' | 'ì' | 'ï' | 'î' => 'i',
+            'ó' | 'ò' | 'ö' | 'ô' => 'o',
+            'ú' | 'ù' | 'ü' | 'û' => 'u',
+            'ñ' => 'n',
+            'Á' | 'À' | 'Ä' | 'Â' => 'A',
+            'É' | 'È' | 'Ë' | 'Ê' => 'E',
+            'Í' | 'Ì' | 'Ï' | 'Î' => 'I',
+            'Ó' | 'Ò' | 'Ö' | 'Ô' => 'O',
+            'Ú' | 'Ù' | 'Ü' | 'Û' => 'U',
+            'Ñ' => 'N',
+            c if c.is_ascii_alphanumeric() => c,
+            _ => ' '
+        })
+        .collect::<String>()
+        .split_whitespace()
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => String::new()
+            }
+        })
+        .collect();
+
+    if words.is_empty() { "Unnamed".to_string() } else { words.join("-") }
+}
+
+// Beancount commodities start with a capital letter, followed by capitals,
+// digits or one of "'._-".
+fn is_commodity(text: &str) -> bool {
+    let mut chars = text.chars();
+
+    chars.next().is_some_and(|c| c.is_ascii_uppercase())
+        && chars.all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || "'._-".contains(c))
+}
+
+fn tag_name(tag: &str) -> String {
+    tag.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || "-_/.".contains(c) { c } else { '-' })
+        .collect()
+}
+
+fn quoted(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn amount(cents: i64, currency: &str) -> String {
+    let sign = if cents < 0 { "-" } else { "" };
+
+    format!("{}{}.{:02} {}", sign, cents.abs() / 100, cents.abs() % 100, currency)
+}
+
+fn format_entry(flavor: Flavor, entry: &Entry, currency: &str) -> error::Result<String> {
+    let flag = if entry.charged { "*" } else { "!" };
+    let tags: Vec<String> = entry.tags.unwrap_or("")
+        .split(',')
+        .filter(|tag| !tag.is_empty())
+        .map(tag_name)
+        .collect();
+
+    let mut text = format!("{} {} ", utils::julian_day_to_iso(entry.date)?, flag);
+
+    match flavor {
+        Flavor::Ledger => {
+            // hledger reads the text before '|' as the payee.
+            match entry.payee {
+                Some(payee) => text.push_str(&format!("{} | {}\n", payee, entry.message)),
+                None => text.push_str(&format!("{}\n", entry.message))
+            }
+
+            if !tags.is_empty() {
+                text.push_str(&format!("    ; :{}:\n", tags.join(":")));
+            }
+        }
+        Flavor::Beancount => {
+            if let Some(payee) = entry.payee {
+                text.push_str(&format!("{} ", quoted(payee)));
+            }
+            text.push_str(&quoted(entry.message));
+
+            for tag in &tags {
+                text.push_str(&format!(" #{}", tag));
+            }
+            text.push('\n');
+        }
+    }
+
+    text.push_str(&format!("    {:<40}  {:>16}\n", entry.account, amount(entry.cents, currency)));
+    text.push_str(&format!("    {:<40}  {:>16}\n", entry.counter, amount(-entry.cents, currency)));
+
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    fn entry<'a>(payee: Option<&'a str>, tags: Option<&'a str>) -> Entry<'a> {
+        Entry {
+            date: 2_461_333,
+            charged: false,
+            payee,
+            message: "Dinner \"La Casa\"",
+            tags,
+            account: "Assets:Bank",
+            counter: "Expenses:Food",
+            cents: -1205
+        }
+    }
+
+    #[test]
+    fn account_components() {
+        assert_eq!(account_component("cuenta de ahorro"), "Cuenta-De-Ahorro");
+        assert_eq!(account_component("Café & más"), "Cafe-Mas");
+        assert_eq!(account_component("***"), "Unnamed");
+    }
+
+    #[test]
+    fn counter_accounts_come_from_categories() {
+        let transaction = Transaction { t_type: EXPENSE, category: Some("food: snacks".to_string()), ..Transaction::empty() };
+        assert_eq!(counter_account(&transaction), "Expenses:Food:Snacks");
+
+        let transaction = Transaction { t_type: 1, ..Transaction::empty() };
+        assert_eq!(counter_account(&transaction), "Income:Uncategorized");
+
+        let transaction = Transaction { t_type: ADJUSTMENT, ..Transaction::empty() };
+        assert_eq!(counter_account(&transaction), ADJUSTMENT_ACCOUNT);
+    }
+
+    #[test]
+    fn beancount_commodities() {
+        assert!(is_commodity("USD"));
+        assert!(is_commodity("MXN.V2"));
+        assert!(!is_commodity("usd"));
+        assert!(!is_commodity("1USD"));
+        assert!(!is_commodity("US D"));
+        assert!(!is_commodity(""));
+    }
+
+    #[test]
+    fn ledger_entries() {
+        assert_eq!(format_entry(Flavor::Ledger, &entry(Some("La Casa"), Some("trip,date night")), "USD").unwrap(), concat!(
+            "2026-10-19 ! La Casa | Dinner \"La Casa\"\n",
+            "    ; :trip:date-night:\n",
+            "    Assets:Bank                                     -12.05 USD\n",
+            "    Expenses:Food                                    12.05 USD\n"
+        ));
+    }
+
+    #[test]
+    fn beancount_entries() {
+        assert_eq!(format_entry(Flavor::Beancount, &entry(None, Some("trip")), "USD").unwrap(), concat!(
+            "2026-10-19 ! \"Dinner \\\"La Casa\\\"\" #trip\n",
+            "    Assets:Bank                                     -12.05 USD\n",
+            "    Expenses:Food                                    12.05 USD\n"
+        ));
+    }
+
+    #[test]
+    fn pending_expenses_stay_out_of_the_opening_balance() {
+        let conn = walletdb::memory_database();
+        let path = env::temp_dir().join(format!("wallet-journal-{}.beancount", process::id()));
+        let str_path = path.to_str().unwrap();
+
+        walletdb::new_account("Bank", 100.0, false, None).unwrap();
+        walletdb::new_transaction("Coffee", 3.5, EXPENSE, true, false, None, None).unwrap();
+        walletdb::new_transaction("Rent", 50.0, EXPENSE, false, false, None, None).unwrap();
+        conn.execute("UPDATE transactions SET date = 2461300", []).unwrap();
+
+        export_journal(Flavor::Beancount, "usd", Some("2026-10-01"), None, None, Some(str_path)).unwrap();
+        assert!(!path.exists());
+
+        export_journal(Flavor::Beancount, "USD", Some("2026-10-01"), None, None, Some(str_path)).unwrap();
+        let journal = fs::read_to_string(&path).unwrap();
+
+        assert!(journal.contains("2026-10-01 * \"Opening balance\"\n    Assets:Bank                                      96.50 USD\n"));
+        assert!(journal.contains("2026-09-16 ! \"Rent\"\n    Assets:Bank                                     -50.00 USD\n"));
+        assert!(journal.contains("2026-09-16 open Assets:Bank USD\n"));
+        assert!(!journal.contains("Coffee"));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,98 +1,539 @@
 mod structs;
 mod utils;
+mod beancount;
+mod budgets;
+mod check;
 mod commands;
+mod csv_import;
+mod error;
+mod export;
+mod forecast;
+mod gnucash;
+mod homebank;
+mod html_report;
+mod importer;
+mod journal;
+mod ledger;
+mod ofx;
+mod output;
+mod payees;
+mod qif;
+mod reconcile;
+mod reports;
+mod rules;
+mod statement;
 mod walletdb;
 
+
+use std::fmt::Display;
 use std::path::PathBuf;
+use std::process;
+use std::str::FromStr;
+
+use clap::ArgMatches;
+
+use ledger::Flavor;
+use structs::CsvProfile;
 
 fn main() {
     let matches = commands::cli().get_matches();
 
-    match matches.subcommand() {
-        Some(("account", sub_matches)) => {
-            let account_subcommands = sub_matches.subcommand().unwrap();
+    if matches.is_present("porcelain") {
+        output::set_mode(output::Mode::Porcelain);
+    } else if matches.value_of("output") == Some("json") {
+        output::set_mode(output::Mode::Json);
+    }
+
+    let result = run(&matches);
+
+    if let Err(e) = &result {
+        output::error(e.kind(), e);
+    }
+
+    output::finish();
+
+    if let Err(e) = result {
+        process::exit(e.exit_code());
+    }
+}
+
+fn run(matches: &ArgMatches) -> error::Result<()> {
+    match subcommand(matches)? {
+        ("account", sub_matches) => {
+            let account_subcommands = subcommand(sub_matches)?;
 
             match account_subcommands {
                 ("default", args) => {
-                    let id = args.value_of("ID").expect("Required...");
+                    let id = required(args, "ID")?;
 
-                    walletdb::account_default(id).unwrap();
+                    walletdb::account_default(id)?;
                 }
                 ("delete", args) => {
                     let id = args.value_of("ID");
                     let delete_all = args.is_present("all");
 
-                    walletdb::account_delete(id, delete_all).unwrap();
+                    walletdb::account_delete(id, delete_all)?;
                 }
                 ("edit", args) => {
-                    let id = args.value_of("ID").expect("Required...");
+                    let id = required(args, "ID")?;
                     let opt_name = args.value_of("name");
                     let opt_balance = args.value_of("balance");
+                    let opt_minimum = args.value_of("minimum");
 
-                    walletdb::account_edit(id, opt_name, opt_balance).unwrap();
+                    walletdb::account_edit(id, opt_name, opt_balance, opt_minimum)?;
                 }
                 ("transfer", args) => {
-                    println!("Unimplemented...");
+                    let value: f64 = args.value_of_t("balance")?;
+                    let id_destination = required(args, "destination")?;
+                    let opt_id_source = args.value_of("source");
+
+                    walletdb::account_transfer(value, id_destination, opt_id_source)?;
                 }
-                _ => unreachable!()
+                (name, _) => return unknown_subcommand(name)
             }
         }
-        Some(("backup", args)) => {
-            let filename = args.value_of("FILENAME").expect("Required...");
+        ("afford", args) => {
+            let amount: f64 = args.value_of_t("AMOUNT")?;
+            let opt_id_account = args.value_of("account");
+            let opt_msi: Option<i64> = opt_value_of_t(args, "msi")?;
+            let days: i64 = args.value_of_t("days")?;
+
+            forecast::afford(amount, opt_id_account, opt_msi, days)?;
+        }
+        ("backup", args) => {
+            let filename = required(args, "FILENAME")?;
+            let encrypt = args.is_present("encrypt");
             let backup_path = PathBuf::from(format!("./{}.db3", filename));
 
-            walletdb::backup_database(&backup_path).unwrap();
+            walletdb::backup_database(&backup_path, encrypt)?;
+        }
+        ("budget", sub_matches) => {
+            let budget_subcommands = subcommand(sub_matches)?;
+
+            match budget_subcommands {
+                ("delete", args) => {
+                    let category = required(args, "CATEGORY")?;
+
+                    budgets::budget_delete(category)?;
+                }
+                ("list", args) => {
+                    let opt_month = args.value_of("month");
+
+                    budgets::budget_list(opt_month)?;
+                }
+                ("set", args) => {
+                    let category = required(args, "CATEGORY")?;
+                    let amount: f64 = args.value_of_t("AMOUNT")?;
+
+                    budgets::budget_set(category, amount)?;
+                }
+                (name, _) => return unknown_subcommand(name)
+            }
+        }
+        ("check", args) => {
+            let fix = args.is_present("fix");
+
+            check::check(fix)?;
+        }
+        ("db", sub_matches) => {
+            let db_subcommands = subcommand(sub_matches)?;
+
+            match db_subcommands {
+                ("rekey", _) => {
+                    walletdb::db_rekey()?;
+                }
+                (name, _) => return unknown_subcommand(name)
+            }
+        }
+        ("export", args) => {
+            let item_type = required(args, "ITEM")?;
+            let format = required(args, "format")?;
+            let opt_from = args.value_of("from");
+            let opt_to = args.value_of("to");
+            let opt_id_account = args.value_of("account");
+            let opt_path = args.value_of("file");
+            let currency = required(args, "currency")?;
+
+            match item_type {
+                "ledger" | "beancount" => {
+                    let flavor = if item_type == "ledger" { Flavor::Ledger } else { Flavor::Beancount };
+
+                    ledger::export_journal(flavor, currency, opt_from, opt_to, opt_id_account, opt_path)?;
+                }
+                "qif" => qif::export_qif(opt_from, opt_to, opt_id_account, opt_path)?,
+                _ => {
+                    let table_name = utils::item_type_to_table_name(item_type);
+
+                    export::export(&table_name, format, opt_from, opt_to, opt_id_account, opt_path)?;
+                }
+            }
         }
-        Some(("init", _)) => {
-            match walletdb::initialize_database() {
-                Ok(_) => println!("Successfully created new database!"),
-                Err(_) => println!("Something went wrong with the database creation! Try again...")
+        ("forecast", args) => {
+            let days: i64 = args.value_of_t("days")?;
+            let opt_id_account = args.value_of("account");
+
+            forecast::forecast(days, opt_id_account)?;
+        }
+        ("history", args) => {
+            let count: i64 = args.value_of_t("count")?;
+
+            journal::history(count)?;
+        }
+        ("import", sub_matches) => {
+            let import_subcommands = subcommand(sub_matches)?;
+
+            match import_subcommands {
+                ("beancount", args) => {
+                    let path = required(args, "FILE")?;
+                    let dry_run = args.is_present("dry-run");
+
+                    beancount::import_beancount(path, dry_run)?;
+                }
+                ("csv", args) => {
+                    let path = required(args, "FILE")?;
+                    let profile = required(args, "profile")?;
+                    let opt_id_account = args.value_of("account");
+                    let window: i64 = args.value_of_t("window")?;
+                    let dry_run = args.is_present("dry-run");
+
+                    csv_import::import_csv(path, profile, opt_id_account, window, dry_run)?;
+                }
+                ("gnucash", args) => {
+                    let path = required(args, "FILE")?;
+                    let dry_run = args.is_present("dry-run");
+
+                    gnucash::import_gnucash(path, dry_run)?;
+                }
+                ("homebank", args) => {
+                    let path = required(args, "FILE")?;
+                    let dry_run = args.is_present("dry-run");
+
+                    homebank::import_homebank(path, dry_run)?;
+                }
+                ("ofx", args) => {
+                    let path = required(args, "FILE")?;
+                    let opt_id_account = args.value_of("account");
+                    let window: i64 = args.value_of_t("window")?;
+                    let dry_run = args.is_present("dry-run");
+
+                    ofx::import_ofx(path, opt_id_account, window, dry_run)?;
+                }
+                ("profile", profile_matches) => {
+                    match subcommand(profile_matches)? {
+                        ("add", args) => {
+                            let first_char = |name: &str| -> error::Result<char> {
+                                match required(args, name)? {
+                                    "\\t" => Ok('\t'),
+                                    value => match value.chars().next() {
+                                        Some(first) => Ok(first),
+                                        None => error::invalid(format!("The {} can't be empty!", name))
+                                    }
+                                }
+                            };
+
+                            let profile = CsvProfile {
+                                name: required(args, "NAME")?.to_string(),
+                                delimiter: first_char("delimiter")?,
+                                skip_rows: args.value_of_t("skip-rows")?,
+                                date_column: args.value_of_t("date-column")?,
+                                date_format: required(args, "date-format")?.to_string(),
+                                description_column: args.value_of_t("description-column")?,
+                                amount_column: opt_value_of_t(args, "amount-column")?,
+                                debit_column: opt_value_of_t(args, "debit-column")?,
+                                credit_column: opt_value_of_t(args, "credit-column")?,
+                                decimal_separator: first_char("decimal-separator")?
+                            };
+
+                            csv_import::profile_add(&profile)?;
+                        }
+                        ("delete", args) => {
+                            let name = required(args, "NAME")?;
+
+                            csv_import::profile_delete(name)?;
+                        }
+                        ("list", _) => {
+                            csv_import::profile_list()?;
+                        }
+                        (name, _) => return unknown_subcommand(name)
+                    }
+                }
+                (name, _) => return unknown_subcommand(name)
             }
         }
-        Some(("list", args)) => {
-            let item_type = args.value_of("ITEM").expect("Required...");
-            let count: i64 = args.value_of_t("count").expect("Required...");
+        ("init", args) => {
+            let encrypt = args.is_present("encrypt");
+
+            walletdb::initialize_database(encrypt)?;
+            output::message("Successfully created new database!");
+        }
+        ("list", args) => {
+            let item_type = required(args, "ITEM")?;
+            let count: i64 = if args.is_present("all") {
+                -1
+            } else {
+                args.value_of_t("count")?
+            };
 
             let table_name = utils::item_type_to_table_name(item_type);
 
-            match walletdb::list(&table_name, count) {
-                Err(e) => utils::validate_tables(&format!("{}", e), &table_name),
-                _ => ()
+            match args.value_of("as-of") {
+                Some(str_date) if item_type == "account" => walletdb::list_accounts_as_of(str_date, count)?,
+                Some(_) => return error::invalid("Only accounts can be listed as of a date!"),
+                None => walletdb::list(&table_name, count)?
             }
         }
-        Some(("new", sub_matches)) => {
-            let new_subcommands = sub_matches.subcommand().unwrap();
+        ("new", sub_matches) => {
+            let new_subcommands = subcommand(sub_matches)?;
 
             match new_subcommands {
                 ("account", args) => {
-                    let name = args.value_of("NAME").expect("Required...");
-                    let balance: f64 = args.value_of_t("BALANCE").expect("Required...");
+                    let name = required(args, "NAME")?;
+                    let balance: f64 = args.value_of_t("BALANCE")?;
+
+                    let credit = args.is_present("credit");
+                    let opt_currency = args.value_of("currency");
 
-                    walletdb::new_account(&name, balance).unwrap();
+                    walletdb::new_account(name, balance, credit, opt_currency)?;
                 }
                 ("expense", args) => {
-                    let message = args.value_of("MESSAGE").expect("Required...");
-                    let value: f64 = args.value_of_t("VALUE").expect("Required...");
+                    let message = required(args, "MESSAGE")?;
+                    let value: f64 = args.value_of_t("VALUE")?;
                     let charged = args.is_present("charged");
                     let force_price = args.is_present("force_price");
                     let opt_id_account = args.value_of("account");
+                    let opt_payee = args.value_of("payee");
 
                     walletdb::new_transaction(
-                        &message, value, 0, charged, force_price, opt_id_account
-                    ).unwrap();
+                        message, value, 0, charged, force_price, opt_id_account, opt_payee
+                    )?;
                 }
                 ("incoming", args) => {
-                    let message = args.value_of("MESSAGE").expect("Required...");
-                    let value: f64 = args.value_of_t("VALUE").expect("Required...");
+                    let message = required(args, "MESSAGE")?;
+                    let value: f64 = args.value_of_t("VALUE")?;
                     let opt_id_account = args.value_of("account");
+                    let opt_payee = args.value_of("payee");
 
                     walletdb::new_transaction(
-                        &message, value, 1, true, false, opt_id_account
-                    ).unwrap();
+                        message, value, 1, true, false, opt_id_account, opt_payee
+                    )?;
+                }
+                ("msi", args) => {
+                    let name = required(args, "NAME")?;
+                    let price: f64 = args.value_of_t("PRICE")?;
+                    let installments: i64 = args.value_of_t("INSTALLMENTS")?;
+                    let date = required(args, "date")?;
+                    let paid: i64 = args.value_of_t("paid")?;
+                    let opt_id_account = args.value_of("account");
+
+                    walletdb::new_msi(name, price, installments, date, paid, opt_id_account)?;
+                }
+                ("payment", args) => {
+                    let name = required(args, "NAME")?;
+                    let price: f64 = args.value_of_t("PRICE")?;
+                    let date = required(args, "date")?;
+                    let every: i64 = args.value_of_t("every")?;
+                    let income = args.is_present("income");
+                    let opt_id_account = args.value_of("account");
+
+                    walletdb::new_payment(name, price, date, every, income, opt_id_account)?;
                 }
-                _ => unreachable!()
+                ("queue", args) => {
+                    let message = required(args, "MESSAGE")?;
+                    let value: f64 = args.value_of_t("VALUE")?;
+                    let opt_id_account = args.value_of("account");
+
+                    walletdb::new_queued(message, value, opt_id_account)?;
+                }
+                (name, _) => return unknown_subcommand(name)
             }
         }
-        _ => println!("Not match yet!"),
+        ("payee", sub_matches) => {
+            let payee_subcommands = subcommand(sub_matches)?;
+
+            match payee_subcommands {
+                ("add", args) => {
+                    let name = required(args, "NAME")?;
+                    let opt_category = args.value_of("category");
+
+                    payees::payee_add(name, opt_category)?;
+                }
+                ("category", args) => {
+                    let payee = required(args, "PAYEE")?;
+                    let opt_category = args.value_of("CATEGORY");
+
+                    payees::payee_category(payee, opt_category)?;
+                }
+                ("list", _) => {
+                    payees::payee_list()?;
+                }
+                ("merge", args) => {
+                    let payee = required(args, "PAYEE")?;
+                    let into = required(args, "INTO")?;
+
+                    payees::payee_merge(payee, into)?;
+                }
+                ("rename", args) => {
+                    let payee = required(args, "PAYEE")?;
+                    let new_name = required(args, "NEW_NAME")?;
+
+                    payees::payee_rename(payee, new_name)?;
+                }
+                (name, _) => return unknown_subcommand(name)
+            }
+        }
+        ("reconcile", args) => {
+            let id = required(args, "ACCOUNT")?;
+            let statement_balance: f64 = args.value_of_t("STATEMENT_BALANCE")?;
+            let opt_date = args.value_of("date");
+            let adjust = args.is_present("adjust");
+
+            reconcile::reconcile(id, statement_balance, opt_date, adjust)?;
+        }
+        ("redo", args) => {
+            let count: i64 = args.value_of_t("N")?;
+
+            journal::redo(count)?;
+        }
+        ("report", sub_matches) => {
+            let report_subcommands = subcommand(sub_matches)?;
+
+            match report_subcommands {
+                ("categories", args) => {
+                    let opt_month = args.value_of("month");
+                    let opt_from = args.value_of("from");
+                    let opt_to = args.value_of("to");
+                    let opt_id_account = args.value_of("account");
+                    let pending = args.is_present("pending");
+                    let opt_as_of = args.value_of("as-of");
+
+                    reports::report_categories(opt_month, opt_from, opt_to, opt_id_account, pending, opt_as_of)?;
+                }
+                ("html", args) => {
+                    let opt_path = args.value_of("file");
+                    let opt_year: Option<i32> = opt_value_of_t(args, "year")?;
+
+                    html_report::report_html(opt_path, opt_year)?;
+                }
+                ("monthly", args) => {
+                    let opt_year: Option<i32> = opt_value_of_t(args, "year")?;
+                    let opt_id_account = args.value_of("account");
+                    let pending = args.is_present("pending");
+                    let opt_as_of = args.value_of("as-of");
+
+                    reports::report_monthly(opt_year, opt_id_account, pending, opt_as_of)?;
+                }
+                ("networth", args) => {
+                    let monthly = args.is_present("monthly");
+                    let sparkline = args.is_present("sparkline");
+                    let opt_from = args.value_of("from");
+                    let opt_as_of = args.value_of("as-of");
+
+                    reports::report_networth(monthly, sparkline, opt_from, opt_as_of)?;
+                }
+                ("payees", args) => {
+                    let opt_from = args.value_of("from");
+                    let opt_to = args.value_of("to");
+                    let opt_id_account = args.value_of("account");
+
+                    reports::report_payees(opt_from, opt_to, opt_id_account)?;
+                }
+                (name, _) => return unknown_subcommand(name)
+            }
+        }
+        ("rule", sub_matches) => {
+            let rule_subcommands = subcommand(sub_matches)?;
+
+            match rule_subcommands {
+                ("add", args) => {
+                    let pattern = required(args, "match")?;
+                    let is_regex = args.is_present("regex");
+                    let opt_category = args.value_of("category");
+                    let opt_payee = args.value_of("payee");
+                    let opt_tags = args.value_of("tags");
+                    let opt_id_account = args.value_of("account");
+                    let opt_amount_range = args.value_of("amount-range");
+
+                    rules::rule_add(
+                        pattern, is_regex, opt_category, opt_payee, opt_tags, opt_id_account, opt_amount_range
+                    )?;
+                }
+                ("apply", args) => {
+                    let since = required(args, "since")?;
+                    let opt_id_account = args.value_of("account");
+
+                    rules::rule_apply(since, opt_id_account)?;
+                }
+                ("delete", args) => {
+                    let id = required(args, "ID")?;
+
+                    rules::rule_delete(id)?;
+                }
+                ("list", _) => {
+                    rules::rule_list()?;
+                }
+                ("test", args) => {
+                    let text = required(args, "TEXT")?;
+                    let opt_value: Option<f64> = opt_value_of_t(args, "value")?;
+                    let opt_id_account = args.value_of("account");
+
+                    rules::rule_test(text, opt_value, opt_id_account)?;
+                }
+                (name, _) => return unknown_subcommand(name)
+            }
+        }
+        ("statement", args) => {
+            let id = required(args, "ACCOUNT")?;
+            let month = required(args, "month")?;
+            let format = required(args, "format")?;
+            let opt_path = args.value_of("file");
+
+            statement::statement(id, month, format, opt_path)?;
+        }
+        ("undo", args) => {
+            let count: i64 = args.value_of_t("N")?;
+
+            journal::undo(count)?;
+        }
+        (name, _) => return unknown_subcommand(name)
+    }
+
+    Ok(())
+}
+
+// Clap asks for a subcommand where one is needed, this keeps run() free of
+// panics if it ever doesn't.
+fn subcommand(matches: &ArgMatches) -> error::Result<(&str, &ArgMatches)> {
+    let Some(subcommand) = matches.subcommand() else {
+        return error::invalid("A subcommand is needed! Try 'wallet --help'.");
+    };
+
+    Ok(subcommand)
+}
+
+// Subcommands listed by clap that have no command yet, like 'dequeue', and
+// external ones.
+fn unknown_subcommand(name: &str) -> error::Result<()> {
+    error::invalid(format!("The subcommand '{}' is not available! Try 'wallet --help'.", name))
+}
+
+// Positional arguments are optional for clap so it can show the help when
+// they are missing, the commands need them.
+fn required<'a>(args: &'a ArgMatches, name: &str) -> error::Result<&'a str> {
+    match args.value_of(name) {
+        Some(value) => Ok(value),
+        None => error::invalid(format!("The argument '{}' is required! Try 'wallet --help'.", name))
+    }
+}
+
+// Optional values that are given but can't be parsed are an error, not None.
+fn opt_value_of_t<T>(args: &ArgMatches, name: &str) -> error::Result<Option<T>>
+where
+    T: FromStr,
+    <T as FromStr>::Err: Display
+{
+    if args.is_present(name) {
+        Ok(Some(args.value_of_t(name)?))
+    } else {
+        Ok(None)
     }
 }
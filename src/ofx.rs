@@ -0,0 +1,330 @@
+use crate::error;
+use crate::importer::{self, StatementRow};
+use crate::journal;
+use crate::output;
+use crate::structs::Account;
+use crate::utils;
+use crate::walletdb;
+
+
+use chrono::NaiveDate;
+use rusqlite::{params, Connection};
+
+struct OfxStatement {
+    acctid: String,
+    rows: Vec<StatementRow>,
+    ledger_balance: Option<f64>,
+    ledger_date: Option<i64>
+}
+
+// Wallet 'import ofx' subcommand is defined below.
+pub fn import_ofx(
+            path: &str, opt_id_account: Option<&str>, window: i64, dry_run: bool
+        ) -> error::Result<()> {
+    let mut conn = walletdb::open_database()?;
+
+    let content = String::from_utf8_lossy(&utils::read_file(path)?).to_string();
+
+    let statements = read_statements(&content);
+
+    if statements.is_empty() {
+        return error::invalid(format!("No statements found in the file '{}'!", path));
+    } else if statements.len() > 1 && opt_id_account.is_some() {
+        return error::invalid(format!("The file has {} statements, link each OFX account before using --account...", statements.len()));
+    }
+
+    let tx = conn.transaction()?;
+    let opt_id_operation = if dry_run { None } else { Some(journal::begin(&tx)?) };
+
+    for statement in &statements {
+        let mut account = match select_linked_account(&tx, &statement.acctid)? {
+            Some(account) if opt_id_account.is_none() => account,
+            _ => match opt_id_account {
+                Some(id) => walletdb::select_account(&tx, Some(id))?,
+                None => {
+                    output::message(format!("The OFX account '{}' is not linked to any account!", statement.acctid));
+                    output::message("Use '--account <ID>' to link it, the next imports will remember it.");
+                    output::record(vec![
+                        ("record", "skipped".into()),
+                        ("entry", statement.acctid.as_str().into()),
+                        ("reason", "the OFX account is not linked to any account".into())
+                    ]);
+                    continue;
+                }
+            }
+        };
+
+        output::message(format!("Statement of the OFX account '{}' into '{}':", statement.acctid, account.name));
+
+        if let Some(id_operation) = opt_id_operation {
+            link_account(&tx, id_operation, &statement.acctid, account.id)?;
+        }
+
+        importer::import_rows(&tx, opt_id_operation, &mut account, &statement.rows, window)?;
+
+        if let Some(ledger_balance) = statement.ledger_balance {
+            let date = statement.ledger_date.map(utils::julian_day_to_iso).transpose()?;
+            let as_of = date.as_ref()
+                .map(|date| format!(" as of {}", date))
+                .unwrap_or_default();
+
+            output::message(format!(
+                "OFX ledger balance{}: ${:.2}. Wallet balance: ${:.2}. Difference: ${:.2}",
+                as_of, ledger_balance, account.balance, ledger_balance - account.balance
+            ));
+            output::record(vec![
+                ("record", "ledger".into()),
+                ("acctid", statement.acctid.as_str().into()),
+                ("date", date.into()),
+                ("ledger_balance", ledger_balance.into()),
+                ("balance", account.balance.into()),
+                ("difference", (ledger_balance - account.balance).into())
+            ]);
+        }
+    }
+
+    if dry_run {
+        return Ok(());
+    }
+
+    Ok(tx.commit()?)
+}
+
+fn select_linked_account(conn: &Connection, acctid: &str) -> rusqlite::Result<Option<Account>> {
+    let mut stmt = conn.prepare("SELECT * FROM accounts WHERE ofx_acctid = ?1")?;
+    let mut rows = stmt.query(params![acctid])?;
+
+    match rows.next()? {
+        Some(row) => Ok(Some(Account::from_row(row)?)),
+        None => Ok(None)
+    }
+}
+
+// An OFX account is linked to one account only.
+fn link_account(conn: &Connection, id_operation: i64, acctid: &str, id_account: i64) -> rusqlite::Result<()> {
+    let ids: Vec<i64> = {
+        let mut stmt = conn.prepare(
+            "SELECT id_account FROM accounts
+             WHERE (ofx_acctid = ?1 AND id_account <> ?2) OR (id_account = ?2 AND ofx_acctid IS NOT ?1)"
+        )?;
+        let ids = stmt.query_map(params![acctid, id_account], |row| row.get(0))?;
+
+        ids.collect::<rusqlite::Result<_>>()?
+    };
+
+    for id in ids {
+        let before = journal::row_image(conn, "accounts", id)?;
+        let opt_acctid = if id == id_account { Some(acctid) } else { None };
+
+        conn.execute(
+            "UPDATE accounts SET ofx_acctid = ?1 WHERE id_account = ?2",
+            params![opt_acctid, id]
+        )?;
+
+        journal::record(conn, id_operation, "accounts", id, before)?;
+    }
+
+    Ok(())
+}
+
+
+// OFX 1.x is SGML where elements are not closed, OFX 2.x is XML. Reading the
+// text up to the next tag works for both.
+fn read_statements(content: &str) -> Vec<OfxStatement> {
+    let mut statements = Vec::new();
+
+    for tag in ["STMTRS", "CCSTMTRS"] {
+        for block in blocks(content, tag) {
+            let acctid = element(block, "ACCTID").unwrap_or_default();
+
+            let mut rows = Vec::new();
+            for transaction in blocks(block, "STMTTRN") {
+                match read_transaction(transaction) {
+                    Some(row) => rows.push(row),
+                    None => {
+                        output::message(format!("Transaction skipped, it has no valid date or amount:\n{}", transaction.trim()));
+                        output::record(vec![
+                            ("record", "skipped".into()),
+                            ("entry", element(transaction, "FITID").unwrap_or_default().into()),
+                            ("reason", "no valid date or amount".into())
+                        ]);
+                    }
+                }
+            }
+
+            let ledger = blocks(block, "LEDGERBAL");
+            let ledger_balance = ledger.first()
+                .and_then(|ledger| element(ledger, "BALAMT"))
+                .and_then(|amount| importer::parse_amount(&amount, '.'));
+            let ledger_date = ledger.first()
+                .and_then(|ledger| element(ledger, "DTASOF"))
+                .and_then(|date| parse_ofx_date(&date));
+
+            statements.push(OfxStatement { acctid, rows, ledger_balance, ledger_date });
+        }
+    }
+
+    statements
+}
+
+fn read_transaction(block: &str) -> Option<StatementRow> {
+    let date = parse_ofx_date(&element(block, "DTPOSTED")?)?;
+    let value = importer::parse_amount(&element(block, "TRNAMT")?, '.')?;
+
+    let message = match (element(block, "NAME"), element(block, "MEMO")) {
+        (Some(name), Some(memo)) if name != memo => format!("{} - {}", name, memo),
+        (Some(name), _) => name,
+        (None, Some(memo)) => memo,
+        (None, None) => "OFX transaction".to_string()
+    };
+
+    Some(StatementRow {
+        date,
+        message,
+        value,
+        fitid: element(block, "FITID")
+    })
+}
+
+// Dates look like 20261003120000.000[-6:CST], only the day is used.
+fn parse_ofx_date(text: &str) -> Option<i64> {
+    let day = text.get(0..8)?;
+
+    NaiveDate::parse_from_str(day, "%Y%m%d").ok().map(utils::naive_to_julian_day)
+}
+
+fn blocks<'a>(content: &'a str, tag: &str) -> Vec<&'a str> {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+
+    let mut blocks = Vec::new();
+    let mut rest = content;
+
+    while let Some(start) = rest.find(&open) {
+        let after_open = &rest[start + open.len()..];
+        let end = after_open.find(&close).unwrap_or(after_open.len());
+
+        blocks.push(&after_open[..end]);
+        rest = &after_open[end..];
+    }
+
+    blocks
+}
+
+fn element(block: &str, tag: &str) -> Option<String> {
+    let open = format!("<{}>", tag);
+    let start = block.find(&open)? + open.len();
+    let rest = &block[start..];
+    let end = rest.find('<').unwrap_or(rest.len());
+
+    let value = rest[..end].trim()
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&");
+
+    if value.is_empty() { None } else { Some(value) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    const SGML: &str = "OFXHEADER:100
+DATA:OFXSGML
+
+<OFX><BANKMSGSRSV1><STMTTRNRS><STMTRS><CURDEF>USD
+<BANKACCTFROM><ACCTID>12345<ACCTTYPE>CHECKING</BANKACCTFROM>
+<BANKTRANLIST>
+<STMTTRN><TRNTYPE>DEBIT<DTPOSTED>20261003120000.000[-6:CST]<TRNAMT>-3.50<FITID>A1<NAME>Coffee &amp; Co<MEMO>Card</STMTTRN>
+<STMTTRN><TRNTYPE>CREDIT<DTPOSTED>20261005<TRNAMT>100.00<FITID>A2<NAME>Salary<MEMO>Salary</STMTTRN>
+<STMTTRN><TRNTYPE>DEBIT<DTPOSTED>yesterday<TRNAMT>-1.00<FITID>A3</STMTTRN>
+</BANKTRANLIST>
+<LEDGERBAL><BALAMT>106.50<DTASOF>20261006</LEDGERBAL>
+</STMTRS></STMTTRNRS></BANKMSGSRSV1></OFX>
+";
+
+    const XML: &str = "<?xml version=\"1.0\"?><OFX><CREDITCARDMSGSRSV1><CCSTMTTRNRS><CCSTMTRS>
+<CCACCTFROM><ACCTID>9999</ACCTID></CCACCTFROM>
+<BANKTRANLIST><STMTTRN><DTPOSTED>20261001</DTPOSTED><TRNAMT>-20.00</TRNAMT><FITID>C1</FITID><MEMO>Books</MEMO></STMTTRN></BANKTRANLIST>
+</CCSTMTRS></CCSTMTTRNRS></CREDITCARDMSGSRSV1></OFX>";
+
+    fn value(conn: &Connection, sql: &str) -> i64 {
+        conn.query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn sgml_statements() {
+        let statements = read_statements(SGML);
+
+        assert_eq!(statements.len(), 1);
+        assert_eq!(statements[0].acctid, "12345");
+        assert_eq!(statements[0].ledger_balance, Some(106.5));
+        assert_eq!(statements[0].ledger_date, utils::parse_date("2026-10-06"));
+
+        let rows = &statements[0].rows;
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].date, utils::parse_date("2026-10-03").unwrap());
+        assert_eq!((rows[0].message.as_str(), rows[0].value), ("Coffee & Co - Card", -3.5));
+        assert_eq!(rows[0].fitid.as_deref(), Some("A1"));
+        assert_eq!(rows[1].message, "Salary");
+    }
+
+    #[test]
+    fn xml_credit_card_statements() {
+        let statements = read_statements(XML);
+
+        assert_eq!(statements.len(), 1);
+        assert_eq!(statements[0].acctid, "9999");
+        assert_eq!(statements[0].ledger_balance, None);
+        assert_eq!(statements[0].rows[0].message, "Books");
+        assert_eq!(statements[0].rows[0].value, -20.0);
+    }
+
+    #[test]
+    fn fitids_are_imported_once() {
+        let conn = walletdb::memory_database();
+        let path = env::temp_dir().join(format!("wallet-import-{}.ofx", process::id()));
+        fs::write(&path, SGML).unwrap();
+
+        walletdb::new_account("Bank", 10.0, false, None).unwrap();
+        walletdb::new_account("Cash", 0.0, false, None).unwrap();
+
+        import_ofx(path.to_str().unwrap(), None, 3, false).unwrap();
+        assert_eq!(value(&conn, "SELECT COUNT(*) FROM transactions"), 0);
+
+        import_ofx(path.to_str().unwrap(), Some("1"), 3, false).unwrap();
+        assert_eq!(value(&conn, "SELECT COUNT(*) FROM transactions"), 2);
+        assert_eq!(value(&conn, "SELECT balance FROM accounts WHERE id_account = 1"), 10650);
+
+        // The account is remembered and the rows are not imported again.
+        import_ofx(path.to_str().unwrap(), None, 3, false).unwrap();
+        assert_eq!(value(&conn, "SELECT COUNT(*) FROM transactions"), 2);
+
+        // Linking the OFX account to another account unlinks the first one.
+        import_ofx(path.to_str().unwrap(), Some("2"), 3, false).unwrap();
+        assert_eq!(value(&conn, "SELECT id_account FROM accounts WHERE ofx_acctid = '12345'"), 2);
+        assert_eq!(value(&conn, "SELECT COUNT(*) FROM transactions WHERE id_account = 2"), 2);
+
+        journal::undo(1).unwrap();
+        assert_eq!(value(&conn, "SELECT id_account FROM accounts WHERE ofx_acctid = '12345'"), 1);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn linking_binds_quotes_as_text() {
+        let conn = walletdb::memory_database();
+
+        walletdb::new_account("Bank", 10.0, false, None).unwrap();
+        link_account(&conn, 1, "12' OR '1'='1", 1).unwrap();
+
+        assert_eq!(select_linked_account(&conn, "12' OR '1'='1").unwrap().unwrap().id, 1);
+        assert!(select_linked_account(&conn, "12").unwrap().is_none());
+    }
+}
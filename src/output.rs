@@ -0,0 +1,220 @@
+use std::fmt::Display;
+use std::sync::{Mutex, OnceLock};
+
+use serde_json::{json, Map, Value};
+
+// How the results are written, set once from the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    // Messages for people, like always.
+    Text,
+    // One JSON document written when the command ends.
+    Json,
+    // One tab-separated line per record or change, errors to stderr.
+    Porcelain
+}
+
+struct Document {
+    items: Vec<Value>,
+    changes: Vec<Value>,
+    messages: Vec<String>,
+    errors: Vec<Value>,
+    // The command wrote its own data to the standard output, like an export.
+    raw: bool
+}
+
+static MODE: OnceLock<Mode> = OnceLock::new();
+static DOCUMENT: Mutex<Document> = Mutex::new(Document {
+    items: Vec::new(),
+    changes: Vec::new(),
+    messages: Vec::new(),
+    errors: Vec::new(),
+    raw: false
+});
+
+pub fn set_mode(mode: Mode) {
+    let _ = MODE.set(mode);
+}
+
+pub fn mode() -> Mode {
+    *MODE.get().unwrap_or(&Mode::Text)
+}
+
+pub fn is_text() -> bool {
+    mode() == Mode::Text
+}
+
+fn document() -> std::sync::MutexGuard<'static, Document> {
+    DOCUMENT.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+// Text for people. Kept as a message in JSON and left out in porcelain mode.
+pub fn message<T: Display>(text: T) {
+    match mode() {
+        Mode::Text => println!("{}", text),
+        Mode::Json => document().messages.push(text.to_string()),
+        Mode::Porcelain => ()
+    }
+}
+
+// Something that stopped the command. The kind is a short stable name, like
+// 'not-found', for scripts to check.
+pub fn error<T: Display>(kind: &str, text: T) {
+    match mode() {
+        Mode::Text => eprintln!("{}", text),
+        Mode::Json => document().errors.push(json!({ "kind": kind, "message": text.to_string() })),
+        Mode::Porcelain => eprintln!("error\t{}\t{}", kind, porcelain_field(&Value::from(text.to_string())))
+    }
+}
+
+// One item of a list or one line of a report, with its fields in order.
+pub fn record(fields: Vec<(&str, Value)>) {
+    match mode() {
+        Mode::Text => (),
+        Mode::Json => {
+            let item: Map<String, Value> = fields.into_iter().map(|(name, value)| (name.to_string(), value)).collect();
+            document().items.push(Value::Object(item));
+        }
+        Mode::Porcelain => {
+            let line: Vec<String> = fields.iter().map(|(_, value)| porcelain_field(value)).collect();
+            println!("{}", line.join("\t"));
+        }
+    }
+}
+
+// Cents as a JSON number of the currency.
+pub fn money(cents: i64) -> Value {
+    Value::from(cents as f64 / 100.0)
+}
+
+// A row written by a command, with its images before and after as JSON.
+pub fn change(table_name: &str, row_id: i64, before: Option<&str>, after: Option<&str>) {
+    if is_text() {
+        return;
+    }
+
+    let action = change_action(before, after);
+    let image = |opt_text: Option<&str>| opt_text.and_then(|text| serde_json::from_str(text).ok()).unwrap_or(Value::Null);
+
+    document().changes.push(json!({
+        "table": table_name,
+        "id": row_id,
+        "action": action,
+        "before": image(before),
+        "after": image(after)
+    }));
+}
+
+fn change_action(before: Option<&str>, after: Option<&str>) -> &'static str {
+    match (before, after) {
+        (None, Some(_)) => "created",
+        (Some(_), None) => "deleted",
+        _ => "updated"
+    }
+}
+
+// The changes were rolled back, like in a dry run.
+pub fn discard_changes() {
+    document().changes.clear();
+}
+
+pub fn raw_output() {
+    document().raw = true;
+}
+
+// Writes the JSON document, or the changes in porcelain mode. Commands that
+// wrote their own data to the standard output only get their errors written,
+// to stderr.
+pub fn finish() {
+    let document = document();
+
+    match mode() {
+        Mode::Text => (),
+        Mode::Json if document.raw => {
+            if !document.errors.is_empty() {
+                eprintln!("{}", json!({ "ok": false, "errors": document.errors }));
+            }
+        }
+        Mode::Json => println!("{}", serde_json::to_string_pretty(&json_result(&document)).unwrap_or_default()),
+        Mode::Porcelain => {
+            for change in &document.changes {
+                println!("{}\t{}\t{}", change["action"].as_str().unwrap_or(""), change["table"].as_str().unwrap_or(""), change["id"]);
+            }
+        }
+    }
+}
+
+fn json_result(document: &Document) -> Value {
+    json!({
+        "ok": document.errors.is_empty(),
+        "items": document.items,
+        "changes": document.changes,
+        "messages": document.messages,
+        "errors": document.errors
+    })
+}
+
+// Amounts are written with two decimals, text without tabs or line breaks.
+fn porcelain_field(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::Bool(flag) => (if *flag { "1" } else { "0" }).to_string(),
+        Value::Number(number) if number.is_f64() => format!("{:.2}", number.as_f64().unwrap_or(0.0)),
+        Value::String(text) => text.replace(['\t', '\n', '\r'], " "),
+        other => other.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn porcelain_fields_are_plain_text() {
+        assert_eq!(porcelain_field(&Value::Null), "");
+        assert_eq!(porcelain_field(&Value::from(true)), "1");
+        assert_eq!(porcelain_field(&Value::from(12)), "12");
+        assert_eq!(porcelain_field(&money(1050)), "10.50");
+        assert_eq!(porcelain_field(&money(-3)), "-0.03");
+        assert_eq!(porcelain_field(&Value::from("Rent\tof\r\nMay")), "Rent of  May");
+    }
+
+    #[test]
+    fn changes_are_named_by_their_images() {
+        assert_eq!(change_action(None, Some("{}")), "created");
+        assert_eq!(change_action(Some("{}"), None), "deleted");
+        assert_eq!(change_action(Some("{}"), Some("{}")), "updated");
+    }
+
+    #[test]
+    fn the_document_is_ok_without_errors() {
+        let mut document = Document {
+            items: vec![json!({ "id": 1 })],
+            changes: Vec::new(),
+            messages: vec!["Done.".to_string()],
+            errors: Vec::new(),
+            raw: false
+        };
+
+        let result = json_result(&document);
+        assert_eq!(result["ok"], true);
+        assert_eq!(result["items"][0]["id"], 1);
+        assert_eq!(result["messages"][0], "Done.");
+
+        document.errors.push(json!({ "kind": "not-found", "message": "Account not found!" }));
+        let result = json_result(&document);
+        assert_eq!(result["ok"], false);
+        assert_eq!(result["errors"][0]["kind"], "not-found");
+    }
+
+    // Tests run in text mode, where nothing is kept for the document.
+    #[test]
+    fn text_mode_writes_right_away() {
+        message("Text for people.");
+        record(vec![("id", Value::from(1))]);
+        change("accounts", 1, None, Some("{}"));
+
+        assert_eq!(mode(), Mode::Text);
+        assert!(document().messages.is_empty() && document().items.is_empty() && document().changes.is_empty());
+    }
+}
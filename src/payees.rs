@@ -0,0 +1,343 @@
+use crate::error::{self, WalletError};
+use crate::journal;
+use crate::output;
+use crate::structs::{Payee, Transaction};
+use crate::walletdb;
+
+use rusqlite::{params, Connection};
+
+pub fn select_payees(conn: &Connection) -> rusqlite::Result<Vec<Payee>> {
+    let mut stmt = conn.prepare("SELECT * FROM payees ORDER BY name")?;
+    let payees = stmt.query_map([], Payee::from_row)?;
+
+    payees.collect()
+}
+
+// Payees whose name starts with the text, case is ignored. A payee with
+// exactly that name is the only match.
+fn matching_payees(conn: &Connection, text: &str) -> rusqlite::Result<Vec<Payee>> {
+    let text = text.trim().to_lowercase();
+    let payees = select_payees(conn)?;
+
+    if let Some(index) = payees.iter().position(|payee| payee.name.to_lowercase() == text) {
+        return Ok(payees.into_iter().skip(index).take(1).collect());
+    }
+
+    Ok(payees.into_iter().filter(|payee| payee.name.to_lowercase().starts_with(&text)).collect())
+}
+
+fn ambiguous(text: &str, payees: &[Payee]) -> WalletError {
+    let names: Vec<&str> = payees.iter().map(|payee| payee.name.as_str()).collect();
+
+    WalletError::InvalidInput(format!(
+        "Several payees start with '{}', please write more of the name: {}", text, names.join(", ")
+    ))
+}
+
+// An existing payee given by its name or the beginning of it.
+fn find_payee(conn: &Connection, text: &str) -> error::Result<Payee> {
+    let mut payees = matching_payees(conn, text)?;
+
+    match payees.len() {
+        0 => error::not_found(format!("Payee '{}' not found! Try 'wallet payee list'.", text)),
+        1 => Ok(payees.remove(0)),
+        _ => Err(ambiguous(text, &payees))
+    }
+}
+
+// Name for the payee of a new transaction, the known payee that starts with
+// the text or the text itself for a new payee.
+pub fn payee_name(conn: &Connection, text: &str) -> error::Result<String> {
+    let payees = matching_payees(conn, text)?;
+
+    match payees.as_slice() {
+        [] => Ok(text.trim().to_string()),
+        [payee] => Ok(payee.name.clone()),
+        _ => Err(ambiguous(text, &payees))
+    }
+}
+
+// Adds the payee of the transaction to the payees if it's new, otherwise
+// writes it as the known payee and fills the category with its default one.
+pub fn register_payee(conn: &Connection, id_operation: i64, transaction: &mut Transaction) -> rusqlite::Result<()> {
+    let name = match transaction.payee.as_deref().map(str::trim) {
+        Some(name) if !name.is_empty() => name.to_string(),
+        _ => return Ok(())
+    };
+
+    let mut stmt = conn.prepare("SELECT * FROM payees WHERE name = ?1")?;
+    let mut rows = stmt.query(params![name])?;
+
+    match rows.next()? {
+        Some(row) => {
+            let payee = Payee::from_row(row)?;
+
+            transaction.payee = Some(payee.name);
+            if transaction.category.is_none() {
+                transaction.category = payee.category;
+            }
+        }
+        None => {
+            conn.execute("INSERT INTO payees (name) VALUES (?1)", params![name])?;
+            journal::record(conn, id_operation, "payees", conn.last_insert_rowid(), None)?;
+
+            transaction.payee = Some(name);
+        }
+    }
+
+    Ok(())
+}
+
+// Writes the new name in the transactions and rules with the old one.
+fn rename_references(conn: &Connection, id_operation: i64, old_name: &str, new_name: &str) -> rusqlite::Result<usize> {
+    let mut n_transactions = 0;
+
+    for (table_name, id_column) in [("transactions", "id_transaction"), ("rules", "id_rule")] {
+        let ids: Vec<i64> = {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM {} WHERE payee = ?1 COLLATE NOCASE", id_column, table_name
+            ))?;
+            let rows = stmt.query_map(params![old_name], |row| row.get(0))?;
+
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+
+        for id in &ids {
+            let before = journal::row_image(conn, table_name, *id)?;
+
+            conn.execute(
+                &format!("UPDATE {} SET payee = ?1 WHERE {} = ?2", table_name, id_column),
+                params![new_name, id]
+            )?;
+
+            journal::record(conn, id_operation, table_name, *id, before)?;
+        }
+
+        if table_name == "transactions" {
+            n_transactions = ids.len();
+        }
+    }
+
+    Ok(n_transactions)
+}
+
+
+// Wallet 'payee' subcommands are defined below.
+pub fn payee_add(name: &str, opt_category: Option<&str>) -> error::Result<()> {
+    let name = name.trim();
+
+    if name.is_empty() {
+        return error::invalid("The name of the payee can't be empty!");
+    }
+
+    let mut conn = walletdb::open_database()?;
+
+    if let Some(payee) = matching_payees(&conn, name)?.into_iter().find(|p| p.name.to_lowercase() == name.to_lowercase()) {
+        return error::invalid(format!("Payee '{}' already exists!", payee.name));
+    }
+
+    let tx = conn.transaction()?;
+    let id_operation = journal::begin(&tx)?;
+
+    tx.execute("INSERT INTO payees (name, category) VALUES (?1, ?2)", params![name, opt_category])?;
+    journal::record(&tx, id_operation, "payees", tx.last_insert_rowid(), None)?;
+
+    tx.commit()?;
+
+    output::message(format!("Successfully created payee '{}'!", name));
+
+    Ok(())
+}
+
+pub fn payee_list() -> error::Result<()> {
+    let conn = walletdb::open_database()?;
+
+    let payees = select_payees(&conn)?;
+
+    let mut stmt = conn.prepare("SELECT COUNT(*) FROM transactions WHERE payee = ?1 COLLATE NOCASE")?;
+
+    for payee in &payees {
+        let n_transactions: i64 = stmt.query_row(params![payee.name], |row| row.get(0))?;
+
+        output::message(format!("{:<50} {:>5} transactions", payee.to_string(), n_transactions));
+        output::record(vec![
+            ("name", payee.name.as_str().into()),
+            ("category", payee.category.as_deref().into()),
+            ("transactions", n_transactions.into())
+        ]);
+    }
+
+    if payees.is_empty() {
+        output::message("There are no payees! Try 'wallet payee add --help'.");
+    }
+
+    Ok(())
+}
+
+// The category is given to new expenses and incomings of the payee without
+// one. Without category the default one is removed.
+pub fn payee_category(text: &str, opt_category: Option<&str>) -> error::Result<()> {
+    let mut conn = walletdb::open_database()?;
+
+    let payee = find_payee(&conn, text)?;
+
+    let tx = conn.transaction()?;
+    let id_operation = journal::begin(&tx)?;
+
+    let before = journal::row_image(&tx, "payees", payee.id)?;
+    tx.execute("UPDATE payees SET category = ?1 WHERE id_payee = ?2", params![opt_category, payee.id])?;
+    journal::record(&tx, id_operation, "payees", payee.id, before)?;
+
+    tx.commit()?;
+
+    match opt_category {
+        Some(category) => output::message(format!("Default category of '{}' is now [{}].", payee.name, category)),
+        None => output::message(format!("'{}' has no default category now.", payee.name))
+    }
+
+    Ok(())
+}
+
+pub fn payee_rename(text: &str, new_name: &str) -> error::Result<()> {
+    let new_name = new_name.trim();
+
+    if new_name.is_empty() {
+        return error::invalid("The name of the payee can't be empty!");
+    }
+
+    let mut conn = walletdb::open_database()?;
+
+    let payee = find_payee(&conn, text)?;
+
+    let existing = matching_payees(&conn, new_name)?.into_iter()
+        .find(|other| other.id != payee.id && other.name.to_lowercase() == new_name.to_lowercase());
+
+    if let Some(other) = existing {
+        return error::invalid(format!("Payee '{}' already exists! Try 'wallet payee merge' to join both payees.", other.name));
+    }
+
+    let tx = conn.transaction()?;
+    let id_operation = journal::begin(&tx)?;
+
+    let before = journal::row_image(&tx, "payees", payee.id)?;
+    tx.execute("UPDATE payees SET name = ?1 WHERE id_payee = ?2", params![new_name, payee.id])?;
+    journal::record(&tx, id_operation, "payees", payee.id, before)?;
+
+    let n_transactions = rename_references(&tx, id_operation, &payee.name, new_name)?;
+
+    tx.commit()?;
+
+    output::message(format!("Payee '{}' renamed to '{}' in {} transactions.", payee.name, new_name, n_transactions));
+
+    Ok(())
+}
+
+// The transactions and rules of the first payee are moved to the second one,
+// then the first payee is deleted.
+pub fn payee_merge(text: &str, into_text: &str) -> error::Result<()> {
+    let mut conn = walletdb::open_database()?;
+
+    let payee = find_payee(&conn, text)?;
+    let into = find_payee(&conn, into_text)?;
+
+    if payee.id == into.id {
+        return error::invalid("A payee can't be merged into itself!");
+    }
+
+    let tx = conn.transaction()?;
+    let id_operation = journal::begin(&tx)?;
+
+    let n_transactions = rename_references(&tx, id_operation, &payee.name, &into.name)?;
+
+    if into.category.is_none() && payee.category.is_some() {
+        let before = journal::row_image(&tx, "payees", into.id)?;
+        tx.execute("UPDATE payees SET category = ?1 WHERE id_payee = ?2", params![payee.category, into.id])?;
+        journal::record(&tx, id_operation, "payees", into.id, before)?;
+    }
+
+    let before = journal::row_image(&tx, "payees", payee.id)?;
+    tx.execute("DELETE FROM payees WHERE id_payee = ?1", params![payee.id])?;
+    journal::record(&tx, id_operation, "payees", payee.id, before)?;
+
+    tx.commit()?;
+
+    output::message(format!("Payee '{}' merged into '{}', {} transactions moved.", payee.name, into.name, n_transactions));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::structs::EXPENSE;
+
+    fn value(conn: &Connection, sql: &str) -> i64 {
+        conn.query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    fn text(conn: &Connection, sql: &str) -> Option<String> {
+        conn.query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn names_are_completed_from_known_payees() {
+        let conn = walletdb::memory_database();
+
+        payee_add("Netflix", None).unwrap();
+        payee_add("Netto", None).unwrap();
+        payee_add("Net", None).unwrap();
+
+        assert_eq!(payee_name(&conn, "netf").unwrap(), "Netflix");
+        assert_eq!(payee_name(&conn, "NET").unwrap(), "Net");
+        assert_eq!(payee_name(&conn, "Spotify ").unwrap(), "Spotify");
+
+        conn.execute("DELETE FROM payees WHERE name = 'Net'", []).unwrap();
+        assert_eq!(payee_name(&conn, "net").unwrap_err().kind(), "invalid-input");
+    }
+
+    #[test]
+    fn transactions_get_the_default_category() {
+        let conn = walletdb::memory_database();
+
+        walletdb::new_account("Bank", 100.0, false, None).unwrap();
+        payee_add("Market", None).unwrap();
+        payee_category("mark", Some("food")).unwrap();
+
+        walletdb::new_transaction("Groceries", 20.0, EXPENSE, true, false, None, Some("market")).unwrap();
+        walletdb::new_transaction("Tickets", 5.0, EXPENSE, true, false, None, Some("Cinema")).unwrap();
+
+        assert_eq!(text(&conn, "SELECT payee FROM transactions WHERE id_transaction = 1"), Some("Market".to_string()));
+        assert_eq!(text(&conn, "SELECT category FROM transactions WHERE id_transaction = 1"), Some("food".to_string()));
+        assert_eq!(text(&conn, "SELECT category FROM transactions WHERE id_transaction = 2"), None);
+        assert_eq!(value(&conn, "SELECT COUNT(*) FROM payees"), 2);
+    }
+
+    #[test]
+    fn renames_and_merges_move_the_transactions() {
+        let conn = walletdb::memory_database();
+
+        walletdb::new_account("Bank", 100.0, false, None).unwrap();
+        walletdb::new_transaction("Coffee", 3.0, EXPENSE, true, false, None, Some("Cafe")).unwrap();
+        walletdb::new_transaction("Tea", 2.0, EXPENSE, true, false, None, Some("Tea House")).unwrap();
+
+        payee_rename("cafe", "Corner Cafe").unwrap();
+        assert_eq!(text(&conn, "SELECT payee FROM transactions WHERE id_transaction = 1"), Some("Corner Cafe".to_string()));
+
+        // Renaming to the name of another payee is refused.
+        assert_eq!(payee_rename("corner", "tea house").unwrap_err().kind(), "invalid-input");
+        assert_eq!(value(&conn, "SELECT COUNT(*) FROM payees WHERE name = 'Corner Cafe'"), 1);
+
+        payee_category("tea", Some("drinks")).unwrap();
+        payee_merge("tea", "corner").unwrap();
+        assert_eq!(value(&conn, "SELECT COUNT(*) FROM transactions WHERE payee = 'Corner Cafe'"), 2);
+        assert_eq!(text(&conn, "SELECT category FROM payees"), Some("drinks".to_string()));
+
+        journal::undo(1).unwrap();
+        assert_eq!(value(&conn, "SELECT COUNT(*) FROM payees"), 2);
+        assert_eq!(value(&conn, "SELECT COUNT(*) FROM transactions WHERE payee = 'Tea House'"), 1);
+
+        journal::undo(2).unwrap();
+        assert_eq!(text(&conn, "SELECT payee FROM transactions WHERE id_transaction = 1"), Some("Cafe".to_string()));
+    }
+}
@@ -0,0 +1,157 @@
+use crate::error::{self, WalletError};
+use crate::export;
+use crate::ledger;
+use crate::output;
+use crate::structs::{Transaction, EXPENSE, TRANSFER};
+use crate::utils;
+use crate::walletdb;
+
+use std::collections::HashMap;
+use std::io::Write;
+
+// Wallet 'export qif' subcommand is defined below.
+// Each account is written in its own section, '!Type:CCard' for credit
+// accounts and '!Type:Bank' for the rest. Transfers are written in both
+// accounts with the other one as category, like Quicken does.
+pub fn export_qif(
+            opt_from: Option<&str>, opt_to: Option<&str>, opt_id_account: Option<&str>, opt_path: Option<&str>
+        ) -> error::Result<()> {
+    let conn = walletdb::open_database()?;
+
+    let (opt_from_date, opt_to_date) = utils::parse_date_range(opt_from, opt_to)?;
+
+    let opt_account_id = walletdb::account_filter(&conn, opt_id_account)?;
+
+    let accounts = ledger::accounts_before(&conn, opt_from_date)?;
+    let transactions = ledger::select_history(&conn, opt_account_id, opt_from_date, opt_to_date)?;
+    let transfer_accounts = ledger::transfer_accounts(&conn)?;
+
+    let names: HashMap<i64, &str> = accounts.iter()
+        .map(|(account, _)| (account.id, account.name.as_str()))
+        .collect();
+
+    let mut qif = String::new();
+    let mut n_accounts = 0;
+
+    for (account, opening) in accounts.iter().filter(|(account, _)| opt_account_id.is_none_or(|id| id == account.id)) {
+        let qif_type = if account.credit { "CCard" } else { "Bank" };
+        let account_transactions: Vec<&Transaction> = transactions.iter()
+            .filter(|t| t.id_account == account.id)
+            .collect();
+
+        qif.push_str(&format!("!Account\nN{}\nT{}\n^\n!Type:{}\n", qif_text(&account.name), qif_type, qif_type));
+        n_accounts += 1;
+
+        // The opening balance is a transfer of the account to itself.
+        if *opening != 0 {
+            let date = opt_from_date.unwrap_or_else(|| {
+                account_transactions.iter().map(|t| t.date).min().unwrap_or_else(utils::today_julian_day)
+            });
+
+            qif.push_str(&format!(
+                "D{}\nT{}\nC*\nPOpening Balance\nL[{}]\n^\n",
+                qif_date(date)?, qif_amount(*opening), qif_text(&account.name)
+            ));
+        }
+
+        for transaction in account_transactions {
+            let cents = (transaction.value * 100.0).round() as i64;
+
+            qif.push_str(&format!("D{}\n", qif_date(transaction.date)?));
+            qif.push_str(&format!("T{}\n", qif_amount(if transaction.t_type == EXPENSE { -cents } else { cents })));
+
+            if transaction.charged {
+                qif.push_str("C*\n");
+            }
+
+            if let Some(payee) = &transaction.payee {
+                qif.push_str(&format!("P{}\n", qif_text(payee)));
+            }
+
+            qif.push_str(&format!("M{}\n", qif_text(&transaction.message)));
+
+            let opt_transfer_account = transaction.id_transfer
+                .filter(|_| transaction.t_type == TRANSFER)
+                .and_then(|id| transfer_accounts.get(&id))
+                .and_then(|id_account| names.get(id_account));
+
+            match (opt_transfer_account, transaction.category.as_deref()) {
+                (Some(name), _) => qif.push_str(&format!("L[{}]\n", qif_text(name))),
+                (None, Some(category)) if !category.trim().is_empty() => qif.push_str(&format!("L{}\n", qif_text(category))),
+                _ => ()
+            }
+
+            qif.push_str("^\n");
+        }
+    }
+
+    let mut out = export::open_output(opt_path)?;
+
+    if let Err(e) = out.write_all(qif.as_bytes()) {
+        return Err(WalletError::Io("Unable to write the export!".to_string(), e));
+    }
+
+    if let Some(path) = opt_path {
+        output::message(format!("Exported {} transactions of {} accounts to '{}'.", transactions.len(), n_accounts, path));
+    }
+
+    Ok(())
+}
+
+// QIF dates are written as MM/DD/YYYY.
+fn qif_date(julian_date: i64) -> error::Result<String> {
+    let iso = utils::julian_day_to_iso(julian_date)?;
+
+    Ok(format!("{}/{}/{}", &iso[5..7], &iso[8..10], &iso[0..4]))
+}
+
+fn qif_amount(cents: i64) -> String {
+    let sign = if cents < 0 { "-" } else { "" };
+
+    format!("{}{}.{:02}", sign, cents.abs() / 100, cents.abs() % 100)
+}
+
+// Every field is a line of its own, a line break would start another field.
+fn qif_text(text: &str) -> String {
+    text.replace(['\r', '\n'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::structs::INCOMING;
+
+    use std::{env, fs, process};
+
+    #[test]
+    fn dates_amounts_and_texts() {
+        assert_eq!(qif_date(2461333).unwrap(), "10/19/2026");
+        assert_eq!(qif_amount(-1205), "-12.05");
+        assert_eq!(qif_amount(7), "0.07");
+        assert_eq!(qif_text("Rent\r\nOctober"), "Rent  October");
+    }
+
+    #[test]
+    fn accounts_are_written_in_sections() {
+        let conn = walletdb::memory_database();
+        let path = env::temp_dir().join(format!("wallet-export-{}.qif", process::id()));
+
+        walletdb::new_account("Bank", 100.0, false, None).unwrap();
+        walletdb::new_account("Visa", 0.0, true, None).unwrap();
+        walletdb::new_transaction("Coffee\nlarge", 3.5, EXPENSE, true, false, None, None).unwrap();
+        walletdb::new_transaction("Refund", 2.0, INCOMING, false, false, Some("2"), None).unwrap();
+        walletdb::account_transfer(10.0, "2", None).unwrap();
+        conn.execute("UPDATE transactions SET date = 2461333", []).unwrap();
+
+        export_qif(Some("2026-10-19"), None, None, Some(path.to_str().unwrap())).unwrap();
+        let qif = fs::read_to_string(&path).unwrap();
+
+        assert!(qif.starts_with("!Account\nNBank\nTBank\n^\n!Type:Bank\nD10/19/2026\nT100.00\nC*\nPOpening Balance\nL[Bank]\n^\n"));
+        assert!(qif.contains("D10/19/2026\nT-3.50\nC*\nMCoffee large\n^\n"));
+        assert!(qif.contains("!Account\nNVisa\nTCCard\n^\n!Type:CCard\nD10/19/2026\nT2.00\nMRefund\n^\n"));
+        assert!(qif.contains("T-10.00\nC*\nMTransfer to 'Visa'\nL[Visa]\n^\n"));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
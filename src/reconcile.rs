@@ -0,0 +1,160 @@
+use crate::error;
+use crate::journal;
+use crate::output;
+use crate::structs::{Transaction, ADJUSTMENT, EXPENSE};
+use crate::utils;
+use crate::walletdb;
+
+use rusqlite::params;
+
+// Wallet 'reconcile' subcommand is defined below.
+pub fn reconcile(
+            id: &str, statement_balance: f64, opt_date: Option<&str>, adjust: bool
+        ) -> error::Result<()> {
+    let mut conn = walletdb::open_database()?;
+
+    let mut account = walletdb::select_account(&conn, Some(id))?;
+
+    let julian_date = match opt_date {
+        Some(str_date) => utils::parse_date_arg(str_date)?,
+        None => utils::today_julian_day()
+    };
+
+    let pending: Vec<Transaction> = {
+        let mut stmt = conn.prepare(
+            "SELECT * FROM transactions
+             WHERE id_account = ?1 AND charged = 0 AND t_type = ?2 AND date <= ?3
+             ORDER BY date, id_transaction"
+        )?;
+
+        let rows = stmt.query_map(
+            params![account.id, EXPENSE, julian_date],
+            Transaction::from_row
+        )?;
+
+        rows.collect::<rusqlite::Result<_>>()?
+    };
+
+    let int_statement: i64 = (statement_balance * 100.0).round() as i64;
+    let mut int_cleared: i64 = (account.balance * 100.0).round() as i64;
+
+    output::message(format!("Reconciling '{}' against a statement balance of ${:.2}.", account.name, statement_balance));
+    output::message(format!("Current balance is ${:.2} and there are {} pending transactions.", account.balance, pending.len()));
+
+    let mut ticked: Vec<&Transaction> = Vec::new();
+
+    for transaction in &pending {
+        output::message(format!("\nDifference: ${:.2}", (int_statement - int_cleared) as f64 / 100.0));
+        output::message(transaction);
+
+        match utils::prompt("Does it appear on the statement? [y/N/q]: ")?.as_str() {
+            "y" | "yes" => {
+                int_cleared -= (transaction.value * 100.0).round() as i64;
+                ticked.push(transaction);
+            }
+            "q" | "quit" => break,
+            _ => ()
+        }
+    }
+
+    let int_difference = int_statement - int_cleared;
+    let difference = int_difference as f64 / 100.0;
+
+    output::message(format!("\n{} transactions ticked. Remaining difference: ${:.2}", ticked.len(), difference));
+
+    let post_adjustment = int_difference != 0 && (
+        adjust || utils::confirm(&format!("Post an adjustment of {:+.2} for the difference? [y/N]: ", difference))?
+    );
+
+    let tx = conn.transaction()?;
+    let id_operation = journal::begin(&tx)?;
+
+    for transaction in &ticked {
+        walletdb::charge_transaction(&tx, id_operation, transaction.id, None, julian_date)?;
+        account.charge(transaction);
+    }
+
+    if post_adjustment {
+        let adjustment = Transaction {
+            message: "Reconciliation adjustment".to_string(),
+            value: difference,
+            date: julian_date,
+            charged: true,
+            t_type: ADJUSTMENT,
+            id_account: account.id,
+            ..Transaction::empty()
+        };
+
+        walletdb::insert_transaction(&tx, id_operation, &adjustment)?;
+        account.apply(&adjustment);
+    }
+
+    walletdb::update_account(&tx, id_operation, &account)?;
+
+    let remaining: i64 = if post_adjustment { 0 } else { int_difference };
+
+    tx.execute(
+        "INSERT INTO reconciliations (date, statement_balance, difference, id_account)
+         VALUES (?1, ?2, ?3, ?4)",
+        params![julian_date, int_statement, remaining, account.id]
+    )?;
+    journal::record(&tx, id_operation, "reconciliations", tx.last_insert_rowid(), None)?;
+
+    tx.commit()?;
+
+    if remaining == 0 {
+        output::message(format!("Account '{}' reconciled!", account.name));
+    } else {
+        output::message(format!("Reconciliation point saved with a difference of ${:.2}.", difference));
+    }
+    output::message(&account);
+    output::record(vec![
+        ("account", account.id.into()),
+        ("date", utils::julian_day_to_iso(julian_date)?.into()),
+        ("statement_balance", output::money(int_statement)),
+        ("ticked", ticked.iter().map(|transaction| transaction.id).collect::<Vec<i64>>().into()),
+        ("difference", output::money(int_difference)),
+        ("adjusted", post_adjustment.into()),
+        ("reconciled", (remaining == 0).into())
+    ]);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rusqlite::Connection;
+
+    fn value(conn: &Connection, sql: &str) -> i64 {
+        conn.query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn adjust_posts_the_difference() {
+        let conn = walletdb::memory_database();
+
+        walletdb::new_account("Bank", 100.0, false, None).unwrap();
+        reconcile("1", 90.5, Some("2026-10-01"), true).unwrap();
+
+        assert_eq!(value(&conn, "SELECT balance FROM accounts"), 9050);
+        assert_eq!(value(&conn, "SELECT value FROM transactions WHERE t_type = 2"), -950);
+        assert_eq!(value(&conn, "SELECT difference FROM reconciliations"), 0);
+
+        journal::undo(1).unwrap();
+        assert_eq!(value(&conn, "SELECT balance FROM accounts"), 10000);
+        assert_eq!(value(&conn, "SELECT COUNT(*) FROM reconciliations"), 0);
+    }
+
+    #[test]
+    fn matching_statements_need_no_adjustment() {
+        let conn = walletdb::memory_database();
+
+        walletdb::new_account("Bank", 100.0, false, None).unwrap();
+        reconcile("1", 100.0, None, false).unwrap();
+
+        assert_eq!(value(&conn, "SELECT COUNT(*) FROM transactions"), 0);
+        assert_eq!(value(&conn, "SELECT statement_balance FROM reconciliations"), 10000);
+    }
+}
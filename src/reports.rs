@@ -0,0 +1,728 @@
+use crate::error;
+use crate::output;
+use crate::structs::{MsiPurchase, EXPENSE, INCOMING};
+use crate::utils;
+use crate::walletdb;
+
+use std::collections::HashMap;
+
+use chrono::{Datelike, Months, NaiveDate};
+use rusqlite::{params, Connection};
+
+const BAR_WIDTH: i64 = 30;
+// Transactions as they were at the end of the day ?6, the pending ones are
+// only included when ?5 is true.
+const AS_OF_CONDITION: &str = "date <= ?6 AND (?5 OR (charged = 1 AND COALESCE(charge_date, date) <= ?6))";
+const SPARK_LEVELS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+// Wallet 'report' subcommands are defined below.
+
+// Money spent with and received from every payee, the payees that got more
+// money first. Transactions without payee are added up in the last line.
+pub fn report_payees(
+            opt_from: Option<&str>, opt_to: Option<&str>, opt_id_account: Option<&str>
+        ) -> error::Result<()> {
+    let conn = walletdb::open_database()?;
+
+    let (opt_from_date, opt_to_date) = utils::parse_date_range(opt_from, opt_to)?;
+
+    let opt_account_id = walletdb::account_filter(&conn, opt_id_account)?;
+
+    let mut stmt = conn.prepare(
+        "SELECT COALESCE(p.name, NULLIF(TRIM(t.payee), '')) AS name, COUNT(*),
+            SUM(CASE t.t_type WHEN ?1 THEN t.value ELSE 0 END),
+            SUM(CASE t.t_type WHEN ?2 THEN t.value ELSE 0 END)
+         FROM transactions t LEFT JOIN payees p ON p.name = t.payee
+         WHERE t.t_type IN (?1, ?2) AND t.date >= ?3 AND t.date <= ?4
+            AND (?5 IS NULL OR t.id_account = ?5)
+         GROUP BY name COLLATE NOCASE
+         ORDER BY name IS NULL, SUM(CASE t.t_type WHEN ?1 THEN t.value ELSE 0 END) DESC, name"
+    )?;
+
+    let rows = stmt.query_map(
+        params![
+            EXPENSE, INCOMING, opt_from_date.unwrap_or(i64::MIN), opt_to_date.unwrap_or(i64::MAX),
+            opt_account_id
+        ],
+        |row| Ok((row.get::<_, Option<String>>(0)?, row.get::<_, i64>(1)?, row.get::<_, i64>(2)?, row.get::<_, i64>(3)?))
+    )?;
+
+    let rows: Vec<(Option<String>, i64, i64, i64)> = rows.collect::<rusqlite::Result<_>>()?;
+
+    if rows.is_empty() {
+        output::message("There are no expenses or incomings in this period!");
+        return Ok(());
+    }
+
+    output::message(format!("{:<30} {:>6} {:>15} {:>15}", "Payee", "Count", "Spent", "Received"));
+
+    let (mut total_spent, mut total_received) = (0, 0);
+
+    for (opt_name, count, spent, received) in &rows {
+        output::message(format!(
+            "{:<30} {:>6} {:>15.2} {:>15.2}",
+            opt_name.as_deref().unwrap_or("(no payee)"), count, *spent as f64 / 100.0, *received as f64 / 100.0
+        ));
+        output::record(vec![
+            ("payee", opt_name.as_deref().into()),
+            ("count", (*count).into()),
+            ("spent", output::money(*spent)),
+            ("received", output::money(*received))
+        ]);
+
+        total_spent += spent;
+        total_received += received;
+    }
+
+    output::message(format!(
+        "{:<30} {:>6} {:>15.2} {:>15.2}",
+        "Total", rows.iter().map(|(_, count, _, _)| count).sum::<i64>(),
+        total_spent as f64 / 100.0, total_received as f64 / 100.0
+    ));
+
+    Ok(())
+}
+
+// Income, expenses, net savings and savings rate of every month of the year
+// with transactions. Pending transactions are only added up when asked.
+pub fn report_monthly(
+            opt_year: Option<i32>, opt_id_account: Option<&str>, pending: bool, opt_as_of: Option<&str>
+        ) -> error::Result<()> {
+    let as_of = utils::parse_as_of(opt_as_of)?;
+
+    let conn = walletdb::open_database()?;
+
+    let opt_account_id = walletdb::account_filter(&conn, opt_id_account)?;
+
+    let year = match opt_year {
+        Some(year) => year,
+        None => utils::julian_day_to_naive(as_of)?.year()
+    };
+
+    let rows = monthly_totals(&conn, year, opt_account_id, pending, as_of)?;
+
+    if rows.is_empty() {
+        output::message(format!("There are no expenses or incomings in {}!", year));
+        return Ok(());
+    }
+
+    output::message(format!("{:<10} {:>15} {:>15} {:>15} {:>8}", "Month", "Income", "Expenses", "Net", "Rate"));
+
+    for (month, income, expenses) in &rows {
+        output::message(monthly_line(month, *income, *expenses));
+        output::record(vec![
+            ("month", month.as_str().into()),
+            ("income", output::money(*income)),
+            ("expenses", output::money(*expenses)),
+            ("net", output::money(income - expenses))
+        ]);
+    }
+
+    let total_income = rows.iter().map(|(_, income, _)| income).sum();
+    let total_expenses = rows.iter().map(|(_, _, expenses)| expenses).sum();
+
+    output::message(monthly_line("Total", total_income, total_expenses));
+
+    Ok(())
+}
+
+// Income and expenses of every month of the year with transactions.
+pub fn monthly_totals(
+            conn: &Connection, year: i32, opt_account_id: Option<i64>, pending: bool, as_of: i64
+        ) -> rusqlite::Result<Vec<(String, i64, i64)>> {
+    // SQLite reads integers as Julian days, so dates can be formatted directly.
+    let mut stmt = conn.prepare(&format!(
+        "SELECT strftime('%Y-%m', date) AS month,
+            SUM(CASE t_type WHEN ?1 THEN value ELSE 0 END),
+            SUM(CASE t_type WHEN ?2 THEN value ELSE 0 END)
+         FROM transactions
+         WHERE t_type IN (?1, ?2) AND strftime('%Y', date) = ?3
+            AND (?4 IS NULL OR id_account = ?4) AND {}
+         GROUP BY month ORDER BY month",
+        AS_OF_CONDITION
+    ))?;
+
+    let rows = stmt.query_map(
+        params![INCOMING, EXPENSE, format!("{:04}", year), opt_account_id, pending, as_of],
+        |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?, row.get::<_, i64>(2)?))
+    )?;
+
+    rows.collect()
+}
+
+// The savings rate is the part of the income that was not spent.
+fn monthly_line(label: &str, income: i64, expenses: i64) -> String {
+    let net = income - expenses;
+    let rate = if income > 0 {
+        format!("{:.1}%", net as f64 * 100.0 / income as f64)
+    } else {
+        "-".to_string()
+    };
+
+    format!(
+        "{:<10} {:>15.2} {:>15.2} {:>15.2} {:>8}",
+        label, income as f64 / 100.0, expenses as f64 / 100.0, net as f64 / 100.0, rate
+    )
+}
+
+// Expenses of every category in a period, subcategories are added up in their
+// top category and listed below it. Each line is compared with the period of
+// the same length just before.
+pub fn report_categories(
+            opt_month: Option<&str>, opt_from: Option<&str>, opt_to: Option<&str>,
+            opt_id_account: Option<&str>, pending: bool, opt_as_of: Option<&str>
+        ) -> error::Result<()> {
+    let as_of = utils::parse_as_of(opt_as_of)?;
+
+    let ((from, to), (previous_from, previous_to)) = report_periods(opt_month, opt_from, opt_to, as_of)?;
+
+    let conn = walletdb::open_database()?;
+
+    let opt_account_id = walletdb::account_filter(&conn, opt_id_account)?;
+
+    let current = category_spending(&conn, (from, to), opt_account_id, pending, as_of)?;
+    let previous = category_spending(&conn, (previous_from, previous_to), opt_account_id, pending, as_of)?;
+
+    output::message(format!(
+        "Expenses from {} to {}, compared with {} to {}.",
+        utils::julian_day_to_iso(from)?, utils::julian_day_to_iso(to)?,
+        utils::julian_day_to_iso(previous_from)?, utils::julian_day_to_iso(previous_to)?
+    ));
+
+    let total: i64 = current.iter().map(|(_, cents)| cents).sum();
+
+    if total == 0 {
+        output::message("There are no expenses in this period!");
+        return Ok(());
+    }
+
+    let rolled_up = roll_up(&current);
+    let previous_rolled_up = roll_up(&previous);
+    let max = rolled_up.iter().map(|(_, cents, _)| *cents).max().unwrap_or(1).max(1);
+
+    output::message(format!("{:<26} {:>12} {:>7} {:>12} {:>8}", "Category", "Spent", "%", "Previous", "Change"));
+
+    for (top, cents, children) in &rolled_up {
+        let previous_top = previous_rolled_up.iter().find(|(name, _, _)| name == top);
+
+        let previous_cents = previous_top.map(|(_, cents, _)| *cents).unwrap_or(0);
+
+        output::message(category_line(top, *cents, total, previous_cents, max));
+        output::record(vec![
+            ("category", top.as_str().into()),
+            ("spent", output::money(*cents)),
+            ("previous", output::money(previous_cents))
+        ]);
+
+        // A category without subcategories is not repeated below itself.
+        if children.len() == 1 && children[0].0.is_empty() {
+            continue;
+        }
+
+        for (child, child_cents) in children {
+            let previous_child = previous_top
+                .and_then(|(_, _, previous_children)| previous_children.iter().find(|(name, _)| name == child))
+                .map(|(_, cents)| *cents)
+                .unwrap_or(0);
+            let label = if child.is_empty() { "(itself)" } else { child };
+
+            output::message(category_line(&format!("  {}", label), *child_cents, total, previous_child, max));
+
+            if !child.is_empty() {
+                output::record(vec![
+                    ("category", format!("{}:{}", top, child).into()),
+                    ("spent", output::money(*child_cents)),
+                    ("previous", output::money(previous_child))
+                ]);
+            }
+        }
+    }
+
+    let previous_total: i64 = previous.iter().map(|(_, cents)| cents).sum();
+    output::message(category_line("Total", total, total, previous_total, 0));
+
+    Ok(())
+}
+
+// The month given, the range given or the month of the --as-of day, together
+// with the period just before.
+fn report_periods(
+            opt_month: Option<&str>, opt_from: Option<&str>, opt_to: Option<&str>, as_of: i64
+        ) -> error::Result<((i64, i64), (i64, i64))> {
+    if opt_month.is_some() || (opt_from.is_none() && opt_to.is_none()) {
+        let first_day = match opt_month {
+            Some(str_month) => utils::parse_month(str_month)?,
+            None => utils::julian_day_to_naive(as_of)?
+        };
+
+        return match month_periods(first_day.year(), first_day.month()) {
+            Some(periods) => Ok(periods),
+            None => error::invalid(format!("Invalid month '{}'! Please use the format YYYY-MM...", first_day.format("%Y-%m")))
+        };
+    }
+
+    let (opt_from_date, opt_to_date) = utils::parse_date_range(opt_from, opt_to)?;
+
+    let from = match opt_from_date {
+        Some(from) => from,
+        None => return error::invalid("The first day of the period is needed, please add --from YYYY-MM-DD...")
+    };
+    let to = opt_to_date.unwrap_or(as_of);
+
+    if to < from {
+        return error::invalid("The period ends before it starts!");
+    }
+
+    let days = to - from + 1;
+
+    Ok(((from, to), (from - days, from - 1)))
+}
+
+pub fn month_periods(year: i32, month: u32) -> Option<((i64, i64), (i64, i64))> {
+    let first_day = NaiveDate::from_ymd_opt(year, month, 1)?;
+    let next_month = if month == 12 {
+        NaiveDate::from_ymd_opt(year + 1, 1, 1)?
+    } else {
+        NaiveDate::from_ymd_opt(year, month + 1, 1)?
+    };
+    let previous_month = if month == 1 {
+        NaiveDate::from_ymd_opt(year - 1, 12, 1)?
+    } else {
+        NaiveDate::from_ymd_opt(year, month - 1, 1)?
+    };
+
+    let from = utils::naive_to_julian_day(first_day);
+
+    Some((
+        (from, utils::naive_to_julian_day(next_month) - 1),
+        (utils::naive_to_julian_day(previous_month), from - 1)
+    ))
+}
+
+// Expenses of each category, written in lowercase without spaces around ':'.
+pub fn category_spending(
+            conn: &Connection, (from, to): (i64, i64), opt_account_id: Option<i64>, pending: bool, as_of: i64
+        ) -> rusqlite::Result<Vec<(String, i64)>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT category, SUM(value) FROM transactions
+         WHERE t_type = ?1 AND date >= ?2 AND date <= ?3
+            AND (?4 IS NULL OR id_account = ?4) AND {}
+         GROUP BY category",
+        AS_OF_CONDITION
+    ))?;
+
+    let rows = stmt.query_map(
+        params![EXPENSE, from, to, opt_account_id, pending, as_of],
+        |row| Ok((row.get::<_, Option<String>>(0)?, row.get::<_, i64>(1)?))
+    )?;
+
+    let mut spending: HashMap<String, i64> = HashMap::new();
+
+    for row in rows {
+        let (opt_category, cents) = row?;
+
+        let category = opt_category
+            .map(|category| normalize_category(&category))
+            .filter(|category| !category.is_empty())
+            .unwrap_or_else(|| "(uncategorized)".to_string());
+
+        *spending.entry(category).or_insert(0) += cents;
+    }
+
+    Ok(spending.into_iter().collect())
+}
+
+pub fn normalize_category(category: &str) -> String {
+    category.split(':').map(|part| part.trim().to_lowercase()).collect::<Vec<_>>().join(":")
+}
+
+// Top categories with their total and the spending of each subcategory, the
+// biggest first. Expenses of the top category itself have an empty name.
+pub type RolledUp = Vec<(String, i64, Vec<(String, i64)>)>;
+
+pub fn roll_up(spending: &[(String, i64)]) -> RolledUp {
+    let mut tops: HashMap<&str, Vec<(String, i64)>> = HashMap::new();
+
+    for (category, cents) in spending {
+        let (top, child) = category.split_once(':').unwrap_or((category, ""));
+        tops.entry(top).or_default().push((child.to_string(), *cents));
+    }
+
+    let mut rolled_up: RolledUp = tops.into_iter()
+        .map(|(top, mut children)| {
+            children.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+            (top.to_string(), children.iter().map(|(_, cents)| cents).sum(), children)
+        })
+        .collect();
+
+    rolled_up.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    rolled_up
+}
+
+// Bars are relative to the biggest category, no bar is drawn when max is 0.
+fn category_line(label: &str, cents: i64, total: i64, previous: i64, max: i64) -> String {
+    let change = if previous > 0 {
+        format!("{:+.1}%", (cents - previous) as f64 * 100.0 / previous as f64)
+    } else {
+        "new".to_string()
+    };
+
+    let bar = if max > 0 { "█".repeat(((cents * BAR_WIDTH + max / 2) / max) as usize) } else { String::new() };
+
+    format!(
+        "{:<26} {:>12.2} {:>6.1}% {:>12.2} {:>8}  {}",
+        label, cents as f64 / 100.0, cents as f64 * 100.0 / total as f64, previous as f64 / 100.0, change, bar
+    ).trim_end().to_string()
+}
+
+// Balances of the accounts at the end of every year, or month, since the first
+// transaction, rebuilt from the history. Credit accounts are debt when their
+// balance is negative, and the MSI installments not billed yet are taken out
+// of every period.
+pub fn report_networth(
+            monthly: bool, sparkline: bool, opt_from: Option<&str>, opt_as_of: Option<&str>
+        ) -> error::Result<()> {
+    let (opt_from_date, _) = utils::parse_date_range(opt_from, None)?;
+
+    let as_of = utils::parse_as_of(opt_as_of)?;
+
+    let conn = walletdb::open_database()?;
+
+    let first_date: i64 = match opt_from_date {
+        Some(from) => from,
+        // The history starts with the first transaction or the first account opened.
+        None => conn.query_row(
+            "SELECT COALESCE(MIN(day), ?1) FROM (SELECT date AS day FROM transactions UNION ALL SELECT created FROM accounts)",
+            params![as_of], |row| row.get(0)
+        )?
+    };
+
+    let periods = period_ends(first_date.min(as_of), as_of, monthly)?;
+
+    let msi_debt = msi_debt(&conn, &periods)?;
+    let BalanceHistory { names, credit, balances: history } = balance_history(&conn, &periods)?;
+
+    if names.is_empty() {
+        output::message("There are no accounts! Try 'wallet new account --help'.");
+        return Ok(());
+    }
+
+    output::message(format!(
+        "Net worth at the end of each {}, the last one up to {}.",
+        if monthly { "month" } else { "year" }, utils::julian_day_to_iso(as_of)?
+    ));
+    output::message(format!("{:<10} {:>15} {:>15} {:>12} {:>15} {:>13}", "Period", "Assets", "Credit", "MSI", "Net worth", "Change"));
+
+    let mut totals: Vec<i64> = Vec::new();
+
+    for (index, (label, _)) in periods.iter().enumerate() {
+        let sum = |is_credit: bool| -> i64 {
+            history.iter().zip(&credit)
+                .filter(|(_, credit)| **credit == is_credit)
+                .map(|(balances, _)| balances[index])
+                .sum()
+        };
+
+        let (assets, credit_balance) = (sum(false), sum(true));
+        let msi = -msi_debt[index];
+        let total = assets + credit_balance + msi;
+
+        let change = match totals.last() {
+            Some(previous) => format!("{:+.2}", (total - previous) as f64 / 100.0),
+            None => String::new()
+        };
+
+        let line = format!(
+            "{:<10} {:>15.2} {:>15.2} {:>12.2} {:>15.2} {:>13}",
+            label, assets as f64 / 100.0, credit_balance as f64 / 100.0, msi as f64 / 100.0,
+            total as f64 / 100.0, change
+        );
+        output::message(line.trim_end());
+        output::record(vec![
+            ("period", label.as_str().into()),
+            ("assets", output::money(assets)),
+            ("credit", output::money(credit_balance)),
+            ("msi", output::money(msi)),
+            ("net_worth", output::money(total))
+        ]);
+
+        totals.push(total);
+    }
+
+    if sparkline {
+        output::message("");
+
+        for (name, balances) in names.iter().zip(&history) {
+            output::message(sparkline_line(name, balances));
+        }
+        output::message(sparkline_line("Net worth", &totals));
+    }
+
+    Ok(())
+}
+
+// Price of the MSI installments not billed yet at the end of every period, in
+// cents. A purchase is made the month before its first installment and, as in
+// the forecast, each installment is paid the day it is billed. Purchases
+// without billing date are only known from today on.
+pub fn msi_debt(conn: &Connection, periods: &[(String, i64)]) -> error::Result<Vec<i64>> {
+    let mut stmt = conn.prepare("SELECT * FROM msi_purchases WHERE installments > 0")?;
+    let purchases: Vec<MsiPurchase> = stmt.query_map([], MsiPurchase::from_row)?
+        .collect::<rusqlite::Result<_>>()?;
+
+    let today = utils::today_julian_day();
+
+    let mut debts = Vec::new();
+
+    for (_, julian_date) in periods {
+        let mut debt = 0;
+
+        for msi in &purchases {
+            debt += match msi.billing_date {
+                Some(billing_date) => msi_owed(msi, billing_date, *julian_date)?,
+                None if *julian_date >= today => msi.remaining_installments().iter().sum(),
+                None => 0
+            };
+        }
+
+        debts.push(debt);
+    }
+
+    Ok(debts)
+}
+
+// The billing date is the day the installment after the paid ones is billed.
+fn msi_owed(msi: &MsiPurchase, billing_date: i64, julian_date: i64) -> error::Result<i64> {
+    if julian_date < shift_months(billing_date, -msi.months_paid - 1)? {
+        return Ok(0);
+    }
+
+    let mut owed = 0;
+
+    for n in 0..msi.installments {
+        if shift_months(billing_date, n - msi.months_paid)? > julian_date {
+            owed += msi.installment(n);
+        }
+    }
+
+    Ok(owed)
+}
+
+// Days past the end of the month are moved to its last day, as in the forecast.
+fn shift_months(julian_date: i64, months: i64) -> error::Result<i64> {
+    let date = utils::julian_day_to_naive(julian_date)?;
+    let shifted = if months < 0 {
+        date.checked_sub_months(Months::new(months.unsigned_abs() as u32))
+    } else {
+        date.checked_add_months(Months::new(months as u32))
+    };
+
+    Ok(shifted.map(utils::naive_to_julian_day).unwrap_or(julian_date))
+}
+
+// Balance of every account at the end of every period, in cents.
+pub struct BalanceHistory {
+    pub names: Vec<String>,
+    pub credit: Vec<bool>,
+    pub balances: Vec<Vec<i64>>
+}
+
+pub fn balance_history(conn: &Connection, periods: &[(String, i64)]) -> rusqlite::Result<BalanceHistory> {
+    let mut history = BalanceHistory { names: Vec::new(), credit: Vec::new(), balances: Vec::new() };
+
+    for (_, julian_date) in periods {
+        let accounts = walletdb::select_accounts_as_of(conn, *julian_date)?;
+
+        if history.names.is_empty() {
+            history.names = accounts.iter().map(|account| account.name.clone()).collect();
+            history.credit = accounts.iter().map(|account| account.credit).collect();
+            history.balances = vec![Vec::new(); accounts.len()];
+        }
+
+        for (index, account) in accounts.iter().enumerate() {
+            history.balances[index].push((account.balance * 100.0).round() as i64);
+        }
+    }
+
+    Ok(history)
+}
+
+// Label and last day of every month or year from the first date to the last
+// one, the last period ends on that day.
+pub fn period_ends(first_date: i64, last_date: i64, monthly: bool) -> error::Result<Vec<(String, i64)>> {
+    let first = utils::julian_day_to_naive(first_date)?;
+    let (mut year, mut month) = (first.year(), if monthly { first.month() } else { 12 });
+    let mut periods = Vec::new();
+
+    loop {
+        let next = if month == 12 {
+            NaiveDate::from_ymd_opt(year + 1, 1, 1)
+        } else {
+            NaiveDate::from_ymd_opt(year, month + 1, 1)
+        };
+        let last_day = utils::naive_to_julian_day(next.unwrap_or(NaiveDate::MAX)) - 1;
+        let label = if monthly { format!("{:04}-{:02}", year, month) } else { format!("{:04}", year) };
+
+        if last_day >= last_date {
+            periods.push((label, last_date));
+            return Ok(periods);
+        }
+
+        periods.push((label, last_day));
+
+        if monthly && month < 12 {
+            month += 1;
+        } else {
+            year += 1;
+            month = if monthly { 1 } else { 12 };
+        }
+    }
+}
+
+fn sparkline_line(name: &str, values: &[i64]) -> String {
+    let min = values.iter().copied().min().unwrap_or(0);
+    let max = values.iter().copied().max().unwrap_or(0);
+
+    let spark: String = values.iter()
+        .map(|value| {
+            let level = if max > min { (value - min) * 7 / (max - min) } else { 0 };
+            SPARK_LEVELS[level as usize]
+        })
+        .collect();
+
+    format!("{:<20} {} {:>15.2}", name, spark, values.last().copied().unwrap_or(0) as f64 / 100.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn savings_rate_of_a_month() {
+        assert_eq!(
+            monthly_line("2026-10", 200000, 150000),
+            format!("{:<10} {:>15} {:>15} {:>15} {:>8}", "2026-10", "2000.00", "1500.00", "500.00", "25.0%")
+        );
+        assert!(monthly_line("2026-11", 0, 1000).ends_with(" -"));
+    }
+
+    #[test]
+    fn reports_read_the_transactions() {
+        let conn = walletdb::memory_database();
+
+        walletdb::new_account("Bank", 100.0, false, None).unwrap();
+        walletdb::new_transaction("Coffee", 3.0, EXPENSE, true, false, None, Some("Cafe")).unwrap();
+        walletdb::new_transaction("Salary", 50.0, INCOMING, true, false, None, None).unwrap();
+        conn.execute("UPDATE transactions SET date = 2461333", []).unwrap();
+
+        let month: String = conn.query_row(
+            "SELECT strftime('%Y-%m', date) FROM transactions LIMIT 1", [], |row| row.get(0)
+        ).unwrap();
+        assert_eq!(month, "2026-10");
+
+        report_monthly(Some(2026), None, false, Some("2026-12-31")).unwrap();
+        report_payees(Some("2026-10-01"), None, Some("1")).unwrap();
+    }
+
+    #[test]
+    fn periods_of_a_month() {
+        assert_eq!(month_periods(2026, 10), Some(((2461315, 2461345), (2461285, 2461314))));
+        assert_eq!(month_periods(2026, 1).unwrap().1.0, utils::parse_date("2025-12-01").unwrap());
+        assert_eq!(report_periods(None, Some("2026-10-11"), Some("2026-10-20"), 2461333).unwrap(), ((2461325, 2461334), (2461315, 2461324)));
+        assert_eq!(report_periods(None, Some("2026-10-20"), Some("2026-10-11"), 2461333).unwrap_err().kind(), "invalid-input");
+        assert_eq!(report_periods(Some("2026-13"), None, None, 2461333).unwrap_err().kind(), "invalid-input");
+    }
+
+    #[test]
+    fn subcategories_are_added_up_in_their_top_category() {
+        let conn = walletdb::memory_database();
+
+        walletdb::new_account("Bank", 100.0, false, None).unwrap();
+        for (message, value, category) in [("Bread", 2.0, "Food : Bakery"), ("Apples", 3.0, "food:fruit"), ("Milk", 1.0, "food")] {
+            walletdb::new_transaction(message, value, EXPENSE, true, false, None, None).unwrap();
+            conn.execute("UPDATE transactions SET category = ?1 WHERE message = ?2", params![category, message]).unwrap();
+        }
+        walletdb::new_transaction("Bus", 5.0, EXPENSE, true, false, None, None).unwrap();
+
+        let today = utils::today_julian_day();
+        let mut spending = category_spending(&conn, (today, today), None, false, today).unwrap();
+        spending.sort();
+        assert_eq!(spending, vec![
+            ("(uncategorized)".to_string(), 500), ("food".to_string(), 100),
+            ("food:bakery".to_string(), 200), ("food:fruit".to_string(), 300)
+        ]);
+
+        assert_eq!(roll_up(&spending), vec![
+            ("food".to_string(), 600, vec![("fruit".to_string(), 300), ("bakery".to_string(), 200), (String::new(), 100)]),
+            ("(uncategorized)".to_string(), 500, vec![(String::new(), 500)])
+        ]);
+    }
+
+    #[test]
+    fn expenses_count_as_they_were_on_the_day() {
+        let conn = walletdb::memory_database();
+
+        walletdb::new_account("Bank", 100.0, false, None).unwrap();
+        walletdb::new_transaction("Rent", 50.0, EXPENSE, true, false, None, None).unwrap();
+        walletdb::new_transaction("Fees", 5.0, EXPENSE, false, false, None, None).unwrap();
+        conn.execute("UPDATE transactions SET date = 2461330, charge_date = 2461332 WHERE charged = 1", []).unwrap();
+        conn.execute("UPDATE transactions SET date = 2461330 WHERE charged = 0", []).unwrap();
+
+        let spent = |pending: bool, as_of: i64| -> i64 {
+            category_spending(&conn, (2461315, 2461345), None, pending, as_of).unwrap().iter().map(|(_, cents)| cents).sum()
+        };
+
+        assert_eq!(spent(false, 2461331), 0);
+        assert_eq!(spent(true, 2461331), 5500);
+        assert_eq!(spent(false, 2461332), 5000);
+        assert_eq!(spent(true, 2461329), 0);
+    }
+
+    #[test]
+    fn periods_end_today() {
+        let today = utils::parse_date("2026-10-19").unwrap();
+
+        assert_eq!(period_ends(utils::parse_date("2026-08-20").unwrap(), today, true).unwrap(), vec![
+            ("2026-08".to_string(), utils::parse_date("2026-08-31").unwrap()),
+            ("2026-09".to_string(), utils::parse_date("2026-09-30").unwrap()),
+            ("2026-10".to_string(), today)
+        ]);
+        assert_eq!(period_ends(utils::parse_date("2025-02-01").unwrap(), today, false).unwrap(), vec![
+            ("2025".to_string(), utils::parse_date("2025-12-31").unwrap()),
+            ("2026".to_string(), today)
+        ]);
+    }
+
+    #[test]
+    fn sparklines_go_from_the_lowest_to_the_highest_value() {
+        assert_eq!(sparkline_line("Bank", &[0, 350, 700]), format!("{:<20} ▁▄█ {:>15}", "Bank", "7.00"));
+        assert_eq!(sparkline_line("Cash", &[500, 500]), format!("{:<20} ▁▁ {:>15}", "Cash", "5.00"));
+    }
+
+    #[test]
+    fn bars_are_relative_to_the_biggest_category() {
+        let line = category_line("food", 300, 600, 200, 600);
+
+        assert!(line.ends_with(&format!("+50.0%  {}", "█".repeat(BAR_WIDTH as usize / 2))));
+        assert!(category_line("Total", 600, 600, 0, 0).ends_with("new"));
+    }
+
+    #[test]
+    fn msi_installments_are_owed_until_they_are_billed() {
+        let day = |date: &str| utils::naive_to_julian_day(NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap());
+        let msi = MsiPurchase {
+            id: 1, name: "Laptop".to_string(), price: 300.0, installments: 3, months_paid: 1, id_account: 1,
+            billing_date: Some(day("2024-03-15"))
+        };
+        let billing_date = msi.billing_date.unwrap();
+
+        assert_eq!(shift_months(day("2024-03-31"), -1).unwrap(), day("2024-02-29"));
+
+        // Bought the month before the first installment, on 2024-01-15.
+        assert_eq!(msi_owed(&msi, billing_date, day("2024-01-14")).unwrap(), 0);
+        assert_eq!(msi_owed(&msi, billing_date, day("2024-01-31")).unwrap(), 30000);
+        assert_eq!(msi_owed(&msi, billing_date, day("2024-02-29")).unwrap(), 20000);
+        assert_eq!(msi_owed(&msi, billing_date, day("2024-03-31")).unwrap(), 10000);
+        assert_eq!(msi_owed(&msi, billing_date, day("2024-04-30")).unwrap(), 0);
+    }
+}
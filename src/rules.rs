@@ -0,0 +1,400 @@
+use crate::error;
+use crate::journal;
+use crate::output;
+use crate::payees;
+use crate::structs::{Rule, Transaction, EXPENSE, INCOMING};
+use crate::utils;
+use crate::walletdb;
+
+use regex::Regex;
+use rusqlite::{params, Connection};
+use serde_json::Value;
+
+// Fills the category, payee and tags of the transaction from the first rule
+// that matches it, values already set are kept. Returns the rule that fired.
+pub fn apply_rules(rules: &[Rule], transaction: &mut Transaction) -> Option<i64> {
+    let rule = first_match(rules, transaction)?;
+
+    if transaction.category.is_none() {
+        transaction.category = rule.category.clone();
+    }
+    if transaction.payee.is_none() {
+        transaction.payee = rule.payee.clone();
+    }
+    if transaction.tags.is_none() {
+        transaction.tags = rule.tags.clone();
+    }
+
+    Some(rule.id)
+}
+
+fn first_match<'a>(rules: &'a [Rule], transaction: &Transaction) -> Option<&'a Rule> {
+    rules.iter().find(|rule| {
+        rule.matches(&transaction.message, transaction.value, Some(transaction.id_account))
+    })
+}
+
+pub fn select_rules(conn: &Connection) -> rusqlite::Result<Vec<Rule>> {
+    let mut stmt = conn.prepare("SELECT * FROM rules ORDER BY id_rule")?;
+    let rules = stmt.query_map([], Rule::from_row)?;
+
+    rules.collect()
+}
+
+// Ranges look like '100..500', '..500' or '100..'.
+fn parse_amount_range(text: &str) -> Option<(Option<f64>, Option<f64>)> {
+    let (str_min, str_max) = text.split_once("..")?;
+
+    let bound = |text: &str| -> Option<Option<f64>> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Some(None);
+        }
+
+        text.parse::<f64>().ok().map(Some)
+    };
+
+    Some((bound(str_min)?, bound(str_max)?))
+}
+
+// Tags are stored as a comma separated list without spaces or '#'.
+fn normalize_tags(text: &str) -> Option<String> {
+    let tags: Vec<&str> = text.split(',')
+        .map(|tag| tag.trim().trim_start_matches('#'))
+        .filter(|tag| !tag.is_empty())
+        .collect();
+
+    if tags.is_empty() { None } else { Some(tags.join(",")) }
+}
+
+
+// Wallet 'rule' subcommands are defined below.
+pub fn rule_add(
+            pattern: &str, is_regex: bool, opt_category: Option<&str>, opt_payee: Option<&str>,
+            opt_tags: Option<&str>, opt_id_account: Option<&str>, opt_amount_range: Option<&str>
+        ) -> error::Result<()> {
+    let opt_tags = opt_tags.and_then(normalize_tags);
+
+    if opt_category.is_none() && opt_payee.is_none() && opt_tags.is_none() {
+        return error::invalid("The rule needs a category, a payee or tags to set!");
+    }
+
+    if is_regex {
+        if let Err(e) = Regex::new(pattern) {
+            return error::invalid(format!("Invalid regular expression '{}'! Error: {}", pattern, e));
+        }
+    }
+
+    let (opt_min, opt_max) = match opt_amount_range {
+        Some(str_range) => match parse_amount_range(str_range) {
+            Some(range) => range,
+            None => return error::invalid(format!("Invalid amount range '{}'! Please use the format MIN..MAX...", str_range))
+        },
+        None => (None, None)
+    };
+
+    let to_cents = |value: Option<f64>| value.map(|v| (v.abs() * 100.0).round() as i64);
+
+    let mut conn = walletdb::open_database()?;
+
+    let opt_account_id = walletdb::account_filter(&conn, opt_id_account)?;
+
+    let tx = conn.transaction()?;
+    let id_operation = journal::begin(&tx)?;
+
+    tx.execute(
+        "INSERT INTO rules (
+            pattern, is_regex, category, payee, tags, id_account, min_amount, max_amount
+        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            pattern, if is_regex { 1 } else { 0 }, opt_category, opt_payee, &opt_tags,
+            opt_account_id, to_cents(opt_min), to_cents(opt_max)
+        ]
+    )?;
+
+    let id_rule = tx.last_insert_rowid();
+    journal::record(&tx, id_operation, "rules", id_rule, None)?;
+
+    tx.commit()?;
+
+    output::message(format!("Successfully created rule {}!", id_rule));
+
+    Ok(())
+}
+
+pub fn rule_list() -> error::Result<()> {
+    let conn = walletdb::open_database()?;
+
+    let rules = select_rules(&conn)?;
+
+    for rule in &rules {
+        output::message(rule);
+        output::record(rule_fields(rule));
+    }
+
+    if rules.is_empty() {
+        output::message("There are no rules! Try 'wallet rule add --help'.");
+    }
+
+    Ok(())
+}
+
+fn rule_fields(rule: &Rule) -> Vec<(&'static str, Value)> {
+    vec![
+        ("id", rule.id.into()),
+        ("pattern", rule.pattern.as_str().into()),
+        ("regex", rule.regex.is_some().into()),
+        ("category", rule.category.as_deref().into()),
+        ("payee", rule.payee.as_deref().into()),
+        ("tags", rule.tags.as_deref().into()),
+        ("account", rule.id_account.into()),
+        ("min_amount", rule.min_amount.into()),
+        ("max_amount", rule.max_amount.into())
+    ]
+}
+
+pub fn rule_delete(id: &str) -> error::Result<()> {
+    let mut conn = walletdb::open_database()?;
+
+    let id_rule: i64 = match id.parse() {
+        Ok(id_rule) => id_rule,
+        Err(_) => return error::invalid(format!("Invalid rule ID '{}'!", id))
+    };
+
+    let tx = conn.transaction()?;
+    let id_operation = journal::begin(&tx)?;
+
+    let before = journal::row_image(&tx, "rules", id_rule)?;
+
+    if before.is_none() {
+        return error::not_found(format!("Rule {} not found!", id_rule));
+    }
+
+    tx.execute("DELETE FROM rules WHERE id_rule = ?1", params![id_rule])?;
+    journal::record(&tx, id_operation, "rules", id_rule, before)?;
+
+    tx.commit()?;
+
+    output::message(format!("Successfully deleted rule {}!", id_rule));
+
+    Ok(())
+}
+
+// Shows every rule matching the text, the first one is the one that fires.
+pub fn rule_test(text: &str, opt_value: Option<f64>, opt_id_account: Option<&str>) -> error::Result<()> {
+    let conn = walletdb::open_database()?;
+
+    let opt_account_id = walletdb::account_filter(&conn, opt_id_account)?;
+
+    // Without a value only rules without amount range can match.
+    let rules: Vec<Rule> = select_rules(&conn)?.into_iter()
+        .filter(|rule| opt_value.is_some() || (rule.min_amount.is_none() && rule.max_amount.is_none()))
+        .filter(|rule| rule.matches(text, opt_value.unwrap_or(0.0), opt_account_id))
+        .collect();
+
+    // The first rule fires, the others are listed after it.
+    for (index, rule) in rules.iter().enumerate() {
+        let mut fields = rule_fields(rule);
+        fields.push(("fires", (index == 0).into()));
+
+        output::record(fields);
+    }
+
+    match rules.first() {
+        Some(rule) => {
+            output::message(format!("Rule that fires:\n{}", rule));
+
+            if rules.len() > 1 {
+                output::message("Also matching, ignored because an older rule fires first:");
+                for rule in &rules[1..] {
+                    output::message(rule);
+                }
+            }
+        }
+        None => output::message(format!("No rule matches '{}'.", text))
+    }
+
+    Ok(())
+}
+
+// Re-runs the rules over the expenses and incomings dated since the given day.
+// Fields set by the rule that fires are overwritten, the others are kept.
+pub fn rule_apply(str_since: &str, opt_id_account: Option<&str>) -> error::Result<()> {
+    let since = utils::parse_date_arg(str_since)?;
+
+    let mut conn = walletdb::open_database()?;
+
+    let opt_account_id = walletdb::account_filter(&conn, opt_id_account)?;
+
+    let rules = select_rules(&conn)?;
+
+    if rules.is_empty() {
+        output::message("There are no rules! Try 'wallet rule add --help'.");
+        return Ok(());
+    }
+
+    let tx = conn.transaction()?;
+    let id_operation = journal::begin(&tx)?;
+
+    let transactions: Vec<Transaction> = {
+        let mut stmt = tx.prepare(
+            "SELECT * FROM transactions
+             WHERE date >= ?1 AND t_type IN (?2, ?3) AND (?4 IS NULL OR id_account = ?4)
+             ORDER BY date, id_transaction"
+        )?;
+
+        let rows = stmt.query_map(
+            params![since, EXPENSE, INCOMING, opt_account_id],
+            Transaction::from_row
+        )?;
+
+        rows.collect::<rusqlite::Result<_>>()?
+    };
+
+    let mut n_updated = 0;
+
+    for transaction in &transactions {
+        let rule = match first_match(&rules, transaction) {
+            Some(rule) => rule,
+            None => continue
+        };
+
+        let mut updated = Transaction {
+            category: rule.category.clone().or_else(|| transaction.category.clone()),
+            payee: rule.payee.clone().or_else(|| transaction.payee.clone()),
+            tags: rule.tags.clone().or_else(|| transaction.tags.clone()),
+            ..transaction.clone()
+        };
+        payees::register_payee(&tx, id_operation, &mut updated)?;
+
+        if updated.category == transaction.category && updated.payee == transaction.payee
+                && updated.tags == transaction.tags {
+            continue;
+        }
+
+        let before = journal::row_image(&tx, "transactions", transaction.id)?;
+
+        tx.execute(
+            "UPDATE transactions SET category = ?1, payee = ?2, tags = ?3 WHERE id_transaction = ?4",
+            params![&updated.category, &updated.payee, &updated.tags, transaction.id]
+        )?;
+
+        journal::record(&tx, id_operation, "transactions", transaction.id, before)?;
+
+        output::message(format!("Rule {} applied to transaction {} '{}'", rule.id, transaction.id, transaction.message));
+        n_updated += 1;
+    }
+
+    tx.commit()?;
+
+    output::message(format!("{} of {} transactions updated.", n_updated, transactions.len()));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(id: i64, pattern: &str, category: &str) -> Rule {
+        Rule {
+            id,
+            pattern: pattern.to_string(),
+            regex: None,
+            category: Some(category.to_string()),
+            payee: None,
+            tags: None,
+            id_account: None,
+            min_amount: None,
+            max_amount: None
+        }
+    }
+
+    fn categories(conn: &Connection) -> Vec<Option<String>> {
+        conn.prepare("SELECT category FROM transactions ORDER BY id_transaction").unwrap()
+            .query_map([], |row| row.get(0)).unwrap()
+            .collect::<rusqlite::Result<_>>().unwrap()
+    }
+
+    #[test]
+    fn amount_ranges() {
+        assert_eq!(parse_amount_range("100..500"), Some((Some(100.0), Some(500.0))));
+        assert_eq!(parse_amount_range("..500"), Some((None, Some(500.0))));
+        assert_eq!(parse_amount_range("100.."), Some((Some(100.0), None)));
+        assert_eq!(parse_amount_range("100"), None);
+        assert_eq!(parse_amount_range("a..b"), None);
+    }
+
+    #[test]
+    fn tags_are_normalized() {
+        assert_eq!(normalize_tags("#trip, food ,,#"), Some("trip,food".to_string()));
+        assert_eq!(normalize_tags(" , #"), None);
+    }
+
+    #[test]
+    fn rules_match_text_account_and_amount() {
+        let substring = rule(1, "uber", "Transport");
+        assert!(substring.matches("UBER *TRIP", -12.0, Some(1)));
+        assert!(!substring.matches("Ubuntu", 12.0, Some(1)));
+
+        let regex = Rule { regex: Regex::new("^OXXO \\d+$").ok(), ..rule(2, "^OXXO \\d+$", "Snacks") };
+        assert!(regex.matches("OXXO 123", 1.0, None));
+        assert!(!regex.matches("oxxo 123", 1.0, None));
+
+        let limited = Rule { id_account: Some(2), min_amount: Some(10.0), max_amount: Some(20.0), ..rule(3, "", "Food") };
+        assert!(limited.matches("Lunch", -15.0, Some(2)));
+        assert!(!limited.matches("Lunch", 25.0, Some(2)));
+        assert!(!limited.matches("Lunch", 15.0, Some(1)));
+        assert!(!limited.matches("Lunch", 15.0, None));
+    }
+
+    #[test]
+    fn the_first_rule_fills_the_empty_fields() {
+        let rules = [
+            Rule { payee: Some("Uber".to_string()), ..rule(1, "uber", "Transport") },
+            rule(2, "uber eats", "Food")
+        ];
+
+        let mut transaction = Transaction {
+            message: "UBER EATS".to_string(),
+            category: Some("Dinner".to_string()),
+            ..Transaction::empty()
+        };
+
+        assert_eq!(apply_rules(&rules, &mut transaction), Some(1));
+        assert_eq!(transaction.category.as_deref(), Some("Dinner"));
+        assert_eq!(transaction.payee.as_deref(), Some("Uber"));
+
+        let mut transaction = Transaction { message: "Rent".to_string(), ..Transaction::empty() };
+        assert_eq!(apply_rules(&rules, &mut transaction), None);
+        assert_eq!(transaction.category, None);
+    }
+
+    #[test]
+    fn rules_round_trip() {
+        let conn = walletdb::memory_database();
+
+        walletdb::new_account("Bank", 100.0, false, None).unwrap();
+        walletdb::new_transaction("Netflix", 10.0, EXPENSE, true, false, None, None).unwrap();
+
+        rule_add("netflix", false, Some("Streaming"), None, Some("#tv"), None, None).unwrap();
+        assert_eq!(rule_add("[", true, Some("Broken"), None, None, None, None).unwrap_err().kind(), "invalid-input");
+        assert_eq!(rule_add("coffee", false, None, None, None, None, None).unwrap_err().kind(), "invalid-input");
+        assert_eq!(select_rules(&conn).unwrap().len(), 1);
+
+        // New transactions are categorised, older ones only by 'rule apply'.
+        walletdb::new_transaction("NETFLIX.COM", 10.0, EXPENSE, true, false, None, None).unwrap();
+        assert_eq!(categories(&conn), vec![None, Some("Streaming".to_string())]);
+
+        rule_apply("2000-01-01", None).unwrap();
+        assert_eq!(categories(&conn), vec![Some("Streaming".to_string()), Some("Streaming".to_string())]);
+
+        journal::undo(1).unwrap();
+        assert_eq!(categories(&conn), vec![None, Some("Streaming".to_string())]);
+
+        rule_delete("1").unwrap();
+        assert!(select_rules(&conn).unwrap().is_empty());
+        journal::undo(1).unwrap();
+        assert_eq!(select_rules(&conn).unwrap()[0].tags.as_deref(), Some("tv"));
+    }
+}
@@ -0,0 +1,375 @@
+use crate::error::{self, WalletError};
+use crate::export;
+use crate::output;
+use crate::reports;
+use crate::structs::{Account, Transaction, ADJUSTMENT, EXPENSE};
+use crate::utils;
+use crate::walletdb;
+
+use std::io::{self, Write};
+
+use chrono::Datelike;
+use rusqlite::{params, Connection};
+
+struct Statement {
+    account: Account,
+    month: String,
+    from: i64,
+    to: i64,
+    opening: i64,
+    // Charged in the month, in the order they were charged.
+    charged: Vec<Transaction>,
+    // Made in the month and not charged at its end.
+    pending: Vec<Transaction>
+}
+
+impl Statement {
+    fn credits(&self) -> i64 {
+        self.charged.iter().map(signed_cents).filter(|cents| *cents > 0).sum()
+    }
+
+    fn debits(&self) -> i64 {
+        -self.charged.iter().map(signed_cents).filter(|cents| *cents < 0).sum::<i64>()
+    }
+
+    fn closing(&self) -> i64 {
+        self.opening + self.credits() - self.debits()
+    }
+
+    // Charged transactions with the balance after each one.
+    fn running(&self) -> Vec<(&Transaction, i64)> {
+        let mut balance = self.opening;
+
+        self.charged.iter()
+            .map(|transaction| {
+                balance += signed_cents(transaction);
+                (transaction, balance)
+            })
+            .collect()
+    }
+}
+
+fn signed_cents(transaction: &Transaction) -> i64 {
+    let cents = (transaction.value * 100.0).round() as i64;
+
+    if transaction.t_type == EXPENSE { -cents } else { cents }
+}
+
+fn money(cents: i64) -> String {
+    format!("{:.2}", cents as f64 / 100.0)
+}
+
+// Debit and credit columns of a transaction, one of them is empty.
+fn debit_credit(transaction: &Transaction) -> (String, String) {
+    let cents = signed_cents(transaction);
+
+    if cents < 0 { (money(-cents), String::new()) } else { (String::new(), money(cents)) }
+}
+
+fn description(transaction: &Transaction) -> String {
+    match &transaction.payee {
+        Some(payee) => format!("{} ({})", transaction.message, payee),
+        None => transaction.message.clone()
+    }
+}
+
+fn select_transactions(conn: &Connection, sql: &str, statement: &Statement) -> rusqlite::Result<Vec<Transaction>> {
+    let mut stmt = conn.prepare(sql)?;
+    let transactions = stmt.query_map(
+        params![statement.account.id, statement.from, statement.to],
+        Transaction::from_row
+    )?;
+
+    transactions.collect()
+}
+
+// Wallet 'statement' subcommand is defined below.
+// Like the statement of a bank, transactions are listed on the day they were
+// charged and the ones still pending at the end of the month are apart.
+pub fn statement(id: &str, str_month: &str, format: &str, opt_path: Option<&str>) -> error::Result<()> {
+    let first_day = utils::parse_month(str_month)?;
+    let ((from, to), _) = reports::month_periods(first_day.year(), first_day.month())
+        .ok_or_else(|| WalletError::InvalidInput(format!("Invalid month '{}'! Please use the format YYYY-MM...", str_month)))?;
+
+    let conn = walletdb::open_database()?;
+
+    let account = walletdb::select_account(&conn, Some(id))?;
+
+    let statement = read_statement(&conn, account, first_day.format("%Y-%m").to_string(), from, to)?;
+
+    let mut out = export::open_output(opt_path)?;
+
+    match format {
+        "csv" => write_csv(out, &statement)?,
+        "html" => out.write_all(statement_html(&statement)?.as_bytes()).map_err(write_error)?,
+        _ => out.write_all(statement_text(&statement)?.as_bytes()).map_err(write_error)?
+    }
+
+    if let Some(path) = opt_path {
+        output::message(format!("Statement of '{}' for {} written to '{}'.", statement.account.name, statement.month, path));
+    }
+
+    Ok(())
+}
+
+// The opening balance of an account opened in the month is not part of the
+// balance before it, it is listed as a credit on the day the account opened.
+fn read_statement(
+            conn: &Connection, account: Account, month: String, from: i64, to: i64
+        ) -> rusqlite::Result<Statement> {
+    let opening = walletdb::select_accounts_as_of(conn, from - 1)?.into_iter()
+        .find(|before| before.id == account.id)
+        .map(|before| (before.balance * 100.0).round() as i64)
+        .unwrap_or(0);
+
+    let (opt_opened, opening_balance) = walletdb::account_opening(conn, account.id)?;
+
+    let mut statement = Statement {
+        account,
+        month,
+        from,
+        to,
+        opening,
+        charged: Vec::new(),
+        pending: Vec::new()
+    };
+
+    statement.charged = select_transactions(
+        conn,
+        "SELECT * FROM transactions
+         WHERE id_account = ?1 AND charged = 1 AND COALESCE(charge_date, date) BETWEEN ?2 AND ?3
+         ORDER BY COALESCE(charge_date, date), id_transaction",
+        &statement
+    )?;
+
+    statement.pending = select_transactions(
+        conn,
+        "SELECT * FROM transactions
+         WHERE id_account = ?1 AND date BETWEEN ?2 AND ?3 AND NOT (charged = 1 AND COALESCE(charge_date, date) <= ?3)
+         ORDER BY date, id_transaction",
+        &statement
+    )?;
+
+    if let Some(opened) = opt_opened.filter(|opened| *opened >= from && *opened <= to && opening_balance != 0) {
+        let position = statement.charged.iter()
+            .position(|transaction| transaction.charge_date.unwrap_or(transaction.date) >= opened)
+            .unwrap_or(statement.charged.len());
+
+        statement.charged.insert(position, Transaction {
+            message: "Opening".to_string(),
+            value: opening_balance as f64 / 100.0,
+            date: opened,
+            charged: true,
+            t_type: ADJUSTMENT,
+            id_account: statement.account.id,
+            charge_date: Some(opened),
+            ..Transaction::empty()
+        });
+    }
+
+    Ok(statement)
+}
+
+fn statement_text(statement: &Statement) -> error::Result<String> {
+    let mut text = format!(
+        "Statement of account '{}' for {}, from {} to {}.\n\n",
+        statement.account.name, statement.month,
+        utils::julian_day_to_iso(statement.from)?, utils::julian_day_to_iso(statement.to)?
+    );
+
+    text.push_str(&format!("{:<10}  {:<40} {:>12} {:>12} {:>14}\n", "Date", "Description", "Debit", "Credit", "Balance"));
+    text.push_str(&format!("{:<10}  {:<40} {:>12} {:>12} {:>14}\n", "", "Opening balance", "", "", money(statement.opening)));
+
+    for (transaction, balance) in statement.running() {
+        let (debit, credit) = debit_credit(transaction);
+
+        text.push_str(&format!(
+            "{:<10}  {:<40} {:>12} {:>12} {:>14}\n",
+            utils::julian_day_to_iso(transaction.charge_date.unwrap_or(transaction.date))?,
+            description(transaction), debit, credit, money(balance)
+        ));
+    }
+
+    text.push_str(&format!(
+        "{:<10}  {:<40} {:>12} {:>12}\n", "", "Totals", money(statement.debits()), money(statement.credits())
+    ));
+    text.push_str(&format!("{:<10}  {:<40} {:>12} {:>12} {:>14}\n", "", "Closing balance", "", "", money(statement.closing())));
+
+    if !statement.pending.is_empty() {
+        text.push_str("\nPending at the end of the month:\n");
+
+        for transaction in &statement.pending {
+            let (debit, credit) = debit_credit(transaction);
+
+            let line = format!(
+                "{:<10}  {:<40} {:>12} {:>12}",
+                utils::julian_day_to_iso(transaction.date)?, description(transaction), debit, credit
+            );
+
+            text.push_str(line.trim_end());
+            text.push('\n');
+        }
+    }
+
+    Ok(text)
+}
+
+// One row per line of the statement, the kind tells the opening, charged,
+// pending and closing rows apart.
+fn write_csv(out: Box<dyn Write>, statement: &Statement) -> error::Result<()> {
+    let mut writer = csv::Writer::from_writer(out);
+
+    writer.write_record(["kind", "date", "description", "payee", "category", "debit", "credit", "balance"]).map_err(csv_error)?;
+
+    let from = utils::julian_day_to_iso(statement.from)?;
+    writer.write_record(["opening", &from, "Opening balance", "", "", "", "", &money(statement.opening)]).map_err(csv_error)?;
+
+    for (transaction, balance) in statement.running() {
+        let (debit, credit) = debit_credit(transaction);
+
+        writer.write_record([
+            "charged",
+            &utils::julian_day_to_iso(transaction.charge_date.unwrap_or(transaction.date))?,
+            &transaction.message,
+            transaction.payee.as_deref().unwrap_or(""),
+            transaction.category.as_deref().unwrap_or(""),
+            &debit,
+            &credit,
+            &money(balance)
+        ]).map_err(csv_error)?;
+    }
+
+    for transaction in &statement.pending {
+        let (debit, credit) = debit_credit(transaction);
+
+        writer.write_record([
+            "pending",
+            &utils::julian_day_to_iso(transaction.date)?,
+            &transaction.message,
+            transaction.payee.as_deref().unwrap_or(""),
+            transaction.category.as_deref().unwrap_or(""),
+            &debit,
+            &credit,
+            ""
+        ]).map_err(csv_error)?;
+    }
+
+    writer.write_record([
+        "closing", &utils::julian_day_to_iso(statement.to)?, "Closing balance", "", "",
+        &money(statement.debits()), &money(statement.credits()), &money(statement.closing())
+    ]).map_err(csv_error)?;
+
+    writer.flush().map_err(write_error)
+}
+
+fn csv_error(e: csv::Error) -> WalletError {
+    write_error(io::Error::from(e))
+}
+
+fn write_error(e: io::Error) -> WalletError {
+    WalletError::Io("Unable to write the statement!".to_string(), e)
+}
+
+// A whole page that can be opened in a browser or printed.
+fn statement_html(statement: &Statement) -> error::Result<String> {
+    let title = format!("Statement of {} for {}", utils::html_escape(&statement.account.name), statement.month);
+
+    let row = |date: &str, text: &str, debit: &str, credit: &str, balance: &str| format!(
+        "<tr><td>{}</td><td>{}</td><td class=\"n\">{}</td><td class=\"n\">{}</td><td class=\"n\">{}</td></tr>\n",
+        date, utils::html_escape(text), debit, credit, balance
+    );
+
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{0}</title>\n\
+         <style>body {{ font-family: sans-serif; }} table {{ border-collapse: collapse; }} \
+         th, td {{ padding: 4px 10px; border-bottom: 1px solid #ddd; }} .n {{ text-align: right; }} \
+         .total td {{ font-weight: bold; }}</style>\n</head>\n<body>\n<h1>{0}</h1>\n<p>From {1} to {2}.</p>\n",
+        title, utils::julian_day_to_iso(statement.from)?, utils::julian_day_to_iso(statement.to)?
+    );
+
+    html.push_str("<table>\n<tr><th>Date</th><th>Description</th><th>Debit</th><th>Credit</th><th>Balance</th></tr>\n");
+    html.push_str(&row("", "Opening balance", "", "", &money(statement.opening)));
+
+    for (transaction, balance) in statement.running() {
+        let (debit, credit) = debit_credit(transaction);
+        let date = utils::julian_day_to_iso(transaction.charge_date.unwrap_or(transaction.date))?;
+
+        html.push_str(&row(&date, &description(transaction), &debit, &credit, &money(balance)));
+    }
+
+    html.push_str(&row("", "Totals", &money(statement.debits()), &money(statement.credits()), ""));
+    html.push_str(&row("", "Closing balance", "", "", &money(statement.closing())).replacen("<tr>", "<tr class=\"total\">", 1));
+    html.push_str("</table>\n");
+
+    if !statement.pending.is_empty() {
+        html.push_str("<h2>Pending at the end of the month</h2>\n<table>\n");
+        html.push_str("<tr><th>Date</th><th>Description</th><th>Debit</th><th>Credit</th><th></th></tr>\n");
+
+        for transaction in &statement.pending {
+            let (debit, credit) = debit_credit(transaction);
+
+            html.push_str(&row(&utils::julian_day_to_iso(transaction.date)?, &description(transaction), &debit, &credit, ""));
+        }
+
+        html.push_str("</table>\n");
+    }
+
+    html.push_str("</body>\n</html>\n");
+
+    Ok(html)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(str_date: &str) -> i64 {
+        utils::parse_date(str_date).unwrap()
+    }
+
+    fn month_statement(conn: &Connection, month: &str) -> Statement {
+        let first_day = utils::parse_month(month).unwrap();
+        let ((from, to), _) = reports::month_periods(first_day.year(), first_day.month()).unwrap();
+        let account = walletdb::select_account(conn, Some("1")).unwrap();
+
+        read_statement(conn, account, month.to_string(), from, to).unwrap()
+    }
+
+    fn balance_as_of(conn: &Connection, julian_date: i64) -> i64 {
+        let account = walletdb::select_accounts_as_of(conn, julian_date).unwrap().remove(0);
+
+        (account.balance * 100.0).round() as i64
+    }
+
+    #[test]
+    fn accounts_opened_in_the_month_start_with_an_opening_row() {
+        let conn = walletdb::memory_database();
+
+        walletdb::new_account("Bank", 100.0, false, None).unwrap();
+        walletdb::new_transaction("Books", 30.0, EXPENSE, true, false, None, None).unwrap();
+        walletdb::new_transaction("Rent", 50.0, EXPENSE, false, false, None, None).unwrap();
+        conn.execute("UPDATE accounts SET created = ?1", params![date("2026-09-10")]).unwrap();
+        conn.execute("UPDATE transactions SET date = ?1, charge_date = ?2 WHERE charged = 1", params![date("2026-09-12"), date("2026-10-02")]).unwrap();
+        conn.execute("UPDATE transactions SET date = ?1 WHERE charged = 0", params![date("2026-09-20")]).unwrap();
+
+        let september = month_statement(&conn, "2026-09");
+        let rows: Vec<(&str, i64)> = september.running().iter().map(|(t, balance)| (t.message.as_str(), *balance)).collect();
+
+        assert_eq!(september.opening, 0);
+        assert_eq!(rows, vec![("Opening", 10000)]);
+        assert_eq!((september.credits(), september.debits()), (10000, 0));
+        assert_eq!(september.closing(), balance_as_of(&conn, date("2026-09-30")));
+        assert_eq!(september.pending.len(), 2);
+        assert!(statement_text(&september).unwrap().contains(&format!("{:<10}  {:<40} {:>12} {:>12} {:>14}\n", "2026-09-10", "Opening", "", "100.00", "100.00")));
+
+        let october = month_statement(&conn, "2026-10");
+        assert_eq!(october.opening, 10000);
+        assert_eq!((october.credits(), october.debits()), (0, 3000));
+        assert_eq!(october.closing(), balance_as_of(&conn, date("2026-10-31")));
+        assert_eq!(october.pending.len(), 0);
+
+        let august = month_statement(&conn, "2026-08");
+        assert!(august.charged.is_empty());
+        assert_eq!(august.closing(), 0);
+    }
+}
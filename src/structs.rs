@@ -1,15 +1,34 @@
 use std::fmt::{Display, Formatter, Result};
 
+use crate::utils;
+
+use regex::Regex;
 use rusqlite::Row;
 use time::Date;
 
-#[derive(Debug)]
+// Values of 'transactions.t_type'.
+pub const EXPENSE: i64 = 0;
+pub const INCOMING: i64 = 1;
+pub const ADJUSTMENT: i64 = 2;
+// Transfers are two rows with signed values linked by 'id_transfer'.
+pub const TRANSFER: i64 = 3;
+
+// Listings still show the rows whose stored day is not a valid date.
+fn display_date(julian_date: i64) -> String {
+    utils::julian_day_to_iso(julian_date).unwrap_or_else(|_| format!("day {}", julian_date))
+}
+
+#[derive(Debug, Clone)]
 pub struct Account {
     pub id: i64,
     pub name: String,
     pub balance: f64,
     pub available: f64,
-    pub default: bool
+    pub default: bool,
+    pub credit: bool,
+    pub currency: Option<String>,
+    // Forecasts warn when the available balance would drop below it.
+    pub minimum: f64
 }
 
 impl Display for Account {
@@ -29,46 +48,80 @@ impl Account {
             name: "".to_string(),
             balance: 0.0,
             available: 0.0,
-            default: false
+            default: false,
+            credit: false,
+            currency: None,
+            minimum: 0.0
         }
     }
 
-    pub fn exists(&self) -> bool {
-        self.id != -1
+    // Updates the balances with a new transaction of this account.
+    pub fn apply(&mut self, transaction: &Transaction) {
+        let signed_value = if transaction.t_type == EXPENSE { -transaction.value } else { transaction.value };
+
+        self.available += signed_value;
+        if transaction.charged {
+            self.balance += signed_value;
+        }
+    }
+
+    // Updates the balances when a pending transaction gets charged.
+    pub fn charge(&mut self, transaction: &Transaction) {
+        let signed_value = if transaction.t_type == EXPENSE { -transaction.value } else { transaction.value };
+
+        self.balance += signed_value;
     }
 
-    pub fn from_row(row: &Row<'_>) -> Account {
-        let id: i64 = row.get(0).unwrap();
-        let name: String = row.get(1).unwrap();
+    pub fn from_row(row: &Row<'_>) -> rusqlite::Result<Account> {
+        let id: i64 = row.get(0)?;
+        let name: String = row.get(1)?;
 
-        let int_balance: i64 = row.get(2).unwrap();
+        let int_balance: i64 = row.get(2)?;
         let balance: f64 = int_balance as f64 / 100.0;
 
-        let int_available: i64 = row.get(3).unwrap();
+        let int_available: i64 = row.get(3)?;
         let available: f64 = int_available as f64 / 100.0;
 
-        let is_default: i64 = row.get(4).unwrap();
+        let is_default: i64 = row.get(4)?;
         let default = is_default != 0;
 
-        Account {
-            id: id,
-            name: name,
-            balance: balance,
-            available: available,
-            default: default
-        }
+        let is_credit: i64 = row.get(7)?;
+        let credit = is_credit != 0;
+
+        let currency: Option<String> = row.get(8)?;
+
+        let int_minimum: Option<i64> = row.get(10)?;
+        let minimum: f64 = int_minimum.unwrap_or(0) as f64 / 100.0;
+
+        Ok(Account {
+            id,
+            name,
+            balance,
+            available,
+            default,
+            credit,
+            currency,
+            minimum
+        })
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Transaction {
     pub id: i64,
     pub message: String,
     pub value: f64,
-    pub date: String,
+    pub date: i64,
     pub charged: bool,
     pub t_type: i64,
-    pub id_account: i64
+    pub id_account: i64,
+    pub fitid: Option<String>,
+    pub category: Option<String>,
+    pub payee: Option<String>,
+    pub tags: Option<String>,
+    pub id_transfer: Option<i64>,
+    // Day the transaction was charged to the account, None while pending.
+    pub charge_date: Option<i64>
 }
 
 impl Display for Transaction {
@@ -76,7 +129,33 @@ impl Display for Transaction {
         write!(f, "{:<6}.- ", self.id)?;
         write!(f, "{}${:>15.2} ", if self.charged { " " } else { "*" }, self.value)?;
         writeln!(f, "'{}'", self.message)?;
-        write!(f, "         {} {}", self.date, if self.t_type == 0 { ">>>" } else { "<<<" })
+
+        let str_date: String = match Date::from_julian_day(self.date as i32) {
+            Ok(date) => format!("{}-{}-{}", date.year(), date.month(), date.day()),
+            Err(_) => display_date(self.date)
+        };
+        let arrow = match self.t_type {
+            EXPENSE => ">>>",
+            INCOMING => "<<<",
+            ADJUSTMENT => "~~~ adjustment",
+            TRANSFER => "=== transfer",
+            _ => "???"
+        };
+        write!(f, "         {} {}", str_date, arrow)?;
+
+        if let Some(category) = &self.category {
+            write!(f, " [{}]", category)?;
+        }
+        if let Some(payee) = &self.payee {
+            write!(f, " @{}", payee)?;
+        }
+        if let Some(tags) = &self.tags {
+            for tag in tags.split(',') {
+                write!(f, " #{}", tag)?;
+            }
+        }
+
+        Ok(())
     }
 }
 
@@ -86,38 +165,392 @@ impl Transaction {
             id: -1,
             message: "".to_string(),
             value: 0.0,
-            date: "".to_string(),
+            date: 0,
             charged: false,
             t_type: -1,
-            id_account: -1
+            id_account: -1,
+            fitid: None,
+            category: None,
+            payee: None,
+            tags: None,
+            id_transfer: None,
+            charge_date: None
         }
     }
 
-    pub fn from_row(row: &Row<'_>) -> Transaction {
-        let id: i64 = row.get(0).unwrap();
-        let message: String = row.get(1).unwrap();
+    pub fn from_row(row: &Row<'_>) -> rusqlite::Result<Transaction> {
+        let id: i64 = row.get(0)?;
+        let message: String = row.get(1)?;
 
-        let int_value: i64 = row.get(2).unwrap();
+        let int_value: i64 = row.get(2)?;
         let value: f64 = int_value as f64 / 100.0;
 
-        let julian_date: i32 = row.get(3).unwrap();
-        let date = Date::from_julian_day(julian_date).unwrap();
-        let str_date: String = format!("{}-{}-{}", date.year(), date.month(), date.day());
+        let date: i64 = row.get(3)?;
 
-        let int_charged: i64 = row.get(4).unwrap();
+        let int_charged: i64 = row.get(4)?;
         let charged = int_charged == 1;
 
-        let t_type: i64 = row.get(5).unwrap();
-        let id_account: i64 = row.get(6).unwrap();
+        let t_type: i64 = row.get(5)?;
+        let id_account: i64 = row.get(6)?;
+        let fitid: Option<String> = row.get(7)?;
+        let category: Option<String> = row.get(8)?;
+        let payee: Option<String> = row.get(9)?;
+        let tags: Option<String> = row.get(10)?;
+        let id_transfer: Option<i64> = row.get(11)?;
+        let charge_date: Option<i64> = row.get(12)?;
 
-        Transaction {
-            id: id,
-            message: message,
-            value: value,
-            date: str_date,
-            charged: charged,
-            t_type: t_type,
-            id_account: id_account
+        Ok(Transaction {
+            id,
+            message,
+            value,
+            date,
+            charged,
+            t_type,
+            id_account,
+            fitid,
+            category,
+            payee,
+            tags,
+            id_transfer,
+            charge_date
+        })
+    }
+}
+#[derive(Debug)]
+pub struct CsvProfile {
+    pub name: String,
+    pub delimiter: char,
+    pub skip_rows: usize,
+    pub date_column: usize,
+    pub date_format: String,
+    pub description_column: usize,
+    pub amount_column: Option<usize>,
+    pub debit_column: Option<usize>,
+    pub credit_column: Option<usize>,
+    pub decimal_separator: char
+}
+
+impl Display for CsvProfile {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let column = |opt_column: Option<usize>| match opt_column {
+            Some(column) => column.to_string(),
+            None => "-".to_string()
+        };
+
+        write!(f, "{:<20} ", self.name)?;
+        write!(f, "date {} '{}', description {}, ", self.date_column, self.date_format, self.description_column)?;
+        write!(f, "amount {}, debit {}, credit {}, ", column(self.amount_column), column(self.debit_column), column(self.credit_column))?;
+        write!(f, "delimiter '{}', decimal '{}', skip {}", self.delimiter.escape_default(), self.decimal_separator, self.skip_rows)
+    }
+}
+
+impl CsvProfile {
+    pub fn from_row(row: &Row<'_>) -> rusqlite::Result<CsvProfile> {
+        let first_char = |text: String, default: char| text.chars().next().unwrap_or(default);
+        let column = |value: Option<i64>| value.map(|v| v as usize);
+
+        Ok(CsvProfile {
+            name: row.get(1)?,
+            delimiter: first_char(row.get(2)?, ','),
+            skip_rows: row.get::<_, i64>(3)? as usize,
+            date_column: row.get::<_, i64>(4)? as usize,
+            date_format: row.get(5)?,
+            description_column: row.get::<_, i64>(6)? as usize,
+            amount_column: column(row.get(7)?),
+            debit_column: column(row.get(8)?),
+            credit_column: column(row.get(9)?),
+            decimal_separator: first_char(row.get(10)?, '.')
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct Rule {
+    pub id: i64,
+    pub pattern: String,
+    pub regex: Option<Regex>,
+    pub category: Option<String>,
+    pub payee: Option<String>,
+    pub tags: Option<String>,
+    pub id_account: Option<i64>,
+    pub min_amount: Option<f64>,
+    pub max_amount: Option<f64>
+}
+
+impl Display for Rule {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{:<4}.- ", self.id)?;
+        if self.regex.is_some() {
+            write!(f, "/{}/", self.pattern)?;
+        } else {
+            write!(f, "'{}'", self.pattern)?;
+        }
+
+        if let Some(id_account) = self.id_account {
+            write!(f, " in account {}", id_account)?;
+        }
+        match (self.min_amount, self.max_amount) {
+            (Some(min), Some(max)) => write!(f, " from ${:.2} to ${:.2}", min, max)?,
+            (Some(min), None) => write!(f, " from ${:.2}", min)?,
+            (None, Some(max)) => write!(f, " up to ${:.2}", max)?,
+            (None, None) => ()
+        }
+
+        write!(f, " =>")?;
+        if let Some(category) = &self.category {
+            write!(f, " [{}]", category)?;
+        }
+        if let Some(payee) = &self.payee {
+            write!(f, " @{}", payee)?;
         }
+        if let Some(tags) = &self.tags {
+            for tag in tags.split(',') {
+                write!(f, " #{}", tag)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Rule {
+    pub fn from_row(row: &Row<'_>) -> rusqlite::Result<Rule> {
+        let pattern: String = row.get(1)?;
+        let is_regex: i64 = row.get(2)?;
+
+        // Patterns are validated when the rule is added.
+        let regex = if is_regex != 0 { Regex::new(&pattern).ok() } else { None };
+
+        let min_amount: Option<i64> = row.get(7)?;
+        let max_amount: Option<i64> = row.get(8)?;
+
+        Ok(Rule {
+            id: row.get(0)?,
+            pattern,
+            regex,
+            category: row.get(3)?,
+            payee: row.get(4)?,
+            tags: row.get(5)?,
+            id_account: row.get(6)?,
+            min_amount: min_amount.map(|v| v as f64 / 100.0),
+            max_amount: max_amount.map(|v| v as f64 / 100.0)
+        })
+    }
+
+    // Substrings are matched ignoring case, regular expressions as written.
+    pub fn matches(&self, message: &str, value: f64, id_account: Option<i64>) -> bool {
+        let text_matches = match &self.regex {
+            Some(regex) => regex.is_match(message),
+            None => message.to_lowercase().contains(&self.pattern.to_lowercase())
+        };
+
+        let account_matches = match (self.id_account, id_account) {
+            (Some(rule_account), Some(id_account)) => rule_account == id_account,
+            (Some(_), None) => false,
+            (None, _) => true
+        };
+
+        let value = value.abs();
+        let amount_matches = self.min_amount.is_none_or(|min| value >= min)
+            && self.max_amount.is_none_or(|max| value <= max);
+
+        text_matches && account_matches && amount_matches
     }
-}
\ No newline at end of file
+}
+
+#[derive(Debug)]
+pub struct Payee {
+    pub id: i64,
+    pub name: String,
+    // Given to the expenses and incomings of the payee without category.
+    pub category: Option<String>
+}
+
+impl Display for Payee {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{:<4}.- {}", self.id, self.name)?;
+        if let Some(category) = &self.category {
+            write!(f, " [{}]", category)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Payee {
+    pub fn from_row(row: &Row<'_>) -> rusqlite::Result<Payee> {
+        Ok(Payee {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            category: row.get(2)?
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct Payment {
+    pub id: i64,
+    pub name: String,
+    pub price: f64,
+    // Next day the payment is billed.
+    pub billing_date: i64,
+    // Months between billings, 0 when it's billed only once.
+    pub periodicity: i64,
+    pub id_account: i64,
+    // Recurring incomings, like the salary, are payments to the account.
+    pub income: bool
+}
+
+impl Display for Payment {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{:<4}.- ", self.id)?;
+        write!(f, "{}${:>15.2} ", if self.income { "+" } else { " " }, self.price)?;
+        write!(f, "'{}' ", self.name)?;
+        write!(f, "on {}", display_date(self.billing_date))?;
+
+        match self.periodicity {
+            0 => write!(f, " once")?,
+            1 => write!(f, " every month")?,
+            months => write!(f, " every {} months", months)?
+        }
+
+        write!(f, " in account {}", self.id_account)
+    }
+}
+
+impl Payment {
+    pub fn from_row(row: &Row<'_>) -> rusqlite::Result<Payment> {
+        let int_price: i64 = row.get(2)?;
+        let is_income: Option<i64> = row.get(6)?;
+
+        Ok(Payment {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            price: int_price as f64 / 100.0,
+            billing_date: row.get(3)?,
+            periodicity: row.get(4)?,
+            id_account: row.get(5)?,
+            income: is_income.unwrap_or(0) != 0
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct MsiPurchase {
+    pub id: i64,
+    pub name: String,
+    pub price: f64,
+    pub installments: i64,
+    pub months_paid: i64,
+    pub id_account: i64,
+    // Day the next installment is billed, purchases made before this column
+    // existed have none.
+    pub billing_date: Option<i64>
+}
+
+impl Display for MsiPurchase {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{:<4}.-  ${:>15.2} ", self.id, self.price)?;
+        write!(f, "'{}' {}/{} paid", self.name, self.months_paid, self.installments)?;
+
+        if let Some(billing_date) = self.billing_date {
+            write!(f, ", next on {}", display_date(billing_date))?;
+        }
+
+        write!(f, " in account {}", self.id_account)
+    }
+}
+
+impl MsiPurchase {
+    pub fn from_row(row: &Row<'_>) -> rusqlite::Result<MsiPurchase> {
+        let int_price: i64 = row.get(2)?;
+
+        Ok(MsiPurchase {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            price: int_price as f64 / 100.0,
+            installments: row.get(3)?,
+            months_paid: row.get(4)?,
+            id_account: row.get(5)?,
+            billing_date: row.get(6)?
+        })
+    }
+
+    // Cents of each installment left.
+    pub fn remaining_installments(&self) -> Vec<i64> {
+        (self.months_paid..self.installments.max(1)).map(|n| self.installment(n)).collect()
+    }
+
+    // Cents of the installment n, counting from 0. The last one takes the
+    // rounding.
+    pub fn installment(&self, n: i64) -> i64 {
+        let cents = (self.price * 100.0).round() as i64;
+        let installments = self.installments.max(1);
+        let installment = cents / installments;
+
+        if n == installments - 1 { cents - installment * (installments - 1) } else { installment }
+    }
+}
+
+#[derive(Debug)]
+pub struct QueuedPurchase {
+    pub id: i64,
+    pub message: String,
+    pub price: f64,
+    pub id_account: i64
+}
+
+impl Display for QueuedPurchase {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{:<4}.-  ${:>15.2} ", self.id, self.price)?;
+        write!(f, "'{}' in account {}", self.message, self.id_account)
+    }
+}
+
+impl QueuedPurchase {
+    pub fn from_row(row: &Row<'_>) -> rusqlite::Result<QueuedPurchase> {
+        let int_price: i64 = row.get(2)?;
+
+        Ok(QueuedPurchase {
+            id: row.get(0)?,
+            message: row.get(1)?,
+            price: int_price as f64 / 100.0,
+            id_account: row.get(3)?
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct Budget {
+    pub id: i64,
+    // Subcategories are counted in the budget of their category.
+    pub category: String,
+    // Limit of the expenses of every month.
+    pub amount: f64
+}
+
+impl Display for Budget {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{:<4}.- [{}] ${:.2} a month", self.id, self.category, self.amount)
+    }
+}
+
+impl Budget {
+    pub fn from_row(row: &Row<'_>) -> rusqlite::Result<Budget> {
+        let int_amount: i64 = row.get(2)?;
+
+        Ok(Budget {
+            id: row.get(0)?,
+            category: row.get(1)?,
+            amount: int_amount as f64 / 100.0
+        })
+    }
+
+    // Categories are compared without case, like the column of the budgets.
+    pub fn includes(&self, category: &str) -> bool {
+        let category = category.to_lowercase();
+        let own = self.category.to_lowercase();
+
+        category == own || category.starts_with(&format!("{}:", own))
+    }
+}
@@ -1,3 +1,13 @@
+use crate::error::{self, WalletError};
+use crate::output;
+
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+
+use chrono::{Datelike, Local, NaiveDate};
+use time::Date;
+
 pub fn item_type_to_table_name(item_type: &str) -> String {
     match item_type {
         "account" | "transaction" | "payment" | "saving" => {
@@ -10,23 +20,177 @@ pub fn item_type_to_table_name(item_type: &str) -> String {
     }
 }
 
-pub fn validate_tables(e_msg: &str, table_name: &str) {
-    if e_msg.contains("no such table:") {
-        println!("Table '{}' not found! Try 'wallet init' before use it.", table_name);
-    } else {
-        println!("Something went wrong with the query!");
-    }
-}
-
-pub fn opt_str_to_opt_i64(opt_str: Option<&str>) -> Option<i64> {
+pub fn opt_str_to_opt_i64(opt_str: Option<&str>) -> error::Result<Option<i64>> {
     if let Some(str_value) = opt_str {
         let i64_value = match str_value.parse::<i64>() {
             Ok(value) => value,
-            Err(_) => panic!("The value '{}' must be an integer value!", str_value)
+            Err(_) => return error::invalid(format!("The value '{}' must be an integer value!", str_value))
         };
 
-        Some(i64_value)
+        Ok(Some(i64_value))
     } else {
-        None
+        Ok(None)
+    }
+}
+
+
+pub fn today_julian_day() -> i64 {
+    naive_to_julian_day(Local::now().date_naive())
+}
+
+pub fn naive_to_julian_day(date: NaiveDate) -> i64 {
+    date.num_days_from_ce() as i64 + 1_721_425
+}
+
+pub fn julian_day_to_naive(julian_date: i64) -> error::Result<NaiveDate> {
+    i32::try_from(julian_date - 1_721_425).ok()
+        .and_then(NaiveDate::from_num_days_from_ce_opt)
+        .ok_or_else(|| out_of_range(julian_date))
+}
+
+pub fn julian_day_to_iso(julian_date: i64) -> error::Result<String> {
+    i32::try_from(julian_date).ok()
+        .and_then(|julian_date| Date::from_julian_day(julian_date).ok())
+        .map(|date| date.to_string())
+        .ok_or_else(|| out_of_range(julian_date))
+}
+
+// Days stored in the database that can't be turned into a date.
+fn out_of_range(julian_date: i64) -> WalletError {
+    WalletError::InvalidInput(format!("The day {} is out of the range of dates!", julian_date))
+}
+
+// Text written inside HTML elements and attributes.
+pub fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+// Parses dates in the format YYYY-MM-DD.
+pub fn parse_date(str_date: &str) -> Option<i64> {
+    NaiveDate::parse_from_str(str_date, "%Y-%m-%d").ok().map(naive_to_julian_day)
+}
+
+// Like parse_date, with an error that tells which date is not valid.
+pub fn parse_date_arg(str_date: &str) -> error::Result<i64> {
+    match parse_date(str_date) {
+        Some(julian_date) => Ok(julian_date),
+        None => error::invalid(format!("Invalid date '{}'! Please use the format YYYY-MM-DD...", str_date))
+    }
+}
+
+// First day of a month given as YYYY-MM.
+pub fn parse_month(str_month: &str) -> error::Result<NaiveDate> {
+    match NaiveDate::parse_from_str(&format!("{}-01", str_month), "%Y-%m-%d") {
+        Ok(first_day) => Ok(first_day),
+        Err(_) => error::invalid(format!("Invalid month '{}'! Please use the format YYYY-MM...", str_month))
+    }
+}
+
+// Day given with --as-of, today without it.
+pub fn parse_as_of(opt_as_of: Option<&str>) -> error::Result<i64> {
+    match opt_as_of {
+        Some(str_date) => parse_date_arg(str_date),
+        None => Ok(today_julian_day())
+    }
+}
+
+// Dates of --from and --to.
+pub fn parse_date_range(opt_from: Option<&str>, opt_to: Option<&str>) -> error::Result<(Option<i64>, Option<i64>)> {
+    Ok((opt_from.map(parse_date_arg).transpose()?, opt_to.map(parse_date_arg).transpose()?))
+}
+
+pub fn read_file(path: &str) -> error::Result<Vec<u8>> {
+    fs::read(path).map_err(|e| WalletError::Io(format!("Unable to read the file '{}'!", path), e))
+}
+
+// Questions go to stderr when scripts read the standard output.
+pub fn prompt(question: &str) -> error::Result<String> {
+    if output::is_text() {
+        print!("{}", question);
+        io::stdout().flush().map_err(|e| WalletError::Io("Unable to write the question!".to_string(), e))?;
+    } else {
+        eprint!("{}", question);
+    }
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).map_err(|e| WalletError::Io("Unable to read the answer!".to_string(), e))?;
+
+    Ok(answer.trim().to_lowercase())
+}
+
+pub fn confirm(question: &str) -> error::Result<bool> {
+    Ok(matches!(prompt(question)?.as_str(), "y" | "yes"))
+}
+
+pub fn read_passphrase(prompt: &str) -> error::Result<String> {
+    if let Ok(passphrase) = env::var("WALLET_PASSPHRASE") {
+        return Ok(passphrase);
+    }
+
+    prompt_password(prompt)
+}
+
+fn prompt_password(prompt: &str) -> error::Result<String> {
+    rpassword::prompt_password(prompt).map_err(|e| WalletError::Io("Unable to read the passphrase!".to_string(), e))
+}
+
+// An empty passphrase would leave the database readable by anyone.
+pub fn read_new_passphrase(env_name: &str) -> error::Result<String> {
+    if let Ok(passphrase) = env::var(env_name) {
+        if passphrase.is_empty() {
+            return error::invalid(format!("The passphrase can't be empty! Check the variable {}...", env_name));
+        }
+
+        return Ok(passphrase);
+    }
+
+    loop {
+        let passphrase = prompt_password("New passphrase: ")?;
+
+        if passphrase.is_empty() {
+            output::message("The passphrase can't be empty!");
+            continue;
+        }
+
+        let confirmation = prompt_password("Confirm passphrase: ")?;
+
+        if passphrase == confirmation {
+            return Ok(passphrase);
+        }
+
+        output::message("Passphrases don't match! Try again...");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates_are_parsed_to_julian_days() {
+        assert_eq!(parse_date("2026-10-19"), Some(2_461_333));
+        assert_eq!(parse_date("2026-10-19"), Some(naive_to_julian_day(NaiveDate::from_ymd_opt(2026, 10, 19).unwrap())));
+        assert_eq!(parse_date("19/10/2026"), None);
+        assert_eq!(parse_date("2026-02-30"), None);
+    }
+
+    #[test]
+    fn as_of_defaults_to_today() {
+        assert_eq!(parse_as_of(None).unwrap(), today_julian_day());
+        assert_eq!(parse_as_of(Some("2026-10-19")).unwrap(), 2_461_333);
+        assert_eq!(parse_as_of(Some("yesterday")).unwrap_err().kind(), "invalid-input");
+    }
+
+    #[test]
+    fn new_passphrases_come_from_the_environment() {
+        env::set_var("WALLET_TEST_NEW_PASSPHRASE", "secret");
+        assert_eq!(read_new_passphrase("WALLET_TEST_NEW_PASSPHRASE").unwrap(), "secret");
+
+        env::set_var("WALLET_TEST_EMPTY_PASSPHRASE", "");
+        assert_eq!(read_new_passphrase("WALLET_TEST_EMPTY_PASSPHRASE").unwrap_err().kind(), "invalid-input");
     }
 }
@@ -1,42 +1,249 @@
-use crate::structs::{Account};
+use crate::error::{self, WalletError};
+use crate::export;
+use crate::journal;
+use crate::output;
+use crate::payees;
+use crate::rules;
+use crate::structs::{Account, MsiPurchase, Payment, QueuedPurchase, Rule, Transaction, ADJUSTMENT, EXPENSE, INCOMING, TRANSFER};
 use crate::utils;
 
-use std::io;
 use std::fs;
-use std::path::PathBuf;
+use std::path::Path;
 
-use chrono::prelude::{DateTime, Local};
-use time::{Date, format_description};
-
-use rusqlite::{params, Connection};
+use rusqlite::{params, Connection, ErrorCode, Row};
 
 const DB_NAME: &str = "wallet.db3";
 
+// Amount a transaction adds to its account. Expenses are stored as positive
+// values, adjustments carry their own sign.
+pub const SIGNED_VALUE: &str = "CASE t_type WHEN 0 THEN -value ELSE value END";
+
+#[cfg(test)]
+thread_local! {
+    static MEMORY_DATABASE: std::cell::RefCell<Option<String>> = const { std::cell::RefCell::new(None) };
+}
+
+// Tables created by 'wallet init', every command expects all of them.
+const TABLES: [&str; 13] = [
+    "accounts", "transactions", "payments", "savings", "msi_purchases", "queued_purchases", "operations",
+    "operation_rows", "reconciliations", "csv_profiles", "rules", "payees", "budgets"
+];
+
+// Database connection and encryption helpers.
+// Opens the database created by 'wallet init', asking for the passphrase when
+// it's encrypted. The in-memory database of the tests has no file.
+pub fn open_database() -> error::Result<Connection> {
+    let name = database_name();
+
+    if name == DB_NAME && !Path::new(DB_NAME).exists() {
+        return Err(WalletError::NotInitialised("Database does not exists! Try 'wallet init'...".to_string()));
+    }
+
+    let conn = connect(&name)?;
+
+    let mut stmt = conn.prepare("SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?1")?;
+
+    for table_name in TABLES {
+        let n_tables: i64 = stmt.query_row(params![table_name], |row| row.get(0))?;
+
+        if n_tables == 0 {
+            return Err(WalletError::NotInitialised(
+                format!("Table '{}' not found! Try 'wallet init' before use it.", table_name)
+            ));
+        }
+    }
+
+    stmt.finalize()?;
+
+    Ok(conn)
+}
+
+// Opens the file, or creates it, without looking at its tables.
+fn connect(name: &str) -> error::Result<Connection> {
+    let conn = Connection::open(name)?;
+
+    if is_readable(&conn) {
+        return Ok(conn);
+    }
+
+    let passphrase = utils::read_passphrase("Database passphrase: ")?;
+    conn.pragma_update(None, "key", &passphrase)?;
+
+    if conn.query_row("SELECT count(*) FROM sqlite_master", [], |_| Ok(())).is_err() {
+        return error::invalid("Wrong passphrase or the file is not a wallet database!");
+    }
+
+    Ok(conn)
+}
+
+// Commands run by tests use the in-memory database of their thread.
+fn database_name() -> String {
+    #[cfg(test)]
+    if let Some(name) = MEMORY_DATABASE.with(|name| name.borrow().clone()) {
+        return name;
+    }
+
+    DB_NAME.to_string()
+}
+
+// A new in-memory database with every table. The commands run by this thread
+// use it while the returned connection is open.
+#[cfg(test)]
+pub fn memory_database() -> Connection {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static N_DATABASES: AtomicUsize = AtomicUsize::new(0);
+
+    let name = format!("file:wallet-test-{}?mode=memory&cache=shared", N_DATABASES.fetch_add(1, Ordering::SeqCst));
+    let conn = Connection::open(&name).unwrap();
+
+    create_tables(&conn).unwrap();
+    MEMORY_DATABASE.with(|database| *database.borrow_mut() = Some(name));
+
+    conn
+}
+
+fn is_readable(conn: &Connection) -> bool {
+    match conn.query_row("SELECT count(*) FROM sqlite_master", [], |_| Ok(())) {
+        Ok(_) => true,
+        Err(rusqlite::Error::SqliteFailure(e, _)) => e.code != ErrorCode::NotADatabase,
+        Err(_) => true
+    }
+}
+
+fn is_encrypted(path: &str) -> rusqlite::Result<bool> {
+    if !Path::new(path).exists() {
+        return Ok(false);
+    }
+
+    let conn = Connection::open(path)?;
+    let encrypted = !is_readable(&conn);
+    conn.close().map_err(|(_, e)| e)?;
+
+    Ok(encrypted)
+}
+
+fn export_encrypted(conn: &Connection, target: &str, passphrase: &str) -> rusqlite::Result<()> {
+    conn.execute("ATTACH DATABASE ?1 AS encrypted KEY ?2", params![target, passphrase])?;
+    conn.query_row("SELECT sqlcipher_export('encrypted')", [], |_| Ok(()))?;
+    conn.execute("DETACH DATABASE encrypted", [])?;
+
+    Ok(())
+}
+
+
 // Wallet subcommands are defined below.
-pub fn backup_database(backup_path: &PathBuf) -> Result<(), io::Error> {
-    match fs::copy(DB_NAME, backup_path.to_str().unwrap()) {
-        Ok(_) => println!("Backup created successfully!"),
-        Err(e) => {
-            match e.kind() {
-                io::ErrorKind::NotFound => println!("Database does not exists! Try 'wallet init'..."),
-                _ => println!("Something went wrong! Error: {}", e),
+pub fn backup_database(backup_path: &Path, encrypt: bool) -> error::Result<()> {
+    if !Path::new(DB_NAME).exists() {
+        return Err(WalletError::NotInitialised("Database does not exists! Try 'wallet init'...".to_string()));
+    }
+
+    // An existing backup is only replaced when the user says so.
+    if backup_path.exists() && !utils::confirm(
+        &format!("The file '{}' already exists. Replace it? [y/N] ", backup_path.display())
+    )? {
+        output::message(format!("Backup cancelled! The file '{}' was kept.", backup_path.display()));
+        return Ok(());
+    }
+
+    if encrypt {
+        let conn = open_database()?;
+        let passphrase = utils::read_new_passphrase("WALLET_BACKUP_PASSPHRASE")?;
+
+        let path = match backup_path.to_str() {
+            Some(path) => path,
+            None => return error::invalid(format!("The path '{}' is not valid UTF-8!", backup_path.display()))
+        };
+
+        if backup_path.exists() {
+            if let Err(e) = fs::remove_file(backup_path) {
+                return Err(WalletError::Io(format!("Unable to replace the file '{}'!", backup_path.display()), e));
             }
         }
+
+        export_encrypted(&conn, path, &passphrase)?;
+        conn.close().map_err(|(_, e)| e)?;
+
+        output::message("Encrypted backup created successfully!");
+        return Ok(());
+    }
+
+    if let Err(e) = fs::copy(DB_NAME, backup_path) {
+        return Err(WalletError::Io(format!("Unable to copy the database to '{}'!", backup_path.display()), e));
+    }
+
+    if is_encrypted(DB_NAME)? {
+        output::message("Backup created successfully! It uses the same passphrase as the database.");
+    } else {
+        output::message("Backup created successfully!");
     }
 
     Ok(())
 }
 
-pub fn initialize_database() -> rusqlite::Result<()> {
-    let conn = Connection::open(DB_NAME)?;
+pub fn db_rekey() -> error::Result<()> {
+    if !is_encrypted(DB_NAME)? {
+        return error::invalid("The database is not encrypted! Try 'wallet init --encrypt' to encrypt it.");
+    }
+
+    let conn = open_database()?;
+    let passphrase = utils::read_new_passphrase("WALLET_NEW_PASSPHRASE")?;
+
+    conn.pragma_update(None, "rekey", &passphrase)?;
+    conn.close().map_err(|(_, e)| e)?;
+
+    output::message("Successfully changed the database passphrase!");
+
+    Ok(())
+}
+
+pub fn initialize_database(encrypt: bool) -> error::Result<()> {
+    let conn = if encrypt && !is_encrypted(DB_NAME)? {
+        let passphrase = utils::read_new_passphrase("WALLET_PASSPHRASE")?;
+        let existing_data = fs::metadata(DB_NAME).map(|m| m.len() > 0).unwrap_or(false);
+
+        if existing_data {
+            let tmp_name = format!("{}.tmp", DB_NAME);
+            let _ = fs::remove_file(&tmp_name);
+
+            let plain = Connection::open(DB_NAME)?;
+            export_encrypted(&plain, &tmp_name, &passphrase)?;
+            plain.close().map_err(|(_, e)| e)?;
+
+            if let Err(e) = fs::rename(&tmp_name, DB_NAME) {
+                return Err(WalletError::Io("Unable to replace the database file!".to_string(), e));
+            }
+
+            output::message("Existing database encrypted.");
+        }
+
+        let conn = Connection::open(DB_NAME)?;
+        conn.pragma_update(None, "key", &passphrase)?;
+        conn
+    } else {
+        connect(DB_NAME)?
+    };
+
+    create_tables(&conn)?;
+    conn.close().map_err(|(_, e)| e)?;
+
+    Ok(())
+}
 
+fn create_tables(conn: &Connection) -> rusqlite::Result<()> {
     conn.execute(
         "CREATE TABLE IF NOT EXISTS accounts (
             id_account      INTEGER PRIMARY KEY,
             name            TEXT NOT NULL,
             balance         INTEGER DEFAULT 0,
             available       INTEGER DEFAULT 0,
-            is_default      INTEGER DEFAULT 0
+            is_default      INTEGER DEFAULT 0,
+            opening_balance INTEGER DEFAULT 0,
+            ofx_acctid      TEXT,
+            is_credit       INTEGER DEFAULT 0,
+            currency        TEXT,
+            created         INTEGER,
+            minimum         INTEGER DEFAULT 0
         )", []
     )?;
 
@@ -49,6 +256,12 @@ pub fn initialize_database() -> rusqlite::Result<()> {
             charged         INTEGER DEFAULT 0,
             t_type          INTEGER NOT NULL,
             id_account      INTEGER NOT NULL,
+            fitid           TEXT,
+            category        TEXT,
+            payee           TEXT,
+            tags            TEXT,
+            id_transfer     INTEGER,
+            charge_date     INTEGER,
             FOREIGN KEY (id_account) REFERENCES accounts (id_account)
         )", []
     )?;
@@ -61,6 +274,7 @@ pub fn initialize_database() -> rusqlite::Result<()> {
             billing_date    INTEGER NOT NULL,
             priodicity      INTEGER NOT NULL,
             id_account      INTEGER NOT NULL,
+            is_income       INTEGER DEFAULT 0,
             FOREIGN KEY (id_account) REFERENCES accounts (id_account)
         )", []
     )?;
@@ -84,6 +298,7 @@ pub fn initialize_database() -> rusqlite::Result<()> {
             installments    INTEGER NOT NULL,
             months_paid     INTEGER NOT NULL,
             id_account      INTEGER NOT NULL,
+            billing_date    INTEGER,
             FOREIGN KEY (id_account) REFERENCES accounts (id_account)
         )", []
     )?;
@@ -98,40 +313,243 @@ pub fn initialize_database() -> rusqlite::Result<()> {
         )", []
     )?;
 
-    conn.close().unwrap();
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS operations (
+            id_operation    INTEGER PRIMARY KEY,
+            command         TEXT NOT NULL,
+            date            INTEGER NOT NULL,
+            undone          INTEGER DEFAULT 0
+        )", []
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS operation_rows (
+            id_operation_row    INTEGER PRIMARY KEY,
+            id_operation        INTEGER NOT NULL,
+            table_name          TEXT NOT NULL,
+            row_id              INTEGER NOT NULL,
+            before              TEXT,
+            after               TEXT,
+            FOREIGN KEY (id_operation) REFERENCES operations (id_operation)
+        )", []
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS reconciliations (
+            id_reconciliation   INTEGER PRIMARY KEY,
+            date                INTEGER NOT NULL,
+            statement_balance   INTEGER NOT NULL,
+            difference          INTEGER NOT NULL,
+            id_account          INTEGER NOT NULL,
+            FOREIGN KEY (id_account) REFERENCES accounts (id_account)
+        )", []
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS csv_profiles (
+            id_profile          INTEGER PRIMARY KEY,
+            name                TEXT NOT NULL UNIQUE,
+            delimiter           TEXT DEFAULT ',',
+            skip_rows           INTEGER DEFAULT 1,
+            date_column         INTEGER NOT NULL,
+            date_format         TEXT NOT NULL,
+            description_column  INTEGER NOT NULL,
+            amount_column       INTEGER,
+            debit_column        INTEGER,
+            credit_column       INTEGER,
+            decimal_separator   TEXT DEFAULT '.'
+        )", []
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS rules (
+            id_rule         INTEGER PRIMARY KEY,
+            pattern         TEXT NOT NULL,
+            is_regex        INTEGER DEFAULT 0,
+            category        TEXT,
+            payee           TEXT,
+            tags            TEXT,
+            id_account      INTEGER,
+            min_amount      INTEGER,
+            max_amount      INTEGER,
+            FOREIGN KEY (id_account) REFERENCES accounts (id_account)
+        )", []
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS payees (
+            id_payee        INTEGER PRIMARY KEY,
+            name            TEXT NOT NULL UNIQUE COLLATE NOCASE,
+            category        TEXT
+        )", []
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS budgets (
+            id_budget       INTEGER PRIMARY KEY,
+            category        TEXT NOT NULL UNIQUE COLLATE NOCASE,
+            amount          INTEGER NOT NULL
+        )", []
+    )?;
+
+    migrate_database(conn)?;
+
+    Ok(())
+}
+
+// Brings databases created by older versions up to date. Must be safe to run
+// on every 'wallet init'.
+fn migrate_database(conn: &Connection) -> rusqlite::Result<()> {
+    if add_column(conn, "accounts", "opening_balance INTEGER DEFAULT NULL")? {
+        // The current balance minus the charged history is the best opening
+        // balance we can get for accounts created before this column existed.
+        conn.execute(
+            &format!(
+                "UPDATE accounts SET opening_balance = balance - COALESCE((
+                    SELECT SUM({}) FROM transactions t
+                    WHERE t.id_account = accounts.id_account AND t.charged = 1
+                ), 0)",
+                SIGNED_VALUE
+            ), []
+        )?;
+    }
+
+    add_column(conn, "accounts", "ofx_acctid TEXT")?;
+    add_column(conn, "transactions", "fitid TEXT")?;
+    add_column(conn, "transactions", "category TEXT")?;
+    add_column(conn, "transactions", "payee TEXT")?;
+    add_column(conn, "transactions", "tags TEXT")?;
+    add_column(conn, "accounts", "is_credit INTEGER DEFAULT 0")?;
+    add_column(conn, "accounts", "currency TEXT")?;
+    add_column(conn, "transactions", "id_transfer INTEGER")?;
+
+    // Charged transactions without charge date were charged on their date.
+    add_column(conn, "transactions", "charge_date INTEGER")?;
+    conn.execute("UPDATE transactions SET charge_date = date WHERE charged = 1 AND charge_date IS NULL", [])?;
+
+    // Accounts older than this column were opened on the day the journal
+    // recorded their creation or, without it, on the day of their first
+    // transaction.
+    if add_column(conn, "accounts", "created INTEGER")? {
+        conn.execute(
+            "UPDATE accounts SET created = COALESCE((
+                SELECT MIN(op.date) FROM operation_rows r JOIN operations op USING (id_operation)
+                WHERE r.table_name = 'accounts' AND r.row_id = accounts.id_account AND r.before IS NULL
+                    AND op.undone = 0
+            ), (
+                SELECT MIN(t.date) FROM transactions t WHERE t.id_account = accounts.id_account
+            ))", []
+        )?;
+    }
+
+    add_column(conn, "accounts", "minimum INTEGER DEFAULT 0")?;
+    add_column(conn, "payments", "is_income INTEGER DEFAULT 0")?;
+    add_column(conn, "msi_purchases", "billing_date INTEGER")?;
+
+    // Payees were only text in transactions and rules before their table.
+    conn.execute(
+        "INSERT OR IGNORE INTO payees (name)
+         SELECT payee FROM transactions WHERE payee IS NOT NULL AND TRIM(payee) != ''
+         UNION SELECT payee FROM rules WHERE payee IS NOT NULL AND TRIM(payee) != ''", []
+    )?;
 
     Ok(())
 }
 
-pub fn list(table_name: &str, count: i64) -> rusqlite::Result<()> {
-    let conn = Connection::open(DB_NAME)?;
-    let mut stmt = conn.prepare(&format!("SELECT * FROM {} LIMIT {}", table_name, count))?;
+// Adds the column unless the table already has it, the definition starts with
+// the name of the column.
+fn add_column(conn: &Connection, table_name: &str, definition: &str) -> rusqlite::Result<bool> {
+    let column_name = definition.split_whitespace().next().unwrap_or(definition);
+    let n_columns: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM pragma_table_info(?1) WHERE name = ?2",
+        params![table_name, column_name],
+        |row| row.get(0)
+    )?;
+
+    if n_columns > 0 {
+        return Ok(false);
+    }
+
+    conn.execute(&format!("ALTER TABLE {} ADD COLUMN {}", table_name, definition), [])?;
 
-    let items = match table_name {
-        "accounts" => stmt.query_map([], |row| Ok(Account::from_row(row)))?,
-        _ => panic!("Not implemented yet!")
+    Ok(true)
+}
+
+pub fn list(table_name: &str, count: i64) -> error::Result<()> {
+    let conn = open_database()?;
+
+    let (format_row, order): (fn(&Row) -> rusqlite::Result<String>, &str) = match table_name {
+        "accounts" => (|row| Ok(Account::from_row(row)?.to_string()), "id_account ASC"),
+        "transactions" => (|row| Ok(Transaction::from_row(row)?.to_string()), "date DESC, id_transaction DESC"),
+        "payments" => (|row| Ok(Payment::from_row(row)?.to_string()), "billing_date ASC, id_payment ASC"),
+        "msi_purchases" => (|row| Ok(MsiPurchase::from_row(row)?.to_string()), "id_msi ASC"),
+        "queued_purchases" => (|row| Ok(QueuedPurchase::from_row(row)?.to_string()), "id_queued ASC"),
+        _ => return error::invalid(format!("Table '{}' can't be listed yet!", table_name))
     };
 
+    let mut stmt = conn.prepare(
+        &format!("SELECT * FROM {} ORDER BY {} LIMIT {}", table_name, order, count)
+    )?;
+    let mut rows = stmt.query([])?;
+
     let mut items_len = 0;
 
-    for item in items {
+    while let Some(row) = rows.next()? {
+        let fields = export::row_fields(table_name, row)?;
+
         items_len += 1;
-        println!("{}", item.unwrap());
+        output::message(format_row(row)?);
+        output::record(fields);
     }
 
     if items_len == 0 {
-        println!("Table '{}' is empty! Try 'wallet new --help'.", table_name);
+        output::message(format!("Table '{}' is empty! Try 'wallet new --help'.", table_name));
     }
 
+    drop(rows);
     stmt.finalize()?;
-    conn.close().unwrap();
+    conn.close().map_err(|(_, e)| e)?;
     Ok(())
 }
 
 
+// Balances the accounts had at the end of the day, rebuilt from the history.
+pub fn list_accounts_as_of(str_date: &str, count: i64) -> error::Result<()> {
+    let julian_date = utils::parse_date_arg(str_date)?;
+
+    let conn = open_database()?;
+
+    let accounts = select_accounts_as_of(&conn, julian_date)?;
+
+    if accounts.is_empty() {
+        output::message("Table 'accounts' is empty! Try 'wallet new --help'.");
+        return Ok(());
+    }
+
+    let date = utils::julian_day_to_iso(julian_date)?;
+
+    output::message(format!("Balances at the end of {}:", date));
+
+    let count = if count < 0 { accounts.len() } else { count as usize };
+
+    for account in accounts.iter().take(count) {
+        output::message(account);
+        output::record(vec![
+            ("id_account", account.id.into()),
+            ("name", account.name.as_str().into()),
+            ("balance", account.balance.into()),
+            ("available", account.available.into()),
+            ("date", date.as_str().into())
+        ]);
+    }
+
+    Ok(())
+}
+
 // Functions to read/write rows using structs.
-fn select_account(conn: &Connection, opt_str_id: Option<&str>) -> rusqlite::Result<Account> {
-    let opt_id = utils::opt_str_to_opt_i64(opt_str_id);
+// The account with the ID, or the default one without it.
+pub fn select_account(conn: &Connection, opt_str_id: Option<&str>) -> error::Result<Account> {
+    let opt_id = utils::opt_str_to_opt_i64(opt_str_id)?;
 
     let mut stmt = if let Some(id) = opt_id {
         conn.prepare(&format!("SELECT * FROM accounts WHERE id_account={}", id))?
@@ -142,11 +560,10 @@ fn select_account(conn: &Connection, opt_str_id: Option<&str>) -> rusqlite::Resu
     let account: Account;
     {
         let mut rows = stmt.query([])?;
-        account = if let Some(row) = rows.next()? {
-            Account::from_row(row)
-        } else {
-            println!("Account with ID '{}' not found or the ID is invalid!", opt_id.unwrap());
-            Account::empty()
+        account = match (rows.next()?, opt_id) {
+            (Some(row), _) => Account::from_row(row)?,
+            (None, Some(id)) => return error::not_found(format!("Account with ID '{}' not found or the ID is invalid!", id)),
+            (None, None) => return error::not_found("There is no default account! Try 'wallet account default <ID>'.")
         };
     }
 
@@ -155,110 +572,288 @@ fn select_account(conn: &Connection, opt_str_id: Option<&str>) -> rusqlite::Resu
     Ok(account)
 }
 
-fn update_account(conn: &Connection, account: &Account) -> rusqlite::Result<()> {
+// Accounts with the balance and available they had at the end of the day.
+// Transactions count in the balance from the day they were charged and in the
+// available from their date. The opening balance only counts from the day the
+// account was opened: the day it was created or, if earlier, the day of its
+// first transaction. Accounts without creation day nor transactions always
+// have it.
+pub fn select_accounts_as_of(conn: &Connection, julian_date: i64) -> rusqlite::Result<Vec<Account>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT a.*,
+            o.opening + COALESCE((
+                SELECT SUM({0}) FROM transactions t
+                WHERE t.id_account = a.id_account AND t.charged = 1 AND COALESCE(t.charge_date, t.date) <= ?1
+            ), 0),
+            o.opening + COALESCE((
+                SELECT SUM({0}) FROM transactions t
+                WHERE t.id_account = a.id_account AND t.date <= ?1
+            ), 0)
+        FROM accounts a
+        JOIN (
+            SELECT id_account,
+                CASE WHEN COALESCE(MIN(COALESCE(created, first_date), COALESCE(first_date, created)) <= ?1, 1)
+                    THEN opening_balance ELSE 0 END AS opening
+            FROM (
+                SELECT id_account, created, COALESCE(opening_balance, 0) AS opening_balance,
+                    (SELECT MIN(t.date) FROM transactions t WHERE t.id_account = accounts.id_account) AS first_date
+                FROM accounts
+            )
+        ) o ON o.id_account = a.id_account
+        ORDER BY a.id_account",
+        SIGNED_VALUE
+    ))?;
+
+    let accounts = stmt.query_map(params![julian_date], |row| {
+        let n_columns = row.as_ref().column_count();
+        let balance: i64 = row.get(n_columns - 2)?;
+        let available: i64 = row.get(n_columns - 1)?;
+
+        Ok(Account {
+            balance: balance as f64 / 100.0,
+            available: available as f64 / 100.0,
+            ..Account::from_row(row)?
+        })
+    })?;
+
+    accounts.collect()
+}
+
+// Day the account was opened, as 'select_accounts_as_of' counts it, and its
+// opening balance in cents. None when it has neither creation day nor
+// transactions.
+pub fn account_opening(conn: &Connection, id_account: i64) -> rusqlite::Result<(Option<i64>, i64)> {
+    let (opt_created, opt_first_date, opening_balance): (Option<i64>, Option<i64>, i64) = conn.query_row(
+        "SELECT created, (SELECT MIN(date) FROM transactions WHERE id_account = ?1), COALESCE(opening_balance, 0)
+         FROM accounts WHERE id_account = ?1",
+        params![id_account],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+    )?;
+
+    let opt_opened = match (opt_created, opt_first_date) {
+        (Some(created), Some(first_date)) => Some(created.min(first_date)),
+        (opt_created, opt_first_date) => opt_created.or(opt_first_date)
+    };
+
+    Ok((opt_opened, opening_balance))
+}
+
+// The ID of the given account, checking that it exists.
+pub fn account_filter(conn: &Connection, opt_id_account: Option<&str>) -> error::Result<Option<i64>> {
+    match opt_id_account {
+        Some(id) => Ok(Some(select_account(conn, Some(id))?.id)),
+        None => Ok(None)
+    }
+}
+
+pub fn update_account(conn: &Connection, id_operation: i64, account: &Account) -> rusqlite::Result<()> {
     let int_balance: i64 = (account.balance * 100.0).round() as i64;
     let int_available: i64 = (account.available * 100.0).round() as i64;
 
+    let before = journal::row_image(conn, "accounts", account.id)?;
+
     conn.execute(
         "UPDATE accounts
-        SET name = ?1, balance = ?2, available = ?3
-        WHERE id_account = ?4
+        SET name = ?1, balance = ?2, available = ?3, minimum = ?4
+        WHERE id_account = ?5
         ",
-        params![&account.name, int_balance, int_available, account.id]
+        params![&account.name, int_balance, int_available, (account.minimum * 100.0).round() as i64, account.id]
     )?;
 
-    Ok(())
+    journal::record(conn, id_operation, "accounts", account.id, before)
+}
+
+
+// Inserts a new transaction. Expenses and incomings without category, payee or
+// tags get them from the first rule that matches, then the category from the
+// default one of their payee.
+pub fn insert_transaction(
+            conn: &Connection, id_operation: i64, transaction: &Transaction
+        ) -> rusqlite::Result<i64> {
+    let rules = if transaction.t_type == EXPENSE || transaction.t_type == INCOMING {
+        rules::select_rules(conn)?
+    } else {
+        Vec::new()
+    };
+
+    insert_transaction_with_rules(conn, id_operation, transaction, &rules)
+}
+
+// Same as insert_transaction with the rules already loaded, imports load them
+// once for all their rows.
+pub fn insert_transaction_with_rules(
+            conn: &Connection, id_operation: i64, transaction: &Transaction, rules: &[Rule]
+        ) -> rusqlite::Result<i64> {
+    let mut transaction = transaction.clone();
+
+    if transaction.t_type == EXPENSE || transaction.t_type == INCOMING {
+        rules::apply_rules(rules, &mut transaction);
+        payees::register_payee(conn, id_operation, &mut transaction)?;
+    }
+
+    let int_value: i64 = (transaction.value * 100.0).round() as i64;
+    let int_charged: i64 = if transaction.charged { 1 } else { 0 };
+    let opt_charge_date = if transaction.charged { transaction.charge_date.or(Some(transaction.date)) } else { None };
+
+    conn.execute(
+        "INSERT INTO transactions (
+            message, value, date, charged, t_type, id_account, fitid, category, payee, tags,
+            id_transfer, charge_date
+        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+        params![
+            &transaction.message, int_value, transaction.date, int_charged,
+            transaction.t_type, transaction.id_account, &transaction.fitid,
+            &transaction.category, &transaction.payee, &transaction.tags, transaction.id_transfer,
+            opt_charge_date
+        ]
+    )?;
+
+    let id_transaction = conn.last_insert_rowid();
+    journal::record(conn, id_operation, "transactions", id_transaction, None)?;
+
+    Ok(id_transaction)
+}
+
+// Inserts both rows of a transfer, each one pointing to the other.
+pub fn insert_transfer(
+            conn: &Connection, id_operation: i64, source: &Transaction, destination: &Transaction
+        ) -> rusqlite::Result<()> {
+    let id_source = insert_transaction(conn, id_operation, source)?;
+
+    complete_transfer(conn, id_operation, id_source, destination)
+}
+
+// Writes the side of a transfer that is missing and links both sides.
+pub fn complete_transfer(
+            conn: &Connection, id_operation: i64, id_existing: i64, missing: &Transaction
+        ) -> rusqlite::Result<()> {
+    let id_missing = insert_transaction(conn, id_operation, &Transaction {
+        id_transfer: Some(id_existing),
+        ..missing.clone()
+    })?;
+
+    let before = journal::row_image(conn, "transactions", id_existing)?;
+
+    conn.execute(
+        "UPDATE transactions SET id_transfer = ?1 WHERE id_transaction = ?2",
+        params![id_missing, id_existing]
+    )?;
+
+    journal::record(conn, id_operation, "transactions", id_existing, before)
+}
+
+// Marks a pending transaction as charged on the given day. The FITID of the
+// statement row that confirmed it is kept, if any.
+pub fn charge_transaction(
+            conn: &Connection, id_operation: i64, id_transaction: i64, opt_fitid: Option<&str>,
+            charge_date: i64
+        ) -> rusqlite::Result<()> {
+    let before = journal::row_image(conn, "transactions", id_transaction)?;
+
+    conn.execute(
+        "UPDATE transactions SET charged = 1, fitid = COALESCE(?2, fitid), charge_date = ?3
+         WHERE id_transaction = ?1",
+        params![id_transaction, opt_fitid, charge_date]
+    )?;
+
+    journal::record(conn, id_operation, "transactions", id_transaction, before)
 }
 
 
 // Wallet 'account' subcommands are defined below.
-pub fn account_default(id: &str) -> rusqlite::Result<()> {
-    let conn = Connection::open(DB_NAME)?;
+pub fn account_default(id: &str) -> error::Result<()> {
+    let mut conn = open_database()?;
 
     let account = select_account(&conn, Some(id))?;
 
     if account.default {
-        println!("This is already the default account!");
-        println!("{}", account);
+        output::message("This is already the default account!");
+        output::message(account);
 
         return Ok(());
-    } else if !account.exists() {
-        println!("The ID '{}' was not found in accounts!", id);
+    }
 
-        return Ok(());
+    let tx = conn.transaction()?;
+    let id_operation = journal::begin(&tx)?;
+
+    for id_default in journal::ids_where(&tx, "accounts", "is_default = 1")? {
+        let before = journal::row_image(&tx, "accounts", id_default)?;
+
+        tx.execute("UPDATE accounts SET is_default = 0 WHERE id_account = ?1", params![id_default])?;
+
+        journal::record(&tx, id_operation, "accounts", id_default, before)?;
     }
 
-    match conn.execute("UPDATE accounts SET is_default = 0 WHERE is_default = 1", []) {
-        Err(e) => utils::validate_tables(&format!("{}", e), "accounts"),
-        _ => ()
-    };
+    let before = journal::row_image(&tx, "accounts", account.id)?;
 
-    match conn.execute(
-        "UPDATE accounts SET is_default = 1 WHERE id_account = ?1",
-        params![id]
-    ) {
-        Ok(1) => {
-            println!("Success! The account '{}' is now default!", account.name);
-        }
-        Ok(_) => println!("More than one row was updated! Please check the consistency of IDs..."),
-        Err(e) => utils::validate_tables(&format!("{}", e), "accounts")
+    match tx.execute("UPDATE accounts SET is_default = 1 WHERE id_account = ?1", params![account.id])? {
+        1 => output::message(format!("Success! The account '{}' is now default!", account.name)),
+        _ => output::message("More than one row was updated! Please check the consistency of IDs...")
     };
 
-    Ok(())
+    journal::record(&tx, id_operation, "accounts", account.id, before)?;
+
+    Ok(tx.commit()?)
 }
 
-pub fn account_delete(opt_id: Option<&str>, delete_all: bool) -> rusqlite::Result<()> {
-    let conn = Connection::open(DB_NAME)?;
+pub fn account_delete(opt_id: Option<&str>, delete_all: bool) -> error::Result<()> {
+    let mut conn = open_database()?;
 
-    let (account, query) = if delete_all {
+    let (account, condition) = if delete_all {
         (
             Account::empty(),
-            format!("DELETE FROM accounts")
+            "1".to_string()
         )
     } else if let Some(id) = opt_id {
-        (
-            select_account(&conn, Some(id))?,
-            format!("DELETE FROM accounts WHERE id_account = {}", id).to_string()
-        )
+        let account = select_account(&conn, Some(id))?;
+        let condition = format!("id_account = {}", account.id);
+
+        (account, condition)
     } else {
-        panic!("If you won't delete all items you must provide a valid ID!");
+        return error::invalid("If you won't delete all items you must provide a valid ID!");
     };
 
     if account.default && !delete_all {
-        println!("You can't delete the default account unless you delete all.");
-    } else {
-        match conn.execute(&query, []) {
-            Ok(0) => {
-                println!("Zero rows deleted!");
-                if delete_all {
-                    println!("Table 'accounts' is empty. Try 'wallet new --help'.");
-                }
-            }
-            Ok(n_rows) => println!("Successfully deleted {} rows!", n_rows),
-            Err(e) => {
-                utils::validate_tables(&format!("{}", e), "accounts");
+        return error::invalid("You can't delete the default account unless you delete all.");
+    }
+
+    let tx = conn.transaction()?;
+    let id_operation = journal::begin(&tx)?;
+
+    let mut images = Vec::new();
+    for id_account in journal::ids_where(&tx, "accounts", &condition)? {
+        images.push((id_account, journal::row_image(&tx, "accounts", id_account)?));
+    }
+
+    match tx.execute(&format!("DELETE FROM accounts WHERE {}", condition), [])? {
+        0 => {
+            output::message("Zero rows deleted!");
+            if delete_all {
+                output::message("Table 'accounts' is empty. Try 'wallet new --help'.");
             }
         }
+        n_rows => output::message(format!("Successfully deleted {} rows!", n_rows))
+    }
+
+    for (id_account, before) in images {
+        journal::record(&tx, id_operation, "accounts", id_account, before)?;
     }
 
-    conn.close().unwrap();
+    tx.commit()?;
+
     Ok(())
 }
 
 pub fn account_edit(
-            id: &str, opt_name: Option<&str>, opt_balance: Option<&str>
-        ) -> rusqlite::Result<()> {
-    let conn = Connection::open(DB_NAME)?;
+            id: &str, opt_name: Option<&str>, opt_balance: Option<&str>, opt_minimum: Option<&str>
+        ) -> error::Result<()> {
+    let mut conn = open_database()?;
 
     let mut account = select_account(&conn, Some(id))?;
-
-    if !account.exists() {
-        return Ok(());
-    } else {
-        println!("Account to update:\n{}", account);
-    }
+    let original = account.to_string();
 
     let mut value_received = false;
+    let mut diff = 0.0;
 
     if let Some(name) = opt_name {
         account.name = name.to_string();
@@ -274,123 +869,404 @@ pub fn account_edit(
                 new_balance
             },
             Err(_) => {
-                panic!("Invalid value for balance '{}'! Please enter a valid real number...", balance);
+                return error::invalid(format!("Invalid value for balance '{}'! Please enter a valid real number...", balance));
             }
         };
 
-        let diff = account.balance - original_balance;
+        diff = account.balance - original_balance;
         account.available += diff;
     }
 
+    if let Some(minimum) = opt_minimum {
+        match minimum.parse::<f64>() {
+            Ok(new_minimum) => {
+                account.minimum = new_minimum;
+                value_received = true;
+            }
+            Err(_) => {
+                return error::invalid(format!("Invalid value for minimum '{}'! Please enter a valid real number...", minimum));
+            }
+        }
+    }
+
     if !value_received {
-        println!("You must provide at least one valid argument to update!");
-        println!("The account will keep its values.");
+        return error::invalid("You must provide at least one valid argument to update! The account will keep its values.");
+    }
 
-        return Ok(());
+    output::message(format!("Account to update:\n{}", original));
+
+    let tx = conn.transaction()?;
+    let id_operation = journal::begin(&tx)?;
+
+    // Balance changes are kept in the history as adjustments.
+    if (diff * 100.0).round() != 0.0 {
+        let adjustment = Transaction {
+            message: "Balance adjustment".to_string(),
+            value: diff,
+            date: utils::today_julian_day(),
+            charged: true,
+            t_type: ADJUSTMENT,
+            id_account: account.id,
+            ..Transaction::empty()
+        };
+
+        insert_transaction(&tx, id_operation, &adjustment)?;
+        output::message(format!("Balance change recorded as an adjustment of {:+.2}.", diff));
     }
 
-    match update_account(&conn, &account) {
-        Ok(_) => println!("Successfully updated account data!"),
-        Err(e) => utils::validate_tables(&format!("{}", e), "accounts")
+    update_account(&tx, id_operation, &account)?;
+    output::message("Successfully updated account data!");
+
+    tx.commit()?;
+
+    output::message(format!("Resulting account:\n{}", account));
+
+    Ok(())
+}
+
+pub fn account_transfer(
+            value: f64, id_destination: &str, opt_id_source: Option<&str>
+        ) -> error::Result<()> {
+    let mut conn = open_database()?;
+
+    let mut source = select_account(&conn, opt_id_source)?;
+    let mut destination = select_account(&conn, Some(id_destination))?;
+
+    if source.id == destination.id {
+        return error::invalid("The source and the destination must be different accounts!");
+    } else if value < 0.01 {
+        return error::invalid("The value of a transfer must be at least one cent '0.01'!");
+    } else if value > source.available {
+        return Err(WalletError::InsufficientFunds(format!(
+            "The account '{}' has no money enough for this transfer!\nAvailable balance is {} and the transfer is {}.",
+            source.name, source.available, value
+        )));
+    } else if source.currency != destination.currency {
+        return error::invalid("Transfers between accounts with different currencies are not supported!");
     }
 
-    println!("Resulting account:\n{}", account);
+    let outgoing = Transaction {
+        message: format!("Transfer to '{}'", destination.name),
+        value: -value,
+        date: utils::today_julian_day(),
+        charged: true,
+        t_type: TRANSFER,
+        id_account: source.id,
+        ..Transaction::empty()
+    };
+    let incoming = Transaction {
+        message: format!("Transfer from '{}'", source.name),
+        value,
+        id_account: destination.id,
+        ..outgoing.clone()
+    };
+
+    let tx = conn.transaction()?;
+    let id_operation = journal::begin(&tx)?;
+
+    insert_transfer(&tx, id_operation, &outgoing, &incoming)?;
+
+    source.apply(&outgoing);
+    destination.apply(&incoming);
+
+    update_account(&tx, id_operation, &source)?;
+    update_account(&tx, id_operation, &destination)?;
+
+    tx.commit()?;
+
+    output::message(format!("Successfully transferred ${:.2}!", value));
+    output::message(format!("{}\n{}", source, destination));
 
     Ok(())
 }
 
 
 // Wallet 'new' subcommands are defined below.
-pub fn new_account(name: &str, balance: f64) -> rusqlite::Result<()> {
-    let conn = Connection::open(DB_NAME)?;
+pub fn new_account(
+            name: &str, balance: f64, credit: bool, opt_currency: Option<&str>
+        ) -> error::Result<()> {
+    let mut conn = open_database()?;
 
-    let exists_account = match conn.execute("SELECT * FROM accounts", []) {
-        Ok(_) => { false }
-        Err(_) => { true }
-    };
+    let n_accounts: i64 = conn.query_row("SELECT COUNT(*) FROM accounts", [], |row| row.get(0))?;
 
-    let default: i64 = if exists_account { 0 } else { 1 };
+    let default: i64 = if n_accounts > 0 { 0 } else { 1 };
     let int_balance: i64 = (balance * 100.0).round() as i64;
 
-    let result = conn.execute(
-        "INSERT INTO accounts (name, balance, available, is_default) VALUES (?1, ?2, ?3, ?4)",
-        params![name, int_balance, int_balance, default]
-    );
-
-    match result {
-        Ok(_) => {
-            println!("Successfully created new account!");
-            println!("New account {} - ${:.2}", name, balance);
-        },
-        Err(e) => {
-            utils::validate_tables(&format!("{}", e), "accounts");
-        }
-    }
+    let tx = conn.transaction()?;
+    let id_operation = journal::begin(&tx)?;
+
+    tx.execute(
+        "INSERT INTO accounts (name, balance, available, is_default, opening_balance, is_credit, currency, created)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![name, int_balance, int_balance, default, int_balance, credit, opt_currency, utils::today_julian_day()]
+    )?;
+    journal::record(&tx, id_operation, "accounts", tx.last_insert_rowid(), None)?;
+
+    tx.commit()?;
+
+    output::message("Successfully created new account!");
+    output::message(format!("New account {} - ${:.2}", name, balance));
 
-    conn.close().unwrap();
     Ok(())
 }
 
 pub fn new_transaction(
             message: &str, value: f64, t_type: i64, charged: bool, force_price: bool,
-            opt_id_account: Option<&str>
-        ) -> rusqlite::Result<()> {
-    let conn = Connection::open(DB_NAME)?;
+            opt_id_account: Option<&str>, opt_payee: Option<&str>
+        ) -> error::Result<()> {
+    let mut conn = open_database()?;
 
     let mut account = select_account(&conn, opt_id_account)?;
 
+    let opt_payee = match opt_payee {
+        Some(text) => Some(payees::payee_name(&conn, text)?),
+        None => None
+    };
+
     if value < 0.01 {
-        println!("The value of a transaction must be at least one cent '0.01'!");
+        return error::invalid("The value of a transaction must be at least one cent '0.01'!");
+    } else if t_type == EXPENSE && value > account.available && !force_price {
+        return Err(WalletError::InsufficientFunds(format!(
+            "The account '{}' has no money enough for this purchase!\nAvailable balance is {} and the purchase price is {}.",
+            account.name, account.available, value
+        )));
+    }
 
-        return Ok(());
-    } else if t_type == 0 && value > account.available && !force_price {
-        println!("The account '{}' has no money enough for this purchase!", account.name);
-        println!("Available balance is {} and the purchase price is {}.", account.available, value);
+    let transaction = Transaction {
+        message: message.to_string(),
+        value,
+        date: utils::today_julian_day(),
+        charged,
+        t_type,
+        id_account: account.id,
+        payee: opt_payee,
+        ..Transaction::empty()
+    };
 
-        return Ok(());
+    let tx = conn.transaction()?;
+    let id_operation = journal::begin(&tx)?;
+
+    insert_transaction(&tx, id_operation, &transaction)?;
+
+    account.apply(&transaction);
+    update_account(&tx, id_operation, &account)?;
+
+    tx.commit()?;
+
+    output::message("Successfully updated account data!");
+
+    Ok(())
+}
+
+// Scheduled payments, and recurring incomings with 'income', billed every
+// 'every' months from the date. Forecasts count on them.
+pub fn new_payment(
+            name: &str, price: f64, str_date: &str, every: i64, income: bool, opt_id_account: Option<&str>
+        ) -> error::Result<()> {
+    let billing_date = utils::parse_date_arg(str_date)?;
+
+    if price < 0.01 {
+        return error::invalid("The price of a payment must be at least one cent '0.01'!");
+    } else if every < 0 {
+        return error::invalid("The months between billings can't be negative! Use 0 for a payment billed once.");
     }
 
-    let int_value: i64 = (value * 100.0).round() as i64;
-    let int_charged: i64 = if charged { 1 } else { 0 };
+    let mut conn = open_database()?;
 
-    let julian_date: i64;
-    {
-        let local_date: DateTime<Local> = Local::now();
-        let str_date = local_date.to_rfc3339().get(0..10).unwrap().to_string();
-        
-        let parse_format = format_description::parse("[year]-[month]-[day]").unwrap();
-        let date = Date::parse(&str_date, &parse_format).unwrap();
+    let account = select_account(&conn, opt_id_account)?;
+
+    let tx = conn.transaction()?;
+    let id_operation = journal::begin(&tx)?;
+
+    tx.execute(
+        "INSERT INTO payments (name, price, billing_date, priodicity, id_account, is_income)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![name, (price * 100.0).round() as i64, billing_date, every, account.id, income]
+    )?;
+    journal::record(&tx, id_operation, "payments", tx.last_insert_rowid(), None)?;
+
+    tx.commit()?;
+
+    output::message(format!(
+        "Successfully created {} '{}' of ${:.2} in account '{}'!",
+        if income { "recurring incoming" } else { "payment" }, name, price, account.name
+    ));
+
+    Ok(())
+}
 
-        julian_date = date.to_julian_day() as i64;
+// Purchases paid in monthly installments without interest (MSI). The date is
+// the day the next installment is billed.
+pub fn new_msi(
+            name: &str, price: f64, installments: i64, str_date: &str, paid: i64, opt_id_account: Option<&str>
+        ) -> error::Result<()> {
+    let billing_date = utils::parse_date_arg(str_date)?;
+
+    if price < 0.01 {
+        return error::invalid("The price of a purchase must be at least one cent '0.01'!");
+    } else if installments < 1 || paid < 0 || paid >= installments {
+        return error::invalid("The purchase needs at least one installment and fewer installments paid than installments!");
     }
 
-    let result = conn.execute(
-        "INSERT INTO transactions (message, value, date, charged, t_type, id_account)
+    let mut conn = open_database()?;
+
+    let account = select_account(&conn, opt_id_account)?;
+
+    let tx = conn.transaction()?;
+    let id_operation = journal::begin(&tx)?;
+
+    tx.execute(
+        "INSERT INTO msi_purchases (name, price, installments, months_paid, id_account, billing_date)
          VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-        params![message, int_value, julian_date, int_charged, t_type, account.id]
-    );
-
-    match result {
-        Ok(_) => {
-            if t_type == 0 {
-                account.available -= value;
-                if charged {
-                    account.balance -= value;
-                }
-            } else if t_type == 1{
-                account.available += value;
-                account.balance += value;
-            }
+        params![name, (price * 100.0).round() as i64, installments, paid, account.id, billing_date]
+    )?;
+    journal::record(&tx, id_operation, "msi_purchases", tx.last_insert_rowid(), None)?;
 
-            match update_account(&conn, &account) {
-                Ok(_) => println!("Successfully updated account data!"),
-                Err(e) => utils::validate_tables(&format!("{}", e), "accounts")
-            }
-        },
-        Err(e) => {
-            utils::validate_tables(&format!("{}", e), "accounts");
-        }
+    tx.commit()?;
+
+    output::message(format!(
+        "Successfully created '{}' of ${:.2} in {} installments of ${:.2} in account '{}'!",
+        name, price, installments, price / installments as f64, account.name
+    ));
+
+    Ok(())
+}
+
+// Purchases planned but not made yet.
+pub fn new_queued(message: &str, price: f64, opt_id_account: Option<&str>) -> error::Result<()> {
+    if price < 0.01 {
+        return error::invalid("The price of a purchase must be at least one cent '0.01'!");
     }
 
+    let mut conn = open_database()?;
+
+    let account = select_account(&conn, opt_id_account)?;
+
+    let tx = conn.transaction()?;
+    let id_operation = journal::begin(&tx)?;
+
+    tx.execute(
+        "INSERT INTO queued_purchases (message, price, id_account) VALUES (?1, ?2, ?3)",
+        params![message, (price * 100.0).round() as i64, account.id]
+    )?;
+    journal::record(&tx, id_operation, "queued_purchases", tx.last_insert_rowid(), None)?;
+
+    tx.commit()?;
+
+    output::message(format!("Successfully queued '{}' of ${:.2} in account '{}'!", message, price, account.name));
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::path::PathBuf;
+    use std::process;
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("wallet-{}-{}.db3", name, process::id()))
+    }
+
+    #[test]
+    fn balance_edits_are_recorded_as_adjustments() {
+        let conn = memory_database();
+
+        new_account("Bank", 100.0, false, None).unwrap();
+        account_edit("1", None, Some("80.25"), None).unwrap();
+        account_edit("1", Some("Savings"), None, None).unwrap();
+
+        let adjustments: Vec<(i64, i64, i64)> = conn
+            .prepare("SELECT value, t_type, charged FROM transactions").unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))).unwrap()
+            .collect::<rusqlite::Result<_>>().unwrap();
+        assert_eq!(adjustments, vec![(-1975, ADJUSTMENT, 1)]);
+
+        let (balance, available): (i64, i64) = conn
+            .query_row("SELECT balance, available FROM accounts", [], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+        assert_eq!((balance, available), (8025, 8025));
+    }
+
+    #[test]
+    fn opening_balances_count_from_the_day_accounts_are_opened() {
+        let conn = memory_database();
+        let today = utils::today_julian_day();
+
+        new_account("Bank", 100.0, false, None).unwrap();
+        new_account("Cash", 20.0, false, None).unwrap();
+        new_transaction("Books", 30.0, EXPENSE, true, false, Some("1"), None).unwrap();
+        conn.execute("UPDATE transactions SET date = ?1, charge_date = ?2", params![today - 10, today - 5]).unwrap();
+
+        let as_of = |julian_date: i64| -> Vec<(f64, f64)> {
+            select_accounts_as_of(&conn, julian_date).unwrap().iter()
+                .map(|account| (account.balance, account.available))
+                .collect()
+        };
+
+        assert_eq!(as_of(today - 11), vec![(0.0, 0.0), (0.0, 0.0)]);
+        assert_eq!(as_of(today - 10), vec![(100.0, 70.0), (0.0, 0.0)]);
+        assert_eq!(as_of(today - 5), vec![(70.0, 70.0), (0.0, 0.0)]);
+        assert_eq!(as_of(today), vec![(70.0, 70.0), (20.0, 20.0)]);
+    }
+
+    #[test]
+    fn accounts_get_their_creation_day_when_migrated() {
+        let conn = memory_database();
+
+        new_account("Bank", 100.0, false, None).unwrap();
+        new_account("Cash", 20.0, false, None).unwrap();
+        new_transaction("Tea", 2.0, EXPENSE, true, false, Some("2"), None).unwrap();
+        conn.execute("UPDATE transactions SET date = 2461000", []).unwrap();
+        conn.execute("UPDATE operations SET date = 2461100", []).unwrap();
+        conn.execute("DELETE FROM operation_rows WHERE table_name = 'accounts' AND row_id = 2", []).unwrap();
+
+        conn.execute("ALTER TABLE accounts DROP COLUMN created", []).unwrap();
+        migrate_database(&conn).unwrap();
+
+        let created: Vec<i64> = conn.prepare("SELECT created FROM accounts ORDER BY id_account").unwrap()
+            .query_map([], |row| row.get(0)).unwrap()
+            .collect::<rusqlite::Result<_>>().unwrap();
+        assert_eq!(created, vec![2461100, 2461000]);
+    }
+
+    #[test]
+    fn encrypted_export_needs_the_passphrase() {
+        let path = temp_path("encrypted-export");
+        let _ = fs::remove_file(&path);
+
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE accounts (name TEXT)", []).unwrap();
+        conn.execute("INSERT INTO accounts (name) VALUES ('Bank')", []).unwrap();
+
+        export_encrypted(&conn, path.to_str().unwrap(), "secret").unwrap();
+        assert!(is_encrypted(path.to_str().unwrap()).unwrap());
+
+        let wrong = Connection::open(&path).unwrap();
+        wrong.pragma_update(None, "key", "other").unwrap();
+        assert!(!is_readable(&wrong));
+
+        let right = Connection::open(&path).unwrap();
+        right.pragma_update(None, "key", "secret").unwrap();
+        let name: String = right.query_row("SELECT name FROM accounts", [], |row| row.get(0)).unwrap();
+        assert_eq!(name, "Bank");
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn plain_and_missing_files_are_not_encrypted() {
+        let path = temp_path("plain");
+        let _ = fs::remove_file(&path);
+        assert!(!is_encrypted(path.to_str().unwrap()).unwrap());
+
+        let conn = Connection::open(&path).unwrap();
+        conn.execute("CREATE TABLE accounts (name TEXT)", []).unwrap();
+        conn.close().unwrap();
+        assert!(!is_encrypted(path.to_str().unwrap()).unwrap());
+
+        fs::remove_file(&path).unwrap();
+    }
+}